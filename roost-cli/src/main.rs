@@ -1,12 +1,28 @@
-//mod repl_helper;
+mod repl_helper;
 
-//use repl_helper::ReplHelper;
-use roost::{interpreter::Interpreter, lexer::Lexer, parser::Parser};
-//use rustyline::{error::ReadlineError, Config, Editor};
+use repl_helper::ReplHelper;
+use roost::{
+    checker,
+    error::SourceMap,
+    interpreter::{
+        modules::ProgramArena,
+        value::{Scope, Value},
+        Interpreter,
+    },
+    lexer::Lexer,
+    parser::Parser,
+    vm::{self, Vm},
+};
+use rustyline::{error::ReadlineError, Config, Editor};
 use std::{
-    fs::File,
+    env,
+    fs::{self, File},
     io::{self, Read},
+    mem,
+    path::{Path, PathBuf},
     process,
+    rc::Rc,
+    thread,
     time::Instant,
 };
 
@@ -26,17 +42,32 @@ struct Roost {
     /// Measure and display the time of execution
     #[clap(short, long)]
     time: bool,
+
+    /// Compile the program to bytecode and run it on the VM, which supports a subset
+    /// of the language and refuses programs using anything else
+    #[clap(long)]
+    vm: bool,
+
+    /// Only check the type annotations, without running the program
+    #[clap(long)]
+    check: bool,
+
+    /// The number of function calls which can be nested
+    #[clap(long, default_value_t = 1000)]
+    max_depth: usize,
 }
 
 macro_rules! print_error {
-    ($error:expr, $filename:expr, $code:expr $(,)?) => {
-        let lines: Vec<&str> = $code.split('\n').collect();
+    ($error:expr, $sources:expr $(,)?) => {
+        let source = $error.source();
+        let lines: Vec<&str> = $sources.code(source).split('\n').collect();
+        let line = |idx: usize| lines.get(idx).copied().unwrap_or_default();
 
         let line1 = if $error.span.start.line > 1 {
             format!(
                 "\n \x1b[90m{: >3} | \x1b[0m{}",
                 $error.span.start.line - 1,
-                lines[$error.span.start.line - 2]
+                line($error.span.start.line - 2)
             )
         } else {
             String::new()
@@ -44,13 +75,13 @@ macro_rules! print_error {
         let line2 = format!(
             " \x1b[90m{: >3} | \x1b[0m{}",
             $error.span.start.line,
-            lines[$error.span.start.line - 1]
+            line($error.span.start.line.saturating_sub(1))
         );
         let line3 = if $error.span.start.line < lines.len() {
             format!(
                 "\n \x1b[90m{: >3} | \x1b[0m{}",
                 $error.span.start.line + 1,
-                lines[$error.span.start.line]
+                line($error.span.start.line)
             )
         } else {
             String::new()
@@ -64,15 +95,31 @@ macro_rules! print_error {
             {
                 "^".repeat($error.span.end.index - $error.span.start.index)
             } else {
-                "^".repeat(lines[$error.span.start.line - 1].len() - $error.span.start.column + 1)
-                    + "..."
+                "^".repeat(
+                    (line($error.span.start.line.saturating_sub(1)).len() + 1)
+                        .saturating_sub($error.span.start.column),
+                ) + "..."
             },
         );
 
+        let stack: String = $error
+            .stack
+            .iter()
+            .map(|frame| {
+                format!(
+                    "\n  \x1b[90mat\x1b[0m {} \x1b[90m({}:{}:{})\x1b[0m",
+                    frame.name.as_deref().unwrap_or("<anonymous>"),
+                    $sources.name(frame.span.source()),
+                    frame.span.start.line,
+                    frame.span.start.column,
+                )
+            })
+            .collect();
+
         eprintln!(
-            "\x1b[1;36m{:?}\x1b[39m at {}:{}:{}\x1b[0m\n{}\n{}\n{}{}\n\n\x1b[1;31m{}\x1b[0m\n",
+            "\x1b[1;36m{:?}\x1b[39m at {}:{}:{}\x1b[0m\n{}\n{}\n{}{}\n\n\x1b[1;31m{}\x1b[0m{}\n",
             $error.kind,
-            $filename,
+            $sources.name(source),
             $error.span.start.line,
             $error.span.start.column,
             line1,
@@ -80,24 +127,45 @@ macro_rules! print_error {
             marker,
             line3,
             $error.message,
+            stack,
         );
     };
 }
 
+/// Prints an error and the errors related to it
+macro_rules! print_errors {
+    ($error:expr, $sources:expr $(,)?) => {
+        let error = $error;
+        print_error!(error, $sources);
+        for error in &error.related {
+            print_error!(error, $sources);
+        }
+    };
+}
+
 macro_rules! exit {
     ($($arg:tt)*) => {{
-        print_error!($($arg)*);
+        print_errors!($($arg)*);
         process::exit(1);
     }};
 }
 
+/// The stack size of the thread running the program, which lets the tree walking
+/// interpreter reach the default maximum call depth even in debug builds
+const STACK_SIZE: usize = 256 * 1024 * 1024;
+
 fn main() {
     use clap::Parser;
     let cli = Roost::parse();
-    match cli.file {
-        Some(_) => run_file(cli),
-        None => todo!("REPL, provide file instead"),
-    }
+    thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(|| match cli.file {
+            Some(_) => run_file(cli),
+            None => run_repl(cli.max_depth),
+        })
+        .unwrap()
+        .join()
+        .unwrap();
 }
 
 fn run_file(cli: Roost) {
@@ -123,20 +191,32 @@ fn run_file(cli: Roost) {
     let end_read = start_total.elapsed();
     let start = Instant::now();
 
-    let nodes = match Parser::new(Lexer::new(&code)).parse() {
+    let module_trees = ProgramArena::new();
+    let mut sources = SourceMap::new();
+    let source = sources.add(filename.as_str(), code.as_str());
+    let nodes = match Parser::new(Lexer::new(&code, source)).parse() {
         Ok(nodes) => nodes,
         Err(errors) => {
             for error in errors {
-                print_error!(error, filename, code);
+                print_error!(error, sources);
             }
             process::exit(1);
         }
     };
+    if let Err(errors) = checker::check(&nodes) {
+        for error in errors {
+            print_error!(error, sources);
+        }
+        process::exit(1);
+    }
+    if cli.check {
+        return;
+    }
 
     let end_parse = start.elapsed();
     let start = Instant::now();
 
-    Interpreter::new(&nodes, io::stdout(), io::stderr(), |code| {
+    let exit_callback = |code| {
         let end_run = start.elapsed();
         let end = start_total.elapsed();
         if cli.time {
@@ -149,8 +229,45 @@ fn run_file(cli: Roost) {
             );
         }
         process::exit(code);
-    }).run(true)
-    .unwrap_or_else(|e| exit!(e, filename, code));
+    };
+
+    let bytecode = match cli.vm {
+        true => match vm::compile(&nodes) {
+            Ok(bytecode) => Some(bytecode),
+            Err(e) => {
+                eprintln!(
+                    "\x1b[31mThe VM does not support {} ({}:{}:{}), run the program without --vm instead\x1b[0m",
+                    e.feature,
+                    filename,
+                    e.span.start.line,
+                    e.span.start.column,
+                );
+                process::exit(1);
+            }
+        },
+        false => None,
+    };
+    match &bytecode {
+        Some(bytecode) => {
+            if let Err(e) = Vm::new(bytecode, io::stdout(), io::stderr(), exit_callback)
+                .with_max_depth(cli.max_depth)
+                .run()
+            {
+                exit!(e, sources);
+            }
+        }
+        None => {
+            let mut interpreter =
+                Interpreter::new(&nodes, io::stdout(), io::stderr(), exit_callback)
+                    .with_max_depth(cli.max_depth)
+                    .with_path(filename)
+                    .with_module_trees(&module_trees)
+                    .with_sources(sources);
+            if let Err(e) = interpreter.run(true) {
+                exit!(e, interpreter.sources());
+            }
+        }
+    }
 
     let end_run = start.elapsed();
     let end = start_total.elapsed();
@@ -165,22 +282,11 @@ fn run_file(cli: Roost) {
     }
 }
 
-// TODO: save filename in Location for correct errors
-//macro_rules! repl_num {
-//    ($error:ident) => {{
-//        let num = $error.start.filename.split("repl-").collect::<Vec<&str>>()[1];
-//        let num = &num[..num.len() - 1];
-//        num.parse::<usize>().unwrap()
-//    }};
-//}
-
-// TODO: REPL
-/*
-fn run_repl() {
-    let mut trees = vec![];
-    let trees_ptr = &mut trees as *mut Vec<Statements>;
-    let mut global_scope = HashMap::new();
-    let mut inputs = vec![];
+fn run_repl(max_depth: usize) {
+    // the trees of all inputs and imported modules, values in the global scope can reference
+    // them. It is declared first, so that the global scope is dropped before it.
+    let trees = ProgramArena::new();
+    let global_scope = Scope::default();
     let mut rl = Editor::with_config(
         Config::builder()
             .completion_type(rustyline::CompletionType::List)
@@ -188,80 +294,90 @@ fn run_repl() {
             .indent_size(4)
             .build(),
     )
-    .unwrap();
-    rl.set_helper(Some(ReplHelper::new(global_scope.clone())));
-    match std::env::var("HOME") {
-        Ok(path) => {
-            let _ = rl.load_history(&format!("{}/.roost_history", path));
-        }
-        Err(_) => {}
+    .unwrap_or_else(|e| {
+        eprintln!("\x1b[31mCould not start the REPL: {}\x1b[0m", e);
+        process::exit(2);
+    });
+    rl.set_helper(Some(ReplHelper::new(Rc::clone(&global_scope))));
+    let history_path = history_path();
+    if let Some(path) = &history_path {
+        let _ = rl.load_history(path);
     }
+
+    let mut sources = SourceMap::new();
+    let mut input_count = 0;
     loop {
         match rl.readline(">> ") {
             Ok(line) => {
                 rl.add_history_entry(&line);
-                inputs.push(line.clone());
-                if line.chars().all(|char| [' ', '\t', '\r'].contains(&char)) {
+                if line.trim().is_empty() {
                     continue;
                 }
+                input_count += 1;
+                let source = sources.add(format!("<repl-{}>", input_count), line.as_str());
 
-                let nodes = match Parser::new(Lexer::new(&line)).parse() {
+                let nodes = match Parser::new(Lexer::new(&line, source)).parse() {
                     Ok(nodes) => nodes,
                     Err(errors) => {
                         for error in errors {
-                            print_error!(
-                                error,
-                                // TODO: save filename in Location for correct errors
-                                format!("<repl-{}>", inputs.len()),
-                                inputs[inputs.len() - 1],
-                            );
+                            print_error!(error, sources);
                         }
                         continue;
                     }
                 };
-                unsafe { (*trees_ptr).push(nodes) }
-                let nodes = trees.last().unwrap();
+                if let Err(errors) = checker::check(&nodes) {
+                    for error in errors {
+                        print_error!(error, sources);
+                    }
+                    continue;
+                }
+                let nodes = trees.add(nodes);
 
-                let mut interpreter =
-                    Interpreter::new(&nodes, io::stdout(), io::stderr(), |code| {
-                        if let Ok(path) = env::var("XDG_STATE_HOME") {
-                            let _ = rl.save_history(&Path::new(&path).join("roost/history"));
-                        } else if let Ok(path) = env::var("HOME") {
-                            let _ = rl.save_history(&Path::new(&path).join(".local/state/roost/history"));
-                        }
-                        process::exit(code);
-                    });
-                interpreter.scopes.push(global_scope.clone());
-                interpreter.scope_idx += 1;
+                let mut interpreter = Interpreter::new(nodes, io::stdout(), io::stderr(), |code| {
+                    save_history(&mut rl, &history_path);
+                    process::exit(code);
+                })
+                .with_max_depth(max_depth)
+                .with_module_trees(&trees)
+                .with_sources(mem::take(&mut sources));
+                interpreter.scopes.push(Rc::clone(&global_scope));
                 match interpreter.run(false) {
                     Ok(result) => {
                         if let Some(val) = result.value {
-                            println!("{:?}", val.borrow());
+                            if *val.borrow() != Value::Null {
+                                println!("{:?}", val.borrow());
+                            }
                         }
                     }
                     Err(error) => {
-                        print_error!(
-                            error,
-                            // TODO: save filename in Location for correct errors
-                            format!("<repl-{}>", inputs.len()),
-                            inputs[inputs.len() - 1]
-                        );
-                        continue;
+                        print_errors!(error, interpreter.sources());
                     }
                 }
-                mem::swap(&mut global_scope, &mut interpreter.scopes[1]);
-                rl.set_helper(Some(ReplHelper::new(global_scope.clone())));
+                sources = interpreter.into_sources();
             }
             Err(ReadlineError::Eof) => break,
             Err(ReadlineError::Interrupted) => continue,
-            Err(_) => std::process::exit(1),
+            Err(_) => process::exit(1),
+        }
+    }
+    save_history(&mut rl, &history_path);
+}
+
+fn history_path() -> Option<PathBuf> {
+    match (env::var("XDG_STATE_HOME"), env::var("HOME")) {
+        (Ok(path), _) if !path.is_empty() => Some(Path::new(&path).join("roost/history")),
+        (_, Ok(path)) if !path.is_empty() => {
+            Some(Path::new(&path).join(".local/state/roost/history"))
         }
+        _ => None,
     }
-    match std::env::var("HOME") {
-        Ok(path) => {
-            let _ = rl.save_history(&format!("{}/.roost_history", path));
+}
+
+fn save_history(rl: &mut Editor<ReplHelper<'_>>, path: &Option<PathBuf>) {
+    if let Some(path) = path {
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
         }
-        Err(_) => {}
+        let _ = rl.save_history(path);
     }
 }
-*/
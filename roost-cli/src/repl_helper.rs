@@ -1,4 +1,4 @@
-use roost::interpreter::value::Value;
+use roost::interpreter::value::Scope;
 use rustyline::{
     completion::Completer,
     highlight::Highlighter,
@@ -6,7 +6,7 @@ use rustyline::{
     validate::{MatchingBracketValidator, Validator},
     Helper,
 };
-use std::{collections::HashMap, io::Cursor};
+use std::io::Cursor;
 use syntect::{
     easy::HighlightLines,
     highlighting::{Theme, ThemeSet},
@@ -14,16 +14,16 @@ use syntect::{
     util::LinesWithEndings,
 };
 
-pub struct ReplHelper {
-    global_scope: HashMap<String, Value>,
+pub struct ReplHelper<'tree> {
+    global_scope: Scope<'tree>,
     brackets: MatchingBracketValidator,
     syntaxes: SyntaxSet,
     theme: Theme,
 }
 
-impl ReplHelper {
-    pub fn new(global_scope: HashMap<String, Value>) -> Self {
-        return Self {
+impl<'tree> ReplHelper<'tree> {
+    pub fn new(global_scope: Scope<'tree>) -> Self {
+        Self {
             global_scope,
             brackets: MatchingBracketValidator::new(),
             syntaxes: {
@@ -38,17 +38,18 @@ impl ReplHelper {
                 );
                 builder.build()
             },
+            // fall back to a bundled theme when `make pull` has not been run yet
             theme: ThemeSet::load_from_reader(&mut Cursor::new(include_str!(
                 "res/one-dark.tmTheme"
             )))
-            .unwrap(),
-        };
+            .unwrap_or_else(|_| ThemeSet::load_defaults().themes["base16-ocean.dark"].clone()),
+        }
     }
 }
 
-impl Helper for ReplHelper {}
+impl Helper for ReplHelper<'_> {}
 
-impl Completer for ReplHelper {
+impl Completer for ReplHelper<'_> {
     type Candidate = String;
 
     fn complete(
@@ -73,27 +74,46 @@ impl Completer for ReplHelper {
 
         let mut completions = vec![
             String::from("var"),
+            String::from("const"),
             String::from("true"),
             String::from("false"),
             String::from("null"),
             String::from("if"),
             String::from("else"),
             String::from("fun"),
+            String::from("static"),
+            String::from("class"),
+            String::from("enum"),
+            String::from("extends"),
+            String::from("super"),
             String::from("loop"),
             String::from("while"),
             String::from("for"),
             String::from("in"),
             String::from("return"),
+            String::from("yield"),
             String::from("break"),
             String::from("continue"),
+            String::from("try"),
+            String::from("catch"),
+            String::from("finally"),
+            String::from("match"),
+            String::from("import"),
+            String::from("as"),
             String::from("print"),
             String::from("printl"),
+            String::from("eprint"),
+            String::from("eprintl"),
             String::from("typeOf"),
+            String::from("assert"),
+            String::from("throw"),
             String::from("exit"),
+            String::from("debug"),
+            String::from("freeze"),
             String::from("answer"),
         ];
-        for name in self.global_scope.keys() {
-            completions.push(name.clone());
+        for name in self.global_scope.borrow().keys() {
+            completions.push(name.to_string());
         }
         completions = completions
             .iter()
@@ -106,11 +126,11 @@ impl Completer for ReplHelper {
             })
             .collect();
 
-        return Ok((name_pos, completions));
+        Ok((name_pos, completions))
     }
 }
 
-impl Hinter for ReplHelper {
+impl Hinter for ReplHelper<'_> {
     type Hint = String;
 
     fn hint(&self, line: &str, pos: usize, ctx: &rustyline::Context<'_>) -> Option<Self::Hint> {
@@ -129,26 +149,29 @@ impl Hinter for ReplHelper {
                 hint.remove(0);
                 completion_pos += 1;
             }
-            return Some(hint);
+            Some(hint)
         } else {
-            return None;
-        };
+            None
+        }
     }
 }
 
-impl Highlighter for ReplHelper {
+impl Highlighter for ReplHelper<'_> {
     fn highlight<'l>(&self, line: &'l str, _: usize) -> std::borrow::Cow<'l, str> {
         let mut h = HighlightLines::new(
             self.syntaxes.find_syntax_by_name("roost").unwrap(),
             &self.theme,
         );
         let mut out = String::new();
-        for line in LinesWithEndings::from(line) {
-            let ranges = h.highlight(line, &self.syntaxes);
+        for source_line in LinesWithEndings::from(line) {
+            let ranges = match h.highlight_line(source_line, &self.syntaxes) {
+                Ok(ranges) => ranges,
+                Err(_) => return std::borrow::Cow::Borrowed(line),
+            };
             let escaped = syntect::util::as_24_bit_terminal_escaped(&ranges[..], false);
             out += &escaped;
         }
-        return std::borrow::Cow::Owned(out);
+        std::borrow::Cow::Owned(out)
     }
 
     fn highlight_prompt<'b, 's: 'b, 'p: 'b>(
@@ -172,15 +195,15 @@ impl Highlighter for ReplHelper {
     }
 
     fn highlight_char(&self, _: &str, _: usize) -> bool {
-        return true;
+        true
     }
 }
 
-impl Validator for ReplHelper {
+impl Validator for ReplHelper<'_> {
     fn validate(
         &self,
         ctx: &mut rustyline::validate::ValidationContext,
     ) -> rustyline::Result<rustyline::validate::ValidationResult> {
-        return self.brackets.validate(ctx);
+        self.brackets.validate(ctx)
     }
 }
@@ -1,34 +1,51 @@
 use ntest::timeout;
 use roost::{
-    interpreter::{Exit, Interpreter},
+    checker,
+    error::{ErrorKind, SourceId, SourceMap},
+    interpreter::{modules::ProgramArena, Interpreter},
     lexer::Lexer,
+    nodes::Program,
     parser::Parser,
+    vm::{self, Vm},
+};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Cursor},
 };
-use std::io::Cursor;
-
-struct SimpleExit;
-impl Exit for SimpleExit {
-    fn exit(&mut self, code: i32) {
-        std::process::exit(code);
-    }
-}
 
 fn test_code(code: &str, expected: &str) {
     let mut out = Cursor::new(vec![]);
 
-    match Interpreter::new_run(
-        match Parser::new_parse(Lexer::new(&code.to_owned(), String::from("test"))) {
-            Ok(node) => node,
-            Err(e) => panic!("{:?}", e),
-        },
-        &mut out,
-        SimpleExit,
-    ) {
+    let program = match Parser::new(Lexer::new(code, SourceId::default())).parse() {
+        Ok(program) => program,
+        Err(e) => panic!("{:?}", e),
+    };
+    match Interpreter::new(&program, &mut out, io::sink(), |code| {
+        std::process::exit(code)
+    })
+    .run(true)
+    {
         Ok(_) => {}
-        Err(e) => panic!("{}", e),
+        Err(e) => panic!("{:?}", e),
     };
 
     assert_eq!(std::str::from_utf8(out.get_ref()).unwrap(), expected);
+    if let Some(out) = run_vm(&program) {
+        assert_eq!(out.unwrap(), expected, "the VM produced different output");
+    }
+}
+
+/// Runs the program on the VM and returns its output or the `Debug` output of its error,
+/// `None` if the program cannot be compiled
+fn run_vm(program: &Program) -> Option<Result<String, String>> {
+    let bytecode = vm::compile(program).ok()?;
+    let mut out = Cursor::new(vec![]);
+    let res = Vm::new(&bytecode, &mut out, io::sink(), |_| {}).run();
+    Some(match res {
+        Ok(_) => Ok(String::from_utf8(out.into_inner()).unwrap()),
+        Err(e) => Err(format!("{:?}", e)),
+    })
 }
 
 #[test]
@@ -164,3 +181,1147 @@ fn comments() {
         "abc",
     )
 }
+
+#[test]
+fn closures() {
+    test_code(
+        r#"
+    fun counter() {
+        var count = 0
+        return fun () { count += 1; count }
+    }
+    var a = counter()
+    var b = counter()
+    print(a(), ''); print(a(), ''); print(b(), '')
+
+    fun adder(n) return fun (x) x + n
+    print(adder(5)(10), '')
+
+    var callbacks = []
+    for (i in 0..3) callbacks.push(fun () i * 10)
+    for (cb in callbacks) print(cb(), '')
+
+    var x = 'global'
+    fun show() x
+    fun shadow() { var x = 'local'; show() }
+    print(shadow())
+    "#,
+        "1 2 1 15 0 10 20 global",
+    )
+}
+
+#[test]
+fn maps() {
+    test_code(
+        r#"
+    var m = ['a': 1, 2: 'two']
+    m['b'] = 3
+    m['a'] += 10
+    print(m, m[2.0], '')
+    for (key in m) print(key, '')
+    print(m.keys(), m.values(), m.has('b'), m.remove('b'), m.has('b'), '')
+    var empty = [:]
+    print(empty, typeOf(empty), !empty)
+    "#,
+        "[a: 11, 2: two, b: 3] two a 2 b [a, 2, b] [11, two, 3] true 3 false [:] map true",
+    );
+    test_code(
+        r#"
+    var m = ['a': 1, 'b': 2]
+    for (key in m) { m.remove(key); m[key + key] = 0 }
+    print(m, '')
+    var xs = [1, 2]
+    for (x in xs) xs.push(x * 10)
+    print(xs, [k for (k in m) if (m.remove(k) == 0)], m)
+    "#,
+        "[aa: 0, bb: 0] [1, 2, 10, 20] [aa, bb] [:]",
+    );
+}
+
+#[test]
+fn imports() {
+    let files = HashMap::from([
+        (
+            "lib/math.ro",
+            "print('loading ')\nvar pi = 3\nfun square(x) x * x\nimport './helper.ro'\nfun twice(x) helper.double(x)\nfun later() { import 'helper.ro' as h; h.double(5) }",
+        ),
+        ("lib/helper.ro", "fun double(x) x * 2"),
+        ("cycle/a.ro", "import 'b.ro'"),
+        ("cycle/b.ro", "import './a.ro'"),
+        ("back.ro", "import 'main.ro'"),
+        ("broken.ro", "print(0x)\nprint(0b2)"),
+    ]);
+    let run = |code: &str| {
+        let mut out = Cursor::new(vec![]);
+        let module_trees = ProgramArena::new();
+        let program = Parser::new(Lexer::new(code, SourceId::default()))
+            .parse()
+            .unwrap();
+        let res = Interpreter::new(&program, &mut out, io::sink(), |code| {
+            std::process::exit(code)
+        })
+        .with_path("main.ro")
+        .with_module_trees(&module_trees)
+        .with_loader(|path| {
+            files
+                .get(path.to_str().unwrap())
+                .map(|code| code.to_string())
+                .ok_or_else(|| "not found".to_string())
+        })
+        .run(true)
+        .map(|_| ());
+        res.map(|_| String::from_utf8(out.into_inner()).unwrap())
+    };
+
+    assert_eq!(
+        run(r#"
+    import 'lib/math.ro'
+    import './lib/../lib/math.ro' as m
+    print(math.square(math.pi), m.twice(4), math.later(), typeOf(math))
+    try { import 'cycle/a.ro' } catch (e) { print(' ' + e.message) }
+    try { import 'back.ro' } catch (e) { print(', ' + e.message) }
+    "#)
+        .unwrap(),
+        "loading 9 8 10 object Circular import: cycle/a.ro -> cycle/b.ro -> cycle/a.ro, \
+        Circular import: main.ro -> back.ro -> main.ro",
+    );
+
+    let error = run("import 'broken.ro'").unwrap_err();
+    assert_eq!(
+        [&*error]
+            .into_iter()
+            .chain(&error.related)
+            .map(|error| (error.kind, error.span.start.line))
+            .collect::<Vec<_>>(),
+        [(ErrorKind::SyntaxError, 1), (ErrorKind::SyntaxError, 2)],
+    );
+}
+
+#[test]
+fn constructors() {
+    test_code(
+        r#"
+    class Point {
+        var x
+        var y = 0
+        var label = 'point'
+
+        fun init(x, y) {
+            this.x = x
+            this.y = y
+        }
+
+        fun sum() this.x + this.y
+    }
+    var p = Point(1, 2)
+    print(p.x, p.y, p.sum(), p.label, '')
+
+    class Empty {}
+    print(typeOf(Empty()), '')
+
+    try { Point(1) } catch (e) { print(e.message, '') }
+    try { Empty(1) } catch (e) { print(e.message) }
+    "#,
+        "1 2 3 point object Constructor takes 2 arguments (x, y), however 1 were supplied \
+        Class without 'init' method takes no arguments, however 1 were supplied",
+    )
+}
+
+#[test]
+fn inheritance() {
+    test_code(
+        r#"
+    class Animal {
+        var name
+        var sound = '...'
+        static var count = 0
+        static fun create(name) this(name)
+        fun init(name) { this.name = name; Animal.count += 1 }
+        fun speak() this.name + ' says ' + this.sound
+    }
+    class Dog extends Animal {
+        var sound = 'woof'
+        fun speak() super.speak() + '!'
+    }
+    class Puppy extends Dog {
+        fun init(name) super.init(name + ' jr.')
+    }
+    print(Dog('Rex').speak(), Puppy.create('Bo').speak(), Puppy.count, '')
+
+    try { class Broken extends 3 {} } catch (e) { print(e.message, '') }
+    try { super.speak() } catch (e) { print(e.message) }
+    "#,
+        "Rex says woof! Bo jr. says woof! 2 Cannot extend type 'number' \
+        'super' can only be used inside of classes with a parent",
+    )
+}
+
+#[test]
+fn operator_overloading() {
+    test_code(
+        r#"
+    class Vec {
+        var x
+        var y
+        fun init(x, y) { this.x = x; this.y = y }
+        fun __add__(other) Vec(this.x + other.x, this.y + other.y)
+        fun __mul__(n) Vec(this.x * n, this.y * n)
+        fun __eq__(other) this.x == other.x && this.y == other.y
+        fun __lt__(other) this.x < other.x
+        fun __index__(i) if (i == 0) this.x else this.y
+        fun __str__() '(' + this.x + ', ' + this.y + ')'
+        fun __iter__() [this.x, this.y]
+    }
+    var a = Vec(1, 2)
+    var b = Vec(3, 4)
+    print(a + b, a * 3, a == Vec(1, 2), a != b, a < b, a > b, '')
+    print(a[1], 'a=' + a, a.toString(), '')
+    for (c in b) print(c, '')
+    a += b
+    print(a)
+    "#,
+        "(4, 6) (3, 6) true true true false 2 a=(1, 2) (1, 2) 3 4 (4, 6)",
+    )
+}
+
+#[test]
+fn stack_traces() {
+    test_code(
+        r#"
+    fun inner(x) x.missing
+    fun middle(x) inner(x)
+    class Box {
+        fun open() middle(1)
+    }
+    try { Box().open() } catch (e) {
+        for (frame in e.stack) print(frame.name, frame.span.start.line, '')
+    }
+    try { (fun () 1 / 0)() } catch (e) { print(e.stack[0].name, e.stack[0].span.start.line) }
+    "#,
+        "inner 3 middle 5 open 7 null 10",
+    )
+}
+
+#[test]
+fn sources() {
+    let mut sources = SourceMap::new();
+    let code = "import 'lib.ro'\nlib.fail()";
+    let main = sources.add("main.ro", code);
+    let program = Parser::new(Lexer::new(code, main)).parse().unwrap();
+    let module_trees = ProgramArena::new();
+    let mut interpreter = Interpreter::new(&program, io::sink(), io::sink(), |code| {
+        std::process::exit(code)
+    })
+    .with_path("main.ro")
+    .with_module_trees(&module_trees)
+    .with_loader(|_| Ok("var a = 1\nfun fail() a.b".to_string()))
+    .with_sources(sources);
+
+    let error = interpreter.run(true).unwrap_err();
+    let sources = interpreter.sources();
+    assert_eq!(sources.name(error.source()), "lib.ro");
+    assert_eq!(
+        sources.line(error.source(), error.span.start.line),
+        Some("fun fail() a.b")
+    );
+    assert_eq!(sources.name(error.stack[0].span.source()), "main.ro");
+}
+
+#[test]
+fn match_expr() {
+    test_code(
+        r#"
+    fun describe(value) match (value) {
+        0 => 'zero',
+        -5..0 => 'negative',
+        1..=9 => 'digit',
+        n if typeOf(n) == 'number' => if (n >= 10) 'big' else 'fraction',
+        'hi' => 'greeting',
+        null => 'nothing',
+        [] => 'empty',
+        [first, ..rest, 0] => 'ends with zero after ' + first + rest,
+        [_, ..] => 'list',
+        { kind: 'ValueError', message } => message,
+        true => 'yes'
+        _ => 'other'
+    }
+    print(describe(0), describe(-3), describe(7), describe(12), describe(1.5), describe('x'), '')
+    print(describe([]), describe([1, 2, 3, 0]), describe([5]), describe(true), describe('hi'), describe(null), '')
+    print(try { match (3) { 1 => 'one' } } catch (e) { describe(e) })
+    print('', describe(0.5), describe(-0.5), describe(9.5))
+    print('', match (2.5) { ..3 => 'below', _ => 'above' }, match (3) { ..3 => 'below', _ => 'above' })
+    "#,
+        "zero negative digit big digit other empty ends with zero after 1[2, 3] list yes greeting nothing No pattern matched the value '3' \
+        fraction negative fraction below above",
+    );
+
+    for (code, span) in [
+        ("match (1) { 1.5..3 => 1 }", "1:13..1:16"),
+        ("match (1) { -1..=2.5 => 1 }", "1:18..1:21"),
+        ("match (1) { [..-0.5] => 1 }", "1:16..1:20"),
+    ] {
+        let errors = Parser::new(Lexer::new(code, SourceId::default()))
+            .parse()
+            .unwrap_err();
+        assert_eq!(errors[0].message, "Range bounds have to be integers");
+        assert_eq!(format!("{:?}", errors[0].span), span, "{code}");
+    }
+}
+
+#[test]
+fn destructuring() {
+    test_code(
+        r#"
+    var [a, b, ..rest] = [1, 2, 3, 4]
+    print(a, b, rest, '')
+    var [first, .., last] = [5, 6, 7]
+    print(first, last, '')
+    class Person { var name = 'Ann'; var age = 42; }
+    var { name, age: years } = Person()
+    print(name, years, '')
+    for ([i, { name }] in [[0, Person()], [1, Person()]]) print(i, name, '')
+    var x = 'x'
+    var y = 'y'
+    [x, y] = [y, x]
+    print(x, y, '')
+    var pair = ['p', ['q']]
+    [x, [y, pair[1][0]]] = ['a', ['b', 'c']]
+    print(x, y, pair, '')
+    fun fails(f) try { f() } catch (e) { print(e.kind + ':', e.message, '') }
+    fails(fun() { var [c, d] = [1] })
+    fails(fun() { var [c, d, ..] = [1] })
+    fails(fun() { var [c] = 'c' })
+    fails(fun() { var { missing } = Person() })
+    fails(fun() { var { c } = [] })
+    fails(fun() { [x, y] = [1, 2, 3] })
+    fails(fun() { [x, y] = [1, 2] })
+    fails(fun() { [x, [y]] = ['a', 'b'] })
+    "#,
+        "1 2 [3, 4] 5 7 Ann 42 0 Ann 1 Ann y x a b [p, [c]] \
+        ValueError: Expected 2 items, found 1 \
+        ValueError: Expected at least 2 items, found 1 \
+        TypeError: Cannot destructure type 'string' as a list \
+        ValueError: Object has no member called 'missing' \
+        TypeError: Cannot destructure type 'list' as an object \
+        ValueError: Expected 2 items, found 3 \
+        TypeError: Cannot change type by reassigning, create a new variable instead \
+        TypeError: Cannot destructure type 'string' as a list ",
+    );
+
+    for (code, span) in [
+        ("[1, 2] = [3, 4]", "1:2..1:3"),
+        ("[a, [b, c + 1]] = [1, [2, 3]]", "1:9..1:14"),
+        ("[a, b] += [1, 2]", "1:1..1:7"),
+        ("f() = 1", "1:1..1:4"),
+        ("a?.b = 1", "1:1..1:5"),
+    ] {
+        let errors = Parser::new(Lexer::new(code, SourceId::default()))
+            .parse()
+            .unwrap_err();
+        assert_eq!(
+            errors[0].message, "Expected variable, field or index, found expression",
+            "{code}",
+        );
+        assert_eq!(format!("{:?}", errors[0].span), span, "{code}");
+    }
+}
+
+#[test]
+fn params() {
+    test_code(
+        r#"
+    fun greet(greeting, name = 'world', punctuation = '!') {
+        greeting + ' ' + name + punctuation
+    }
+    print(greet('hello', punctuation: '?'), greet('hi', 'you', '.'), greet('hey'), '')
+    fun sum(first, ..rest) {
+        for (n in rest) first += n
+        first
+    }
+    print(sum(1), sum(1, 2, 3), '')
+    class Point {
+        var x; var y;
+        fun init(x = 0, y = x) { this.x = x; this.y = y; }
+        fun scale(by = 2, ..ignored) Point(x: this.x * by, y: this.y * by)
+    }
+    var p = Point(y: 3).scale()
+    print(p.x, p.y, Point(4).y, '')
+    fun fails(f) try { f() } catch (e) { print(e.message + ';', '') }
+    fails(fun() greet())
+    fails(fun() greet('a', 'b', 'c', 'd'))
+    fails(fun() greet('a', nme: 'b'))
+    fails(fun() greet('a', greeting: 'b'))
+    fails(fun() Point(1, 2, 3))
+    fails(fun() sum())
+    "#,
+        "hello world? hi you. hey world! 1 6 0 6 4 \
+        Function takes 1 to 3 arguments (greeting, [name], [punctuation]), however 0 were supplied; \
+        Function takes 1 to 3 arguments (greeting, [name], [punctuation]), however 4 were supplied; \
+        Function has no parameter called 'nme' (greeting, [name], [punctuation]); \
+        Argument 'greeting' was supplied more than once; \
+        Constructor takes 0 to 2 arguments ([x], [y]), however 3 were supplied; \
+        Function takes at least 1 arguments (first, ..rest), however 0 were supplied; ",
+    )
+}
+
+#[test]
+fn string_interpolation() {
+    test_code(
+        r#"
+    fun fib(n) if (n < 2) n else fib(n - 1) + fib(n - 2)
+    var n = 10
+    print("fib({n}) = {fib(n)}", "{[1, 2]}{ { var a = 1; a } }", "\{n\}", '{n}', "{"{n}"}", "", '')
+    var p = "x{
+        n + 1
+    }"
+    print(p, '')
+    print(try { "a{1 / 0}" } catch (e) { e.kind })
+    "#,
+        "fib(10) = 55 [1, 2]1 {n} {n} 10  x11 DivisionByZeroError",
+    )
+}
+
+#[test]
+fn const_and_freeze() {
+    test_code(
+        r#"
+    const limit = 3
+    const [low, high] = [1, 10]
+    const list = [1, 2]
+    list.push(limit)
+    list[0] = 0
+    print(limit, low, high, list, '')
+    fun fails(f) try { f() } catch (e) { print(e.message + ';', '') }
+    fails(fun() limit = 4)
+    fails(fun() high += 1)
+    var alias = limit
+    alias = 5
+    const copy = limit
+    fails(fun() copy = 6)
+    print(alias, limit, '')
+
+    const config = freeze(['name': 'app', 'ports': [80, 443]])
+    var frozen = freeze([1, [2]])
+    print(config['ports'][1], frozen, '')
+    fails(fun() config['name'] = 'other')
+    fails(fun() config['debug'] = true)
+    fails(fun() config['ports'].push(8080))
+    fails(fun() frozen[1][0] = 3)
+    fails(fun() frozen.pop())
+    var first = frozen[0]
+    first = 10
+    var same = frozen
+    same = [3]
+    print(limit, config, frozen, first, same, '')
+    frozen = []
+
+    var a = 1
+    const b = a
+    a = 2
+    fun f(x) { x = 5 }
+    const c = 1
+    f(c)
+    var l = [c]
+    l[0] = 2
+    print(a, b, c, l, frozen)
+    "#,
+        "3 1 10 [0, 2, 3] \
+        Cannot reassign constant 'limit' declared at 2:5; \
+        Cannot reassign constant 'high' declared at 3:5; \
+        Cannot reassign constant 'copy' declared at 13:5; 5 3 \
+        443 [1, [2]] \
+        Cannot change a frozen value; Cannot change a frozen value; Cannot change a frozen value; \
+        Cannot change a frozen value; Cannot change a frozen value; \
+        3 [name: app, ports: [80, 443]] [1, [2]] 10 [3] \
+        2 1 1 [2] []",
+    )
+}
+
+#[test]
+fn enums() {
+    test_code(
+        r#"
+    enum Shape {
+        Circle(r),
+        Rect(w, h),
+        Empty,
+    }
+    fun area(shape) match (shape) {
+        Shape.Circle(r) => 3 * r ** 2,
+        Shape.Rect(w, 4) => w * shape.h,
+        Shape.Rect => 0,
+        Shape.Empty => 0,
+    }
+    var shapes = [Shape.Circle(2), Shape.Rect(3, 4), Shape.Empty]
+    for (shape in shapes) print(shape, area(shape), '')
+    print(typeOf(shapes[0]), typeOf(Shape), typeOf(Shape.Circle), '')
+    print(Shape.Rect(1, 2) == Shape.Rect(1, 2), Shape.Rect(1, 2) == Shape.Rect(2, 1), '')
+    print(Shape.Empty == Shape.Empty, Shape.Circle(1) == 1, '')
+    enum Color { Red, Green }
+    var menu = []
+    for (color in Color) menu.push("- {color}")
+    print(menu, '')
+    var color = Color.Red
+    try { color = Shape.Empty } catch (e) { print(e.message + ';', '') }
+    try { Shape.Rect(1) } catch (e) { print(e.message + ';', '') }
+    try { Color.Red() } catch (e) { print(e.message) }
+    "#,
+        "Shape.Circle(2) 12 Shape.Rect(3, 4) 12 Shape.Empty 0 \
+        Shape enum function true false true false \
+        [- Color.Red, - Color.Green] \
+        Cannot change type by reassigning, create a new variable instead; \
+        Variant 'Shape.Rect' takes 2 arguments (w, h), however 1 were supplied; \
+        Type 'Color' is not callable",
+    )
+}
+
+#[test]
+fn null_safety() {
+    test_code(
+        r#"
+    class Node {
+        var value
+        var next = null
+        fun init(value) this.value = value
+        fun get() this.value
+    }
+    var list = Node(1)
+    list.next = Node(2)
+    var empty = null
+    print(list.next?.value, list.next?.next?.value, empty?.next.value, empty?.get(), '')
+    print(list?.next?.get(), list.next.next?.get().missing, '')
+
+    var calls = 0
+    fun fallback() { calls += 1; 'fallback' }
+    print(null ?? 1, false ?? 2, empty ?? list.next?.next ?? fallback(), 0 ?? fallback(), calls, '')
+
+    var name = null
+    name ??= 'first'
+    name ??= fallback()
+    var options = ['retries': 3]
+    options['retries'] ??= 5
+    options['timeout'] ??= 10
+    print(name, options, calls, '')
+    try { empty.value } catch (e) { print(e.message) }
+    "#,
+        "2 null null null 2 null 1 false fallback 0 1 \
+        first [retries: 3, timeout: 10] 1 \
+        Type 'null' has no member called 'value'",
+    )
+}
+
+#[test]
+fn arrow_functions() {
+    test_code(
+        r#"
+    var add = (a, b) => a + b
+    var double = x => x * 2
+    var greet = (name, greeting = 'hi') => {
+        var message = greeting + ' ' + name
+        message
+    }
+    fun apply(f, value) f(value)
+    print(add(1, 2), double(4), apply(x => x + 1, 1), greet('bob'), (() => 'none')(), '')
+    print((1 + 2) * 3, (add)(2, 3), double, typeOf(x => x), '')
+    var compose = (f, g) => x => f(g(x))
+    print(compose(double, x => x + 3)(1), '')
+    var limit = 2
+    print(match (3) {
+        n if n > limit => 'big',
+        n if (n == limit) => 'equal',
+        n if [1, 2].map(x => x * n).contains(2) => 'small',
+        _ => 'other',
+    }, '')
+    var sum = (first: number, ..rest) => first + rest[1]
+    var offset = (a: number, b = 10) => a + b
+    print(sum(1, 2, 3), offset(1), (..r) => r, 5 |> apply((_) => 0, _), typeOf((..2)), '')
+    try { add(1) } catch (e) { print(e.message) }
+    "#,
+        "3 8 2 hi bob none 9 5 <function> function 8 big 4 11 <function> 0 range \
+        Function takes 2 arguments (a, b), however 1 were supplied",
+    );
+
+    for (code, message, span) in [
+        ("print((a, b))", "Expected ')', found ','", "1:9..1:10"),
+        ("print(())", "Expected expression, found ')'", "1:8..1:9"),
+        ("print((a: number))", "Expected ')', found ':'", "1:9..1:10"),
+        (
+            "var f = (a, 1 + 2) => a",
+            "Expected parameter, found expression",
+            "1:13..1:18",
+        ),
+        (
+            "var f = (a = 1, b) => a",
+            "Parameters without a default value cannot follow ones with a default value",
+            "1:17..1:18",
+        ),
+        (
+            "var f = (..a, b) => a",
+            "The rest parameter has to be the last parameter",
+            "1:15..1:16",
+        ),
+    ] {
+        let errors = Parser::new(Lexer::new(code, SourceId::default()))
+            .parse()
+            .unwrap_err();
+        assert_eq!(errors[0].message, message, "{code}");
+        assert_eq!(format!("{:?}", errors[0].span), span, "{code}");
+    }
+}
+
+#[test]
+fn generators() {
+    test_code(
+        r#"
+    fun naturals() {
+        var n = 0
+        loop { yield n; n += 1 }
+    }
+    for (n in naturals()) { if (n > 3) break; print(n, '') }
+
+    fun evens(numbers) for (n in numbers) if (n % 2 == 0) yield n
+    fun take(items, count) {
+        if (count <= 0) return
+        for (item in items) {
+            yield item
+            count -= 1
+            if (count == 0) return
+        }
+    }
+    for (n in take(evens(naturals()), 3)) print(n, '')
+
+    fun logged() {
+        print('start', '')
+        yield 1
+        try {
+            print('inside', '')
+            yield 2
+            1 / 0
+            yield 'skipped'
+        } catch (e) {
+            yield e.kind
+        }
+        var i = 3
+        while (i < 10) { i += 1; if (i % 2 == 0) continue; yield i }
+        return
+        yield 'unreachable'
+    }
+    var gen = logged()
+    print(gen, typeOf(gen), '')
+    for (value in gen) print(value, '')
+    for (value in gen) print('again', '')
+    var pairs = fun() for ([key, value] in [[1, 'a'], [2, 'b']]) yield key + ':' + value
+    for (pair in pairs()) print(pair, '')
+    fun nested() { if (true) { yield 1 } else if (false) loop { yield 2 } }
+    for (value in nested()) print(value, '')
+    fun failing() { yield 1; null.x }
+    try { for (value in failing()) {} } catch (e) { print(e.stack[0].name, e.stack[0].span.start.line) }
+    fun arms(values) for (value in values) match (value) {
+        [a, b] if a < b => { yield a; yield b }
+        [..] => yield 'unsorted'
+        0 => {}
+        n => { var x = n * 2; yield x }
+    }
+    for (value in arms([[1, 2], 0, 5, [3, 1]])) print('', value)
+    "#,
+        "0 1 2 3 0 2 4 <generator> generator start 1 inside 2 DivisionByZeroError 5 7 9 \
+        1:a 2:b 1 failing 44 1 2 10 unsorted",
+    );
+
+    for (code, span) in [
+        ("fun f() { var x = if (true) { yield 1 } }", "1:31..1:36"),
+        (
+            "fun f() { var x = match (1) { _ => { yield 1 } } }",
+            "1:38..1:43",
+        ),
+        ("fun f() { match ({ yield 1 }) { _ => 1 } }", "1:20..1:25"),
+        ("fun f() { print({ yield 1 }) }", "1:19..1:24"),
+        (
+            "fun f() { if (true) { yield 1 } else { 2 } + 1 }",
+            "1:23..1:28",
+        ),
+        ("fun f() { while ({ yield 1; true }) {} }", "1:20..1:25"),
+    ] {
+        let errors = Parser::new(Lexer::new(code, SourceId::default()))
+            .parse()
+            .unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "'yield' cannot be used inside of expressions"
+        );
+        assert_eq!(format!("{:?}", errors[0].span), span, "{code}");
+    }
+}
+
+#[test]
+fn exceptions() {
+    test_code(
+        r#"
+    class AppError {
+        var message
+        fun init(message) this.message = message
+    }
+    class NotFound extends AppError {}
+    fun find(key) throw(NotFound('missing ' + key))
+    print(try { throw(42) } catch (e) { e + 1 }, '')
+    print(try { find('a') } catch (e: TypeError) { 'type' } catch (e: AppError) { e.message }, '')
+    try { [][1] } catch (e: NotFound) { print('wrong') } catch (e: ValueError) { print(e.kind, '') }
+    try {
+        try { 1 / 0 } catch (e) { throw(e) }
+    } catch (e: DivisionByZeroError) { print('rethrown', '') }
+    try {
+        try { find('b') } catch (e) { throw(AppError('lookup failed'), e) }
+    } catch (e) { print(e.message + ':', e.cause.message, '') }
+
+    fun cleanup(f) try { f() } finally { print('cleanup', '') }
+    print(try { cleanup(fun () throw('bad')) } catch (e) { e }, '')
+    fun early() { try { return 'returned' } finally { print('finally', '') } }
+    print(early(), '')
+    for (i in 0..3) { try { if (i == 1) break; print(i, '') } finally { print('after', '') } }
+    fun override() { try { throw('lost') } finally { return 'kept' } }
+    print(override(), '')
+    fun steps() {
+        try { yield 1; return } finally { yield 'finally' }
+        yield 'unreachable'
+    }
+    for (step in steps()) print(step, '')
+    try { throw() } catch (e) { print(e.message, '') }
+    try { throw('x', 'y') } catch (e) { print(e.message) }
+    "#,
+        "43 missing a ValueError rethrown lookup failed: missing b cleanup bad finally returned \
+        0 after after kept 1 finally Function 'throw' takes 1 or 2 arguments, however 0 were supplied \
+        Only objects can be thrown with a cause, found type 'string'",
+    )
+}
+
+#[test]
+fn labeled_loops() {
+    test_code(
+        r#"
+    var found = @outer: for (i in 1..10) {
+        for (j in 1..10) if (i * j == 12) break @outer i + ':' + j
+    }
+    print(found, '')
+    var count = 0
+    @rows: while (count < 10) {
+        count += 1
+        var j = 0
+        loop { j += 1; if (j > 2) continue @rows; if (count == 3) break @rows }
+    }
+    print(count, '')
+    @skip: for (i in 0..3) { for (j in 0..3) { if (j > i) continue @skip; print(i + '' + j, '') } }
+    print('outer: for (x) { }')
+    "#,
+        "2:6 3 00 10 11 20 21 22 outer: for (x) { }",
+    );
+    test_code(
+        r#"
+    fun pairs() {
+        @a: for (i in 0..3) {
+            for (j in 0..3) {
+                if (j == 1) continue @a
+                yield i + '' + j
+            }
+        }
+    }
+    for (pair in pairs()) print(pair, '')
+    "#,
+        "00 10 20 ",
+    );
+    test_code(
+        r#"
+    print(loop { break 'hello world' }, '')
+    print(loop { break 'a-b' }, loop { break 'don\'t' }, '')
+    var a = @a: loop { loop { break @a 'a' } }
+    @b: loop { print(a + ' '); break @b; print('unreachable') }
+    var s = 'note: while (true) it'
+    s = 'tip: for (i in xs) is nice'
+    var f = () => 'a: loop {}'
+    print(s, 'hint: loop {' + 'x', f())
+    "#,
+        "hello world a-b don't a tip: for (i in xs) is nice hint: loop {x a: loop {}",
+    );
+
+    for (code, message) in [
+        (
+            "@outer: loop { fun f() { break @outer } }",
+            "Unknown label 'outer'",
+        ),
+        ("@outer: loop { break @outr }", "Unknown label 'outr'"),
+        ("@outer: print(1)", "Expected 'loop', found 'print'"),
+        ("loop { break @ }", "Expected the name of a label after '@'"),
+    ] {
+        let errors = Parser::new(Lexer::new(code, SourceId::default()))
+            .parse()
+            .unwrap_err();
+        assert_eq!(errors[0].message, message, "{code}");
+    }
+}
+
+#[test]
+fn number_literals() {
+    test_code(
+        r#"
+    print(0xFF, 0Xff_ff, 0b1010, 0o755, 0b1111_0000 & 0xF0, '')
+    print(6.022e23, 1.5e-3, 2E+2, .5e1, 1_000e-3, '')
+    print(match (255) { 0xFF => 'hex', _ => 'other' })
+    "#,
+        "255 65535 10 493 240 602200000000000000000000 0.0015 200 5 1 hex",
+    );
+
+    let max = "79228162514264337593543950335";
+    test_code(
+        &format!(
+            "print(0x{}, 0o{}, 0b{})",
+            "F".repeat(24),
+            "7".repeat(32),
+            "1".repeat(96)
+        ),
+        &format!("{max} {max} {max}"),
+    );
+
+    let too_long = [
+        format!("0x1{}", "0".repeat(24)),
+        format!("0o1{}", "0".repeat(32)),
+        format!("0b1{}", "0".repeat(96)),
+    ];
+    let too_long = too_long
+        .iter()
+        .map(|code| (code.as_str(), format!("Number '{code}' is out of range")));
+
+    for (code, message) in [
+        ("0x", "Expected hexadecimal digits after '0x'"),
+        ("0b102", "Invalid digit '2' in binary number"),
+        (
+            "0o1_000_000_000_000_000_000_000_000_000_000_000",
+            "Number '0o1000000000000000000000000000000000' is out of range",
+        ),
+        ("1e29", "Number '1e29' is out of range"),
+    ]
+    .map(|(code, message)| (code, message.to_string()))
+    .into_iter()
+    .chain(too_long)
+    {
+        let errors = Parser::new(Lexer::new(code, SourceId::default()))
+            .parse()
+            .unwrap_err();
+        assert_eq!(errors.len(), 1, "{code}");
+        assert_eq!(errors[0].message, message);
+        assert_eq!(
+            format!("{:?}", errors[0].span),
+            format!("1:1..1:{}", code.len() + 1)
+        );
+    }
+}
+
+#[test]
+fn list_comprehensions() {
+    test_code(
+        r#"
+    const xs = [1, 5, 2, 8]
+    print([x * 2 for (x in xs) if (x > 3)], '')
+    print([c + i for (c in 'ab') for (i in 0..3) if (i != 1)], '')
+    print([[x, y] for (x in 1..=3) if (x % 2 == 1) for (y in 1..=x) if (x != y)], '')
+    print([a + b for ([a, b] in [[1, 2], [3, 4]])], '')
+    var x = 'outer'
+    print([x for (x in [])], x, '')
+    fun nums() { yield 1; yield 2 }
+    print([n for (n in nums())])
+    "#,
+        "[10, 16] [a0, a2, b0, b2] [[3, 1], [3, 2]] [3, 7] [] outer [1, 2]",
+    );
+}
+
+#[test]
+fn pipelines() {
+    test_code(
+        r#"
+    fun double(n) { n * 2 }
+    fun add(a, b) { a + b }
+    fun sum(numbers) { var total = 0; for (n in numbers) total += n; total }
+    print(3 |> double |> add(1) |> add(10, _), '')
+    print(1..=4 |> sum, '')
+    print(2.5 |> _.floor() |> fun (n) { n + 0.5 }, '')
+    [4, 1, 2]
+        |> fun (xs) { [x * 10 for (x in xs) if (x > 1)] }
+        |> print
+    "#,
+        "17 10 2.5 [40, 20]",
+    );
+
+    let program = Parser::new(Lexer::new(
+        "fun inc(n) { n + 1 }\n1 |> inc |> 5 |> inc",
+        SourceId::default(),
+    ))
+    .parse()
+    .unwrap();
+    let error = Interpreter::new(&program, io::sink(), io::sink(), |_| {})
+        .run(true)
+        .unwrap_err();
+    assert_eq!(error.message, "Type 'number' is not callable");
+    assert_eq!(format!("{:?}", error.span), "2:13..2:14");
+
+    test_code(
+        r#"
+    fun add(a, b) { a + b }
+    print(1 |> add(1 |> add(_, 3)) |> add(_, _), '')
+    print(2 |> _.floor() |> add(_, 1))
+    "#,
+        "10 3",
+    );
+    for (code, span) in [
+        ("1 |> add(2, add(_, 3))", "1:17..1:18"),
+        ("1 |> add(_)(_)", "1:10..1:11"),
+        ("1 |> add(2, fun () _)", "1:20..1:21"),
+        ("[1] |> _[_]", "1:10..1:11"),
+    ] {
+        let errors = Parser::new(Lexer::new(code, SourceId::default()))
+            .parse()
+            .unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "'_' can only start a pipeline stage or be an argument of its last call",
+        );
+        assert_eq!(format!("{:?}", errors[0].span), span, "{code}");
+    }
+}
+
+#[test]
+fn type_annotations() {
+    let code = r#"
+    class Animal {
+        var name: string = ''
+    }
+    class Dog extends Animal {
+        var age: number = 0
+        fun init(name: string, age: number = 0) {
+            this.name = name
+            this.age = age
+        }
+    }
+    fun describe(animal: Animal | null, prefix: string = '-'): string {
+        if (animal == null) return prefix
+        prefix + ' ' + animal.name
+    }
+    var dog: Dog = Dog('Rex', 3)
+    var count: number | null = null
+    count = dog.age + 1
+    const names: list = [describe(dog), describe(null, prefix: '?')]
+    var f: function = fun (x: any): bool { x != null }
+    print(names, count, f(count))
+    "#;
+    let program = Parser::new(Lexer::new(code, SourceId::default()))
+        .parse()
+        .unwrap();
+    assert!(checker::check(&program).is_ok());
+    test_code(code, "[- Rex, ?] 4 true");
+
+    let errors = checker::check(
+        &Parser::new(Lexer::new(
+            r#"
+    class Point {
+        var x: number = 0
+        fun init(x: number) { this.x = 'a' }
+    }
+    enum Color { Red, Green }
+    var a: string = 1 + 2
+    a = true
+    fun f(n: number, s: string | null): Color { if (n > 0) return 'x'; n }
+    f('1', s: 2)
+    var p: Color = Point(false)
+    var q: numbr = null
+    "#,
+            SourceId::default(),
+        ))
+        .parse()
+        .unwrap(),
+    )
+    .unwrap_err();
+    assert_eq!(
+        errors
+            .iter()
+            .map(|error| error.message.as_str())
+            .collect::<Vec<_>>(),
+        [
+            "Field 'x' of class 'Point' has to be of type 'number', found 'string'",
+            "Variable 'a' has to be of type 'string', found 'number'",
+            "Variable 'a' has to be of type 'string', found 'bool'",
+            "Return value of function 'f' has to be of type 'Color', found 'string'",
+            "Return value of function 'f' has to be of type 'Color', found 'number'",
+            "Argument 'n' of function 'f' has to be of type 'number', found 'string'",
+            "Argument 's' of function 'f' has to be of type 'string | null', found 'number'",
+            "Argument 'x' of class 'Point' has to be of type 'number', found 'bool'",
+            "Variable 'p' has to be of type 'Color', found 'Point'",
+            "Unknown type 'numbr'",
+        ]
+    );
+
+    let code = r#"
+    class Item { var v: string = ''; fun init(v: string) { this.v = v }; }
+    fun numbers() {
+        class Item { var v: number = 0; fun init(v: number) { this.v = v }; }
+        var item: Item = Item(1)
+        item.v + 1
+    }
+    fun flags() {
+        class Item { var v: bool = false; fun init(v: bool) { this.v = v }; }
+        Item(true).v
+    }
+    var item: Item = Item('a')
+    print(numbers(), flags(), item.v)
+    "#;
+    let program = Parser::new(Lexer::new(code, SourceId::default()))
+        .parse()
+        .unwrap();
+    assert!(checker::check(&program).is_ok());
+    test_code(code, "2 true a");
+}
+
+#[test]
+fn vm() {
+    let cases = [
+        ("fun f(a) a\nf(1, 2)", "TypeError at 2:1..2:8"),
+        ("var x = 1\nx = 'a'", "TypeError at 2:1..2:8"),
+        ("fun f(n) n.a.b\nfun g() f(1)\ng()", "at f (2:9..3:1)"),
+        ("{ print(y) }\nvar y = 1", "ReferenceError at 1:9..1:10"),
+        ("for (i in ..5) i", "ValueError at 1:1..1:17"),
+        (
+            "var x = 1\nprint(\"a {x / 0} b\")",
+            "DivisionByZeroError at 2:11..2:16",
+        ),
+        (
+            "var x = null ?? 1 ?? 2\nprint(x / (null ?? 0))",
+            "DivisionByZeroError at 2:7..2:22",
+        ),
+        (
+            "try { [1][2] } catch (e) { e.kind }\n1 / 0",
+            "DivisionByZeroError",
+        ),
+        ("const x = 1\nx += 1", "TypeError at 2:1..2:7"),
+        (
+            "fun f() x = 2\nconst x = 1\nf()",
+            "Cannot reassign constant 'x' declared at 2:1",
+        ),
+        (
+            "var l = freeze([1])\nvar m = l\nm = [2]\nl[0] = 3",
+            "TypeError at 4:1..4:9  Cannot change a frozen value",
+        ),
+    ];
+    for (code, expected) in cases {
+        let program = Parser::new(Lexer::new(code, SourceId::default()))
+            .parse()
+            .unwrap();
+        let tree_walker = Interpreter::new(&program, io::sink(), io::sink(), |_| {})
+            .run(true)
+            .map(|_| ())
+            .map_err(|e| format!("{:?}", e));
+        let error = tree_walker.clone().unwrap_err();
+        assert!(error.contains(expected), "{error}");
+        assert_eq!(
+            run_vm(&program).unwrap().map(|_| ()),
+            tree_walker,
+            "the VM failed differently for {code:?}",
+        );
+    }
+
+    test_code(
+        r#"
+    var counters = []
+    for (i in 0..3) {
+        var count = i * 10
+        counters.push(fun () { count += 1; count })
+    }
+    print(counters[0](), counters[0](), counters[2](), '')
+    var found = for (i in 1..) if (i * i > 50) break i
+    print(found, '')
+    var total = 0
+    while (total < 20) { total += 7; if (total % 2 == 0) continue; print(total, '') }
+    print(try { throw('no') } catch (e) { e + '!' }, '')
+    var a = 1
+    var b = a
+    b += 1
+    const c = 3
+    var l = [c, [c]]
+    l[1][0] = 4
+    var m = ['x': l]
+    m['x'] = [5]
+    m['y'] = a
+    print(a, b, c, l, m)
+    "#,
+        "1 2 21 8 7 21 no! 1 2 3 [3, [4]] [x: [5], y: 1]",
+    );
+}
+
+#[test]
+fn call_depth() {
+    let code = "fun r(n) r(n + 1)\nr(0)";
+    let program = Parser::new(Lexer::new(code, SourceId::default()))
+        .parse()
+        .unwrap();
+    let expected = "RuntimeError at 1:10..2:1  Maximum call depth of 50 exceeded";
+    let error = Interpreter::new(&program, io::sink(), io::sink(), |_| {})
+        .with_max_depth(50)
+        .run(true)
+        .unwrap_err();
+    assert!(format!("{error:?}").contains(expected), "{error:?}");
+    let bytecode = vm::compile(&program).unwrap();
+    let error = Vm::new(&bytecode, io::sink(), io::sink(), |_| {})
+        .with_max_depth(50)
+        .run()
+        .unwrap_err();
+    assert!(format!("{error:?}").contains(expected), "{error:?}");
+}
+
+/// The samples which only use features supported by the VM, see [`vm::compile`]
+const VM_SAMPLES: [&str; 10] = [
+    "assignments.ro",
+    "closures.ro",
+    "escapes.ro",
+    "fib.ro",
+    "geometry.ro",
+    "lists.ro",
+    "loop.ro",
+    "maps.ro",
+    "power.ro",
+    "sample.ro",
+];
+
+#[test]
+fn vm_samples() {
+    // the tree walker needs more than the default stack of test threads for samples like fib
+    std::thread::Builder::new()
+        .stack_size(64 * 1024 * 1024)
+        .spawn(|| {
+            for entry in fs::read_dir("../samples").unwrap() {
+                let path = entry.unwrap().path();
+                let code = fs::read_to_string(&path).unwrap();
+                let name = path.file_name().unwrap().to_str().unwrap();
+                let program = match Parser::new(Lexer::new(&code, SourceId::default())).parse() {
+                    Ok(program) => program,
+                    // this sample shows how syntax errors are reported
+                    Err(_) if name == "errors.ro" => continue,
+                    Err(errors) => panic!("{name}: {errors:?}"),
+                };
+                let vm_out = run_vm(&program);
+                assert_eq!(
+                    vm_out.is_some(),
+                    VM_SAMPLES.contains(&name),
+                    "{name} should {}compile",
+                    if vm_out.is_some() { "not " } else { "" },
+                );
+                let Some(vm_out) = vm_out else {
+                    continue;
+                };
+                let mut out = Cursor::new(vec![]);
+                let res = Interpreter::new(&program, &mut out, io::sink(), |_| {}).run(true);
+                let tree_walker_out = match res {
+                    Ok(_) => Ok(String::from_utf8(out.into_inner()).unwrap()),
+                    Err(e) => Err(format!("{:?}", e)),
+                };
+                assert_eq!(vm_out, tree_walker_out, "{}", path.display());
+            }
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
@@ -65,7 +65,7 @@ fn as_plist_val(value: &JsonValue) -> PlistValue {
             }
         }
         JsonValue::String(val) => PlistValue::String(val.clone()),
-        JsonValue::Array(val) => PlistValue::Array(val.iter().map(|it| as_plist_val(it)).collect()),
+        JsonValue::Array(val) => PlistValue::Array(val.iter().map(as_plist_val).collect()),
         JsonValue::Object(val) => {
             PlistValue::Dictionary(val.iter().map(|(k, v)| (k, as_plist_val(v))).collect())
         }
@@ -0,0 +1,898 @@
+use std::{collections::HashMap, mem, result};
+
+use crate::{
+    error::{Error, Span},
+    nodes::*,
+    tokens::TokenKind,
+};
+
+/// The names of the types built into the language, `any` matches values of every type
+const BUILT_IN_TYPES: [&str; 13] = [
+    "any",
+    "number",
+    "bool",
+    "string",
+    "range",
+    "list",
+    "map",
+    "function",
+    "generator",
+    "class",
+    "object",
+    "enum",
+    "null",
+];
+
+/// Checks the type annotations of a program without running it and returns every mismatch
+/// it finds. Values whose type cannot be known before running the program are accepted.
+pub fn check(program: &Program) -> result::Result<(), Vec<Error>> {
+    let mut checker = Checker::default();
+    checker.block(program);
+    match checker.errors.is_empty() {
+        true => Ok(()),
+        false => Err(checker.errors),
+    }
+}
+
+/// The name of the type of a value, or `None` if it is not known. Instances of a class
+/// are named after their class, values of an enum after their enum.
+type Type<'tree> = Option<&'tree str>;
+
+enum Binding<'tree> {
+    /// Every value assigned to the variable has to match the annotation
+    Annotated(&'tree TypeAnnotation),
+    /// The type of the value the variable was initialized with, until it is reassigned
+    Inferred(Type<'tree>),
+    Function(&'tree FunctionDecl),
+    Class(&'tree ClassDecl),
+    Enum(&'tree EnumDecl),
+}
+
+enum Callee<'tree> {
+    Function(&'tree FunctionDecl),
+    Class(&'tree ClassDecl),
+    Variant(&'tree EnumDecl),
+}
+
+#[derive(Default)]
+struct Checker<'tree> {
+    /// The bindings of every block being checked, the names of classes and enums bound in
+    /// them can be used as types
+    scopes: Vec<HashMap<&'tree str, Binding<'tree>>>,
+    /// The name and return type of every function being checked, innermost last
+    functions: Vec<(Option<&'tree str>, Option<&'tree TypeAnnotation>)>,
+    /// The class whose members are being checked, `this` is an instance of it
+    class: Option<&'tree ClassDecl>,
+    errors: Vec<Error>,
+}
+
+impl<'tree> Checker<'tree> {
+    fn bind(&mut self, name: &'tree str, binding: Binding<'tree>) {
+        self.scopes
+            .last_mut()
+            .expect("checker should always have a scope")
+            .insert(name, binding);
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Binding<'tree>> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    fn class_decl(&self, name: &str) -> Option<&'tree ClassDecl> {
+        match self.lookup(name)? {
+            Binding::Class(node) => Some(node),
+            _ => None,
+        }
+    }
+
+    fn bind_target(&mut self, target: &'tree Target) {
+        match target {
+            Target::Ident(name) => self.bind(name, Binding::Inferred(None)),
+            Target::List {
+                before,
+                rest,
+                after,
+                ..
+            } => {
+                for target in before.iter().chain(after) {
+                    self.bind_target(target);
+                }
+                if let Some(Some(rest)) = rest {
+                    self.bind(rest, Binding::Inferred(Some("list")));
+                }
+            }
+            Target::Object { fields, .. } => {
+                for (name, target) in fields {
+                    match target {
+                        Some(target) => self.bind_target(target),
+                        None => self.bind(name, Binding::Inferred(None)),
+                    }
+                }
+            }
+        }
+    }
+
+    fn bind_pattern(&mut self, pattern: &'tree Pattern) {
+        match pattern {
+            Pattern::Binding(name) => self.bind(name, Binding::Inferred(None)),
+            Pattern::List {
+                before,
+                rest,
+                after,
+            } => {
+                for pattern in before.iter().chain(after) {
+                    self.bind_pattern(pattern);
+                }
+                if let Some(Some(rest)) = rest {
+                    self.bind(rest, Binding::Inferred(Some("list")));
+                }
+            }
+            Pattern::Object(fields) => {
+                for (name, pattern) in fields {
+                    match pattern {
+                        Some(pattern) => self.bind_pattern(pattern),
+                        None => self.bind(name, Binding::Inferred(None)),
+                    }
+                }
+            }
+            Pattern::Variant {
+                fields: Some(fields),
+                ..
+            } => {
+                for pattern in fields {
+                    self.bind_pattern(pattern);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /////////////////////////////////////////////
+
+    /// Reports the names in an annotation which are neither built-in types nor declared
+    fn annotation(&mut self, annotation: &'tree Option<TypeAnnotation>) {
+        let Some(annotation) = annotation else { return };
+        for name in &annotation.types {
+            if !self.is_declared(name) && self.lookup(name).is_none() {
+                self.errors.push(error_val!(
+                    TypeError,
+                    annotation.span,
+                    "Unknown type '{name}'",
+                ));
+            }
+        }
+    }
+
+    fn is_declared(&self, name: &str) -> bool {
+        BUILT_IN_TYPES.contains(&name)
+            || matches!(
+                self.lookup(name),
+                Some(Binding::Class(_) | Binding::Enum(_))
+            )
+    }
+
+    /// Reports a value whose type is known and not allowed by the annotation
+    fn expect(
+        &mut self,
+        annotation: &TypeAnnotation,
+        found: Type,
+        span: Span,
+        subject: impl FnOnce() -> String,
+    ) {
+        if let Some(found) = found {
+            if !self.matches(annotation, found) {
+                self.errors.push(error_val!(
+                    TypeError,
+                    span,
+                    "{} has to be of type '{annotation}', found '{found}'",
+                    subject(),
+                ));
+            }
+        }
+    }
+
+    fn matches(&self, annotation: &TypeAnnotation, found: &str) -> bool {
+        annotation.types.iter().any(|expected| {
+            // types which are unknown or stored in variables are not checked
+            !self.is_declared(expected)
+                || expected == "any"
+                || expected == found
+                || (expected == "object" && self.class_decl(found).is_some())
+                || self.ancestors(found).any(|class| class.ident == *expected)
+        })
+    }
+
+    /// Iterates over a class and the classes it inherits from, as far as they are known
+    fn ancestors<'a>(&'a self, class: &str) -> impl Iterator<Item = &'tree ClassDecl> + 'a {
+        let mut curr = self.class_decl(class);
+        // the count guards against classes which inherit from each other
+        let mut remaining: usize = self.scopes.iter().map(HashMap::len).sum();
+        std::iter::from_fn(move || {
+            let class = curr.filter(|_| remaining > 0)?;
+            remaining -= 1;
+            curr = class
+                .parent
+                .as_deref()
+                .and_then(identifier)
+                .and_then(|parent| self.class_decl(parent));
+            Some(class)
+        })
+    }
+
+    /// Finds the annotation of an instance field in a class or the classes it inherits from
+    fn field(&self, class: &str, name: &str) -> Option<&'tree TypeAnnotation> {
+        self.ancestors(class).find_map(|class| {
+            class
+                .block
+                .members
+                .iter()
+                .find_map(|member| match &member.kind {
+                    MemberKind::Attribute(VarStmt {
+                        target: Target::Ident(ident),
+                        annotation: Some(annotation),
+                        ..
+                    }) if !member.is_static && ident == name => Some(annotation),
+                    _ => None,
+                })
+        })
+    }
+
+    fn init(&self, class: &str) -> Option<&'tree FunctionDecl> {
+        self.ancestors(class).find_map(|class| {
+            class
+                .block
+                .members
+                .iter()
+                .find_map(|member| match &member.kind {
+                    MemberKind::Method(method) if !member.is_static && method.ident == "init" => {
+                        Some(method)
+                    }
+                    _ => None,
+                })
+        })
+    }
+
+    fn variable(&self, name: &str) -> Type<'tree> {
+        if name == "this" {
+            if let Some(class) = self.class {
+                return Some(&class.ident);
+            }
+        }
+        match self.lookup(name)? {
+            Binding::Annotated(annotation) => single(annotation),
+            Binding::Inferred(ty) => *ty,
+            Binding::Function(_) => Some("function"),
+            Binding::Class(_) => Some("class"),
+            Binding::Enum(_) => Some("enum"),
+        }
+    }
+
+    /////////////////////////////////////////////
+
+    fn block(&mut self, block: &'tree Block) -> Type<'tree> {
+        self.scopes.push(HashMap::new());
+        // functions, classes and enums can be used in annotations before their declaration
+        for statement in block {
+            match statement {
+                Statement::Function(node) => self.bind(&node.ident, Binding::Function(node)),
+                Statement::Class(node) => self.bind(&node.ident, Binding::Class(node)),
+                Statement::Enum(node) => self.bind(&node.ident, Binding::Enum(node)),
+                _ => {}
+            }
+        }
+        let mut ty = None;
+        for statement in block {
+            ty = self.statement(statement);
+        }
+        self.scopes.pop();
+        ty
+    }
+
+    fn statement(&mut self, node: &'tree Statement) -> Type<'tree> {
+        match node {
+            Statement::Var(node) => self.var_stmt(node, false),
+            Statement::Function(node) => {
+                self.bind(&node.ident, Binding::Function(node));
+                self.function(
+                    Some(&node.ident),
+                    &node.args,
+                    &node.return_type,
+                    &node.block,
+                    node.is_generator,
+                );
+            }
+            Statement::Class(node) => {
+                self.bind(&node.ident, Binding::Class(node));
+                self.class(Some(node), &node.parent, &node.block);
+            }
+            Statement::Enum(node) => self.bind(&node.ident, Binding::Enum(node)),
+            Statement::Break(BreakStmt { expr, .. }) | Statement::Yield(YieldStmt { expr, .. }) => {
+                if let Some(expr) = expr {
+                    self.expression(expr);
+                }
+            }
+            Statement::Continue(_) => {}
+            Statement::Return(node) => self.return_stmt(node),
+            Statement::Import(node) => self.bind(&node.ident, Binding::Inferred(None)),
+            Statement::Expr(node) => return self.expression(node),
+        }
+        None
+    }
+
+    fn var_stmt(&mut self, node: &'tree VarStmt, is_field: bool) {
+        self.annotation(&node.annotation);
+        let ty = node.expr.as_ref().and_then(|expr| self.expression(expr));
+        if let (Some(annotation), Some(expr)) = (&node.annotation, &node.expr) {
+            let class = self.class;
+            self.expect(annotation, ty, expr.span, || {
+                match (&node.target, is_field, class) {
+                    (Target::Ident(name), true, Some(class)) => {
+                        format!("Field '{name}' of class '{}'", class.ident)
+                    }
+                    (Target::Ident(name), true, None) => format!("Field '{name}'"),
+                    (Target::Ident(name), false, _) => format!("Variable '{name}'"),
+                    _ => "Value".to_string(),
+                }
+            })
+        }
+        match (&node.target, &node.annotation) {
+            (Target::Ident(name), Some(annotation)) => {
+                self.bind(name, Binding::Annotated(annotation))
+            }
+            (Target::Ident(name), None) => {
+                self.bind(name, Binding::Inferred(ty.filter(|ty| *ty != "null")))
+            }
+            (target, _) => self.bind_target(target),
+        }
+    }
+
+    fn function(
+        &mut self,
+        name: Option<&'tree str>,
+        args: &'tree Params,
+        return_type: &'tree Option<TypeAnnotation>,
+        block: &'tree Block,
+        is_generator: bool,
+    ) {
+        self.scopes.push(HashMap::new());
+        for param in &args.params {
+            self.annotation(&param.annotation);
+            if let Some(default) = &param.default {
+                let ty = self.expression(default);
+                if let Some(annotation) = &param.annotation {
+                    self.expect(annotation, ty, default.span, || {
+                        format!("Default value of parameter '{}'", param.ident)
+                    });
+                }
+            }
+            let binding = match &param.annotation {
+                Some(annotation) => Binding::Annotated(annotation),
+                None => Binding::Inferred(None),
+            };
+            self.bind(&param.ident, binding);
+        }
+        if let Some(rest) = &args.rest {
+            self.bind(rest, Binding::Inferred(Some("list")));
+        }
+        self.annotation(return_type);
+
+        // generators return a generator, the annotation describes the values they return
+        // when they are done, which cannot be checked here
+        let return_type = return_type.as_ref().filter(|_| !is_generator);
+        self.functions.push((name, return_type));
+        let ty = self.block(block);
+        if let (Some(annotation), Some(Statement::Expr(expr))) = (return_type, block.last()) {
+            self.expect(annotation, ty, expr.span, || describe_return(name));
+        }
+        self.functions.pop();
+        self.scopes.pop();
+    }
+
+    fn return_stmt(&mut self, node: &'tree ReturnStmt) {
+        let ty = match &node.expr {
+            Some(expr) => self.expression(expr),
+            None => Some("null"),
+        };
+        if let Some((name, Some(annotation))) = self.functions.last().copied() {
+            let span = node.expr.as_ref().map_or(node.span, |expr| expr.span);
+            self.expect(annotation, ty, span, || describe_return(name));
+        }
+    }
+
+    fn class(
+        &mut self,
+        decl: Option<&'tree ClassDecl>,
+        parent: &'tree Option<Box<MemberExpr>>,
+        block: &'tree MemberBlock,
+    ) {
+        if let Some(parent) = parent {
+            self.member_expr(parent);
+        }
+        let prev_class = mem::replace(&mut self.class, decl);
+        self.scopes.push(HashMap::new());
+        for member in &block.members {
+            match &member.kind {
+                MemberKind::Attribute(node) => self.var_stmt(node, true),
+                MemberKind::Method(node) => self.function(
+                    Some(&node.ident),
+                    &node.args,
+                    &node.return_type,
+                    &node.block,
+                    node.is_generator,
+                ),
+            }
+        }
+        self.scopes.pop();
+        self.class = prev_class;
+    }
+
+    fn check_args(
+        &mut self,
+        callee: &str,
+        params: &'tree Params,
+        args: &'tree Args,
+        types: &[Type],
+    ) {
+        let mut positional = params.params.iter();
+        for (arg, ty) in args.iter().zip(types) {
+            let param = match &arg.name {
+                Some(name) => params.params.iter().find(|param| param.ident == *name),
+                None => positional.next(),
+            };
+            if let Some(Param {
+                ident,
+                annotation: Some(annotation),
+                ..
+            }) = param
+            {
+                self.expect(annotation, *ty, arg.span, || {
+                    format!("Argument '{ident}' of {callee}")
+                });
+            }
+        }
+    }
+
+    /// Checks a value assigned to a variable or to a field of a variable
+    fn assign(&mut self, target: &'tree CallExpr, ty: Type<'tree>, span: Span) {
+        if !target.following.is_empty() {
+            return;
+        }
+        match (&target.base.base, target.base.following.as_slice()) {
+            (Atom::Identifier { name, .. }, []) => {
+                let Some(binding) = self
+                    .scopes
+                    .iter_mut()
+                    .rev()
+                    .find_map(|scope| scope.get_mut(name.as_str()))
+                else {
+                    return;
+                };
+                match binding {
+                    Binding::Annotated(annotation) => {
+                        let annotation = *annotation;
+                        self.expect(annotation, ty, span, || format!("Variable '{name}'"));
+                    }
+                    Binding::Inferred(prev) if *prev == ty => {}
+                    binding => *binding = Binding::Inferred(None),
+                }
+            }
+            (Atom::Identifier { name, .. }, [MemberPart::Field(field)]) => {
+                let Some(class) = self.variable(name) else {
+                    return;
+                };
+                if let Some(annotation) = self.field(class, field) {
+                    self.expect(annotation, ty, span, || {
+                        format!("Field '{field}' of class '{class}'")
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /////////////////////////////////////////////
+
+    fn expression(&mut self, node: &'tree Expression) -> Type<'tree> {
+        let mut ty = self.range_expr(&node.base);
+        for stage in &node.stages {
+            // the stage is checked like a call with the previous value as its `_` argument
+            self.scopes.push(HashMap::new());
+            self.bind("_", Binding::Inferred(ty));
+            ty = self.call_expr(stage);
+            self.scopes.pop();
+        }
+        ty
+    }
+
+    fn range_expr(&mut self, node: &'tree RangeExpr) -> Type<'tree> {
+        match node {
+            RangeExpr::None(expr) => self.coalesce_expr(expr),
+            RangeExpr::Closed(start, _, end, _) => {
+                self.coalesce_expr(start);
+                self.coalesce_expr(end);
+                Some("range")
+            }
+            RangeExpr::OpenEnd(expr, _) | RangeExpr::OpenStart(_, expr, _) => {
+                self.coalesce_expr(expr);
+                Some("range")
+            }
+            RangeExpr::Open => Some("range"),
+        }
+    }
+
+    fn coalesce_expr(&mut self, node: &'tree CoalesceExpr) -> Type<'tree> {
+        let ty = self.or_expr(&node.base);
+        for expr in &node.following {
+            self.or_expr(expr);
+        }
+        ty.filter(|_| node.following.is_empty())
+    }
+
+    fn or_expr(&mut self, node: &'tree OrExpr) -> Type<'tree> {
+        let ty = self.and_expr(&node.base);
+        for expr in &node.following {
+            self.and_expr(expr);
+        }
+        ty.filter(|_| node.following.is_empty())
+    }
+
+    fn and_expr(&mut self, node: &'tree AndExpr) -> Type<'tree> {
+        let ty = self.bit_or_expr(&node.base);
+        for expr in &node.following {
+            self.bit_or_expr(expr);
+        }
+        ty.filter(|_| node.following.is_empty())
+    }
+
+    fn bit_or_expr(&mut self, node: &'tree BitOrExpr) -> Type<'tree> {
+        let mut types = vec![self.bit_xor_expr(&node.base)];
+        for expr in &node.following {
+            types.push(self.bit_xor_expr(expr));
+        }
+        numeric(&types)
+    }
+
+    fn bit_xor_expr(&mut self, node: &'tree BitXorExpr) -> Type<'tree> {
+        let mut types = vec![self.bit_and_expr(&node.base)];
+        for expr in &node.following {
+            types.push(self.bit_and_expr(expr));
+        }
+        numeric(&types)
+    }
+
+    fn bit_and_expr(&mut self, node: &'tree BitAndExpr) -> Type<'tree> {
+        let mut types = vec![self.eq_expr(&node.base)];
+        for expr in &node.following {
+            types.push(self.eq_expr(expr));
+        }
+        numeric(&types)
+    }
+
+    fn eq_expr(&mut self, node: &'tree EqExpr) -> Type<'tree> {
+        let ty = self.rel_expr(&node.left);
+        match &node.right {
+            Some((_, right)) => {
+                self.rel_expr(right);
+                Some("bool")
+            }
+            None => ty,
+        }
+    }
+
+    fn rel_expr(&mut self, node: &'tree RelExpr) -> Type<'tree> {
+        let ty = self.shift_expr(&node.left);
+        match &node.right {
+            Some((_, right)) => {
+                self.shift_expr(right);
+                Some("bool")
+            }
+            None => ty,
+        }
+    }
+
+    fn shift_expr(&mut self, node: &'tree ShiftExpr) -> Type<'tree> {
+        let mut types = vec![self.add_expr(&node.base)];
+        for (_, expr) in &node.following {
+            types.push(self.add_expr(expr));
+        }
+        numeric(&types)
+    }
+
+    fn add_expr(&mut self, node: &'tree AddExpr) -> Type<'tree> {
+        let mut types = vec![self.mul_expr(&node.base)];
+        for (_, expr) in &node.following {
+            types.push(self.mul_expr(expr));
+        }
+        // adding anything to a string results in a string
+        let concatenates = node.following.iter().all(|(op, _)| *op == TokenKind::Plus)
+            && types.contains(&Some("string"));
+        match concatenates && types.len() > 1 {
+            true => Some("string"),
+            false => numeric(&types),
+        }
+    }
+
+    fn mul_expr(&mut self, node: &'tree MulExpr) -> Type<'tree> {
+        let mut types = vec![self.unary_expr(&node.base)];
+        for (_, expr) in &node.following {
+            types.push(self.unary_expr(expr));
+        }
+        numeric(&types)
+    }
+
+    fn unary_expr(&mut self, node: &'tree UnaryExpr) -> Type<'tree> {
+        match node {
+            UnaryExpr::Unary { operator, expr, .. } => {
+                let ty = self.unary_expr(expr);
+                match operator {
+                    TokenKind::Not => Some("bool"),
+                    _ => numeric(&[ty]),
+                }
+            }
+            UnaryExpr::Done(expr) => self.exp_expr(expr),
+        }
+    }
+
+    fn exp_expr(&mut self, node: &'tree ExpExpr) -> Type<'tree> {
+        let ty = self.assign_expr(&node.base);
+        match &node.exponent {
+            Some(exponent) => {
+                let exponent = self.unary_expr(exponent);
+                numeric(&[ty, exponent])
+            }
+            None => ty,
+        }
+    }
+
+    fn assign_expr(&mut self, node: &'tree AssignExpr) -> Type<'tree> {
+        let left = self.call_expr(&node.left);
+        let Some((operator, expr)) = &node.right else {
+            return left;
+        };
+        let ty = self.expression(expr);
+        match operator {
+            TokenKind::Assign => {
+                self.assign(&node.left, ty, expr.span);
+                ty
+            }
+            _ => None,
+        }
+    }
+
+    fn call_expr(&mut self, node: &'tree CallExpr) -> Type<'tree> {
+        let mut ty = self.member_expr(&node.base);
+        let mut callee = match (&node.base.base, node.base.following.as_slice()) {
+            (Atom::Identifier { name, .. }, []) => match self.lookup(name) {
+                Some(Binding::Function(decl)) => Some(Callee::Function(decl)),
+                Some(Binding::Class(decl)) => Some(Callee::Class(decl)),
+                _ => None,
+            },
+            (Atom::Identifier { name, .. }, [MemberPart::Field(_)]) => match self.lookup(name) {
+                Some(Binding::Enum(decl)) => Some(Callee::Variant(decl)),
+                _ => None,
+            },
+            _ => None,
+        };
+        for part in &node.following {
+            ty = match part {
+                CallPart::Args(args) => {
+                    let types: Vec<_> = args.iter().map(|arg| self.expression(&arg.expr)).collect();
+                    match callee.take() {
+                        Some(Callee::Function(decl)) => {
+                            let callee = format!("function '{}'", decl.ident);
+                            self.check_args(&callee, &decl.args, args, &types);
+                            match decl.is_generator {
+                                true => Some("generator"),
+                                false => decl.return_type.as_ref().and_then(single),
+                            }
+                        }
+                        Some(Callee::Class(decl)) => {
+                            if let Some(init) = self.init(&decl.ident) {
+                                let callee = format!("class '{}'", decl.ident);
+                                self.check_args(&callee, &init.args, args, &types);
+                            }
+                            Some(&decl.ident)
+                        }
+                        Some(Callee::Variant(decl)) => Some(&decl.ident),
+                        None => None,
+                    }
+                }
+                CallPart::Member(part) => {
+                    callee = None;
+                    self.member_part(ty, part)
+                }
+            };
+        }
+        ty
+    }
+
+    fn member_expr(&mut self, node: &'tree MemberExpr) -> Type<'tree> {
+        let mut ty = self.atom(&node.base);
+        let mut enum_decl = match &node.base {
+            Atom::Identifier { name, .. } => match self.lookup(name) {
+                Some(Binding::Enum(decl)) => Some(*decl),
+                _ => None,
+            },
+            _ => None,
+        };
+        for part in &node.following {
+            ty = match (enum_decl.take(), part) {
+                // variants with fields are functions until they are called
+                (Some(decl), MemberPart::Field(name)) => decl
+                    .variants
+                    .iter()
+                    .find(|variant| variant.ident == *name)
+                    .map(|variant| match variant.fields.is_empty() {
+                        true => decl.ident.as_str(),
+                        false => "function",
+                    }),
+                (_, part) => self.member_part(ty, part),
+            };
+        }
+        ty
+    }
+
+    fn member_part(&mut self, ty: Type<'tree>, node: &'tree MemberPart) -> Type<'tree> {
+        match node {
+            MemberPart::Field(name) => ty
+                .and_then(|class| self.field(class, name))
+                .and_then(single),
+            MemberPart::OptionalField(_) => None,
+            MemberPart::Index(expr) => {
+                self.expression(expr);
+                None
+            }
+        }
+    }
+
+    fn atom(&mut self, node: &'tree Atom) -> Type<'tree> {
+        match node {
+            Atom::Number(_) => Some("number"),
+            Atom::Bool(_) => Some("bool"),
+            Atom::String(_) => Some("string"),
+            Atom::InterpolatedString(node) => {
+                for part in &node.parts {
+                    if let StringPart::Expr(expr) = part {
+                        self.expression(expr);
+                    }
+                }
+                Some("string")
+            }
+            Atom::Null => Some("null"),
+            Atom::Identifier { name, .. } => self.variable(name),
+            Atom::Super { .. } => None,
+            Atom::Expr(node) => self.expression(node),
+            Atom::List(node) => {
+                for expr in node {
+                    self.expression(expr);
+                }
+                Some("list")
+            }
+            Atom::Comprehension(node) => {
+                self.scopes.push(HashMap::new());
+                for clause in &node.clauses {
+                    match clause {
+                        ComprehensionClause::For { target, iter } => {
+                            self.expression(iter);
+                            self.bind_target(target);
+                        }
+                        ComprehensionClause::If(cond) => {
+                            self.expression(cond);
+                        }
+                    }
+                }
+                self.expression(&node.expr);
+                self.scopes.pop();
+                Some("list")
+            }
+            Atom::Map(node) => {
+                for entry in node {
+                    self.expression(&entry.key);
+                    self.expression(&entry.value);
+                }
+                Some("map")
+            }
+            Atom::IfExpr(node) => {
+                self.expression(&node.cond);
+                let ty = self.block(&node.block);
+                let else_ty = node.else_block.as_ref().and_then(|block| self.block(block));
+                ty.filter(|ty| Some(*ty) == else_ty)
+            }
+            Atom::ForExpr(node) => {
+                self.expression(&node.iter);
+                self.scopes.push(HashMap::new());
+                self.bind_target(&node.target);
+                self.block(&node.block);
+                self.scopes.pop();
+                None
+            }
+            Atom::WhileExpr(node) => {
+                self.expression(&node.cond);
+                self.block(&node.block);
+                None
+            }
+            Atom::LoopExpr(node) => {
+                self.block(&node.block);
+                None
+            }
+            Atom::FunExpr(node) => {
+                self.function(
+                    None,
+                    &node.args,
+                    &node.return_type,
+                    &node.block,
+                    node.is_generator,
+                );
+                Some("function")
+            }
+            Atom::ClassExpr(node) => {
+                self.class(None, &node.parent, &node.block);
+                Some("class")
+            }
+            Atom::TryExpr(node) => {
+                self.block(&node.try_block);
+                for catch in &node.catches {
+                    if let Some(CatchFilter::Class(filter)) = &catch.filter {
+                        self.member_expr(filter);
+                    }
+                    self.scopes.push(HashMap::new());
+                    self.bind(&catch.ident, Binding::Inferred(None));
+                    self.block(&catch.block);
+                    self.scopes.pop();
+                }
+                if let Some(block) = &node.finally_block {
+                    self.block(block);
+                }
+                None
+            }
+            Atom::MatchExpr(node) => {
+                self.expression(&node.expr);
+                for arm in &node.arms {
+                    self.scopes.push(HashMap::new());
+                    self.bind_pattern(&arm.pattern);
+                    if let Some(guard) = &arm.guard {
+                        self.expression(guard);
+                    }
+                    self.block(&arm.block);
+                    self.scopes.pop();
+                }
+                None
+            }
+            Atom::BlockExpr(node) => self.block(node),
+        }
+    }
+}
+
+/// The type of a value of an operation which results in a number if all operands are numbers
+fn numeric<'tree>(types: &[Type<'tree>]) -> Type<'tree> {
+    match types {
+        [ty] => *ty,
+        _ => types
+            .iter()
+            .all(|ty| *ty == Some("number"))
+            .then_some("number"),
+    }
+}
+
+/// The type of values matching an annotation, if it only allows one type
+fn single(annotation: &TypeAnnotation) -> Type<'_> {
+    match annotation.types.as_slice() {
+        [ty] if ty != "any" => Some(ty),
+        _ => None,
+    }
+}
+
+/// The name of a member expression consisting of only an identifier
+fn identifier(node: &MemberExpr) -> Option<&str> {
+    match (&node.base, node.following.as_slice()) {
+        (Atom::Identifier { name, .. }, []) => Some(name),
+        _ => None,
+    }
+}
+
+fn describe_return(name: Option<&str>) -> String {
+    match name {
+        Some(name) => format!("Return value of function '{name}'"),
+        None => "Return value of function".to_string(),
+    }
+}
@@ -2,7 +2,8 @@ use std::{collections::HashMap, fmt::Debug};
 
 use crate::interpreter::value::{ToValue, Value};
 
-pub type Result<T> = std::result::Result<T, Error>;
+/// Errors are boxed to keep results small, they are only created on the slow path
+pub type Result<T> = std::result::Result<T, Box<Error>>;
 
 macro_rules! error_val {
     ($kind:ident, ($start:expr, $end:expr), $($arg:tt)*) => {
@@ -19,22 +20,63 @@ macro_rules! error_val {
 
 macro_rules! error {
     ($($arg:tt)*) => {
-        return Err(error_val!($($arg)*))
+        return Err(Box::new(error_val!($($arg)*)))
     };
 }
 
 /////////////////////////////////////////////
 
+/// Identifies the source code a [`Location`] belongs to, see [`SourceMap`]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct SourceId(pub usize);
+
+/// Holds the names and code of all sources, so that errors can be shown
+/// with the lines they refer to
+#[derive(Default)]
+pub struct SourceMap {
+    sources: Vec<(String, String)>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, name: impl Into<String>, code: impl Into<String>) -> SourceId {
+        self.sources.push((name.into(), code.into()));
+        SourceId(self.sources.len() - 1)
+    }
+
+    pub fn name(&self, id: SourceId) -> &str {
+        self.sources
+            .get(id.0)
+            .map_or("<unknown>", |(name, _)| name.as_str())
+    }
+
+    pub fn code(&self, id: SourceId) -> &str {
+        self.sources.get(id.0).map_or("", |(_, code)| code.as_str())
+    }
+
+    /// Returns the line with the given number, starting at 1
+    pub fn line(&self, id: SourceId, line: usize) -> Option<&str> {
+        self.code(id).split('\n').nth(line.checked_sub(1)?)
+    }
+}
+
+/////////////////////////////////////////////
+
 #[derive(Clone, Copy, PartialEq)]
 pub struct Location {
+    pub source: SourceId,
     pub line: usize,
     pub column: usize,
     pub index: usize,
 }
 
 impl Location {
-    pub fn new() -> Self {
+    pub fn new(source: SourceId) -> Self {
         Self {
+            source,
             line: 1,
             column: 1,
             index: 0,
@@ -54,7 +96,7 @@ impl Location {
 
 impl Default for Location {
     fn default() -> Self {
-        Self::new()
+        Self::new(SourceId::default())
     }
 }
 
@@ -66,11 +108,14 @@ impl Debug for Location {
 
 impl ToValue for Location {
     fn to_value<'tree>(&self) -> Value<'tree> {
-        Value::Object(HashMap::from([
-            ("line", Value::Number(self.line.into()).wrapped()),
-            ("column", Value::Number(self.column.into()).wrapped()),
-            ("index", Value::Number(self.index.into()).wrapped()),
-        ]))
+        Value::Object {
+            fields: HashMap::from([
+                ("line", Value::Number(self.line.into()).wrapped()),
+                ("column", Value::Number(self.column.into()).wrapped()),
+                ("index", Value::Number(self.index.into()).wrapped()),
+            ]),
+            class: None,
+        }
     }
 }
 
@@ -86,6 +131,10 @@ impl Span {
     pub fn new(start: Location, end: Location) -> Self {
         Self { start, end }
     }
+
+    pub fn source(&self) -> SourceId {
+        self.start.source
+    }
 }
 
 impl Debug for Span {
@@ -96,10 +145,51 @@ impl Debug for Span {
 
 impl ToValue for Span {
     fn to_value<'tree>(&self) -> Value<'tree> {
-        Value::Object(HashMap::from([
-            ("start", self.start.to_value().wrapped()),
-            ("end", self.end.to_value().wrapped()),
-        ]))
+        Value::Object {
+            fields: HashMap::from([
+                ("start", self.start.to_value().wrapped()),
+                ("end", self.end.to_value().wrapped()),
+            ]),
+            class: None,
+        }
+    }
+}
+
+/////////////////////////////////////////////
+
+#[derive(Clone, PartialEq)]
+pub struct Frame {
+    pub name: Option<String>,
+    pub span: Span,
+}
+
+impl Debug for Frame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "at {} ({:?})",
+            self.name.as_deref().unwrap_or("<anonymous>"),
+            self.span,
+        )
+    }
+}
+
+impl ToValue for Frame {
+    fn to_value<'tree>(&self) -> Value<'tree> {
+        Value::Object {
+            fields: HashMap::from([
+                (
+                    "name",
+                    match &self.name {
+                        Some(name) => Value::String(name.clone()),
+                        None => Value::Null,
+                    }
+                    .wrapped(),
+                ),
+                ("span", self.span.to_value().wrapped()),
+            ]),
+            class: None,
+        }
     }
 }
 
@@ -110,6 +200,13 @@ pub struct Error {
     pub kind: ErrorKind,
     pub message: String,
     pub span: Span,
+    /// The calls the error propagated through, innermost first
+    pub stack: Vec<Frame>,
+    /// Identifies the value passed to `throw`, which is kept by the backend until it is caught
+    pub thrown: Option<usize>,
+    /// Errors which are reported together with this one, like the other syntax errors
+    /// of an imported module
+    pub related: Vec<Error>,
 }
 
 impl Error {
@@ -118,29 +215,62 @@ impl Error {
             kind,
             message,
             span,
+            stack: vec![],
+            thrown: None,
+            related: vec![],
         }
     }
+
+    pub fn source(&self) -> SourceId {
+        self.span.source()
+    }
+
+    pub fn with_frame(mut self: Box<Self>, name: Option<&str>, span: Span) -> Box<Self> {
+        self.stack.push(Frame {
+            name: name.map(str::to_string),
+            span,
+        });
+        self
+    }
 }
 
 impl Debug for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?} at {:?}  {}", self.kind, self.span, self.message,)
+        write!(f, "{:?} at {:?}  {}", self.kind, self.span, self.message,)?;
+        for frame in &self.stack {
+            write!(f, "\n    {:?}", frame)?;
+        }
+        Ok(())
     }
 }
 
 impl ToValue for Error {
     fn to_value<'tree>(&self) -> Value<'tree> {
-        Value::Object(HashMap::from([
-            ("kind", Value::String(format!("{:?}", self.kind)).wrapped()),
-            ("message", Value::String(self.message.clone()).wrapped()),
-            ("span", self.span.to_value().wrapped()),
-        ]))
+        Value::Object {
+            fields: HashMap::from([
+                ("kind", Value::String(format!("{:?}", self.kind)).wrapped()),
+                ("message", Value::String(self.message.clone()).wrapped()),
+                ("span", self.span.to_value().wrapped()),
+                (
+                    "stack",
+                    Value::List(
+                        self.stack
+                            .iter()
+                            .map(|frame| frame.to_value().wrapped())
+                            .collect(),
+                    )
+                    .wrapped(),
+                ),
+                ("cause", Value::Null.wrapped()),
+            ]),
+            class: None,
+        }
     }
 }
 
 /////////////////////////////////////////////
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 #[allow(clippy::enum_variant_names)]
 pub enum ErrorKind {
     SyntaxError,
@@ -151,4 +281,22 @@ pub enum ErrorKind {
     OverflowError,
     SystemError,
     RuntimeError,
+    ImportError,
+}
+
+impl ErrorKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "SyntaxError" => Self::SyntaxError,
+            "TypeError" => Self::TypeError,
+            "ReferenceError" => Self::ReferenceError,
+            "ValueError" => Self::ValueError,
+            "DivisionByZeroError" => Self::DivisionByZeroError,
+            "OverflowError" => Self::OverflowError,
+            "SystemError" => Self::SystemError,
+            "RuntimeError" => Self::RuntimeError,
+            "ImportError" => Self::ImportError,
+            _ => return None,
+        })
+    }
 }
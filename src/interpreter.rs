@@ -1,35 +1,68 @@
 #[macro_use]
-mod built_in;
+pub(crate) mod built_in;
+pub mod generator;
+pub(crate) mod locks;
+pub mod modules;
 mod runtime_result;
+pub(crate) mod thrown;
 pub mod value;
 
+use indexmap::IndexMap;
 use rust_decimal::{prelude::ToPrimitive, Decimal};
 
 #[cfg(feature = "no_std_io")]
 use crate::io::Write;
 use crate::{
-    error::{Result, Span},
+    error::{Error, Result, SourceId, SourceMap, Span},
+    lexer::Lexer,
     nodes::*,
+    parser::Parser,
     tokens::TokenKind,
 };
 #[cfg(not(feature = "no_std_io"))]
 use std::io::Write;
-use std::{collections::HashMap, mem, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    mem,
+    ops::ControlFlow,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
 use self::{
+    generator::Generator,
+    locks::Locks,
+    modules::{Loader, ProgramArena},
     runtime_result::RuntimeResult,
+    thrown::Thrown,
     value::{
+        indexing::ToMapKey,
         members::BuiltInMethods,
         types::{self, Type},
-        BuiltIn, ToValue, Value, WrappedValue,
+        BuiltIn, ClassRef, Environment, Scope, Value, Variable, WrappedValue,
     },
 };
 
+/// An evaluated call argument and its name, if it was passed by name
+type Argument<'tree> = (Option<&'tree str>, WrappedValue<'tree>);
+
+/// The place an assignment stores its value in
+enum Place<'tree> {
+    Variable(&'tree str),
+    Field(WrappedValue<'tree>, &'tree str),
+    Index(WrappedValue<'tree>, WrappedValue<'tree>),
+}
+
 macro_rules! try_visit {
-    ($call:expr) => {{
+    ($call:expr) => {
+        try_visit!($call; std::convert::identity)
+    };
+    // `$wrap` turns the early result into the return value of the function
+    ($call:expr; $wrap:expr) => {{
         let res: RuntimeResult = $call;
         if res.should_return() {
-            return Ok(res);
+            return Ok($wrap(res));
         }
         res.take_value()
     }};
@@ -46,29 +79,26 @@ macro_rules! simple_expr {
     (@kind $self:ident, $node:ident, $base:ident, $next:ident, $method:ident) => {
         for other in &$node.following {
             let other = try_visit!($self.$next(other)?);
-            let out = $base
-                .borrow()
-                .$method(&other.borrow(), &$node.span)?
-                .wrapped();
-            $base = out;
+            $base = simple_expr!(@op $self, $node, $base, other, $method)?;
         }
     };
     (@kind $self:ident, $node:ident, $base:ident, $next:ident, $($tok:ident => $method:ident),+ $(,)?) => {
         for (tok, other) in &$node.following {
             let other = try_visit!($self.$next(other)?);
-            let out = match tok {
-                $(TokenKind::$tok => $base.borrow().$method(&other.borrow(), &$node.span),)+
+            $base = match tok {
+                $(TokenKind::$tok => simple_expr!(@op $self, $node, $base, other, $method),)+
                 _ => unreachable!(),
-            }?
-            .wrapped();
-            $base = out;
+            }?;
         }
     };
-}
-
-macro_rules! built_in {
-    ($name:literal, $($built_in:tt)*) => {
-        ($name, Value::BuiltIn(BuiltIn::$($built_in)*).wrapped())
+    (@op $self:ident, $node:ident, $base:ident, $other:ident, $method:ident) => {
+        $self.binary_op(
+            &$base,
+            &$other,
+            concat!("__", stringify!($method), "__"),
+            Value::$method,
+            &$node.span,
+        )
     };
 }
 
@@ -79,12 +109,27 @@ where
     Exit: FnOnce(i32),
 {
     program: &'tree Program,
-    pub scopes: Vec<HashMap<&'tree str, WrappedValue<'tree>>>,
-    pub scope_idx: usize,
+    pub scopes: Vec<Scope<'tree>>,
     stdout: StdOut,
     stderr: StdErr,
     exit_callback: Option<Exit>,
     built_in_methods: BuiltInMethods<'tree>,
+    path: PathBuf,
+    loader: Loader<'tree>,
+    modules: HashMap<PathBuf, WrappedValue<'tree>>,
+    /// Holds the trees of imported modules, imports fail if it is not set
+    module_trees: Option<&'tree ProgramArena>,
+    /// The path of every imported module by the source of its code, the imports of a module
+    /// are resolved relative to it
+    module_paths: HashMap<SourceId, PathBuf>,
+    loading: Vec<PathBuf>,
+    sources: SourceMap,
+    locks: Locks<'tree>,
+    thrown: Thrown<'tree>,
+    /// The number of function calls which can be nested, if limited
+    max_depth: Option<usize>,
+    /// The number of function calls which are currently running
+    depth: usize,
 }
 
 impl<'tree, StdOut, StdErr, Exit> Interpreter<'tree, StdOut, StdErr, Exit>
@@ -99,75 +144,105 @@ where
         stderr: StdErr,
         exit_callback: Exit,
     ) -> Self {
+        // until other sources are set, the program is assumed to be the first one
+        let mut sources = SourceMap::new();
+        sources.add("", "");
         Self {
             program,
-            scopes: vec![HashMap::from([
-                built_in!(
-                    "print",
-                    Print {
-                        newline: false,
-                        stderr: false
-                    },
-                ),
-                built_in!(
-                    "printl",
-                    Print {
-                        newline: true,
-                        stderr: false
-                    },
-                ),
-                built_in!(
-                    "eprint",
-                    Print {
-                        newline: false,
-                        stderr: true
-                    },
-                ),
-                built_in!(
-                    "eprintl",
-                    Print {
-                        newline: true,
-                        stderr: true
-                    },
-                ),
-                built_in!("typeOf", Function(built_in::type_of)),
-                built_in!("assert", Function(built_in::assert)),
-                built_in!("throw", Function(built_in::throw)),
-                built_in!("exit", Exit),
-                built_in!("debug", Debug),
-                ("answer", Value::Number(42.into()).wrapped()),
-            ])],
-            scope_idx: 0,
+            scopes: vec![Rc::new(RefCell::new(
+                built_in::built_ins()
+                    .into_iter()
+                    .map(|(name, value)| (name, Variable::new(value)))
+                    .collect(),
+            ))],
             stdout,
             stderr,
             exit_callback: Some(exit_callback),
             built_in_methods: BuiltInMethods::new(),
+            path: PathBuf::new(),
+            loader: Box::new(modules::default_loader),
+            modules: HashMap::new(),
+            module_trees: None,
+            module_paths: HashMap::new(),
+            loading: vec![],
+            sources,
+            locks: Locks::default(),
+            thrown: Thrown::default(),
+            max_depth: None,
+            depth: 0,
         }
     }
 
-    #[inline]
+    /// Limits the number of nested function calls, deeper calls fail with a `RuntimeError`
+    /// instead of overflowing the stack
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Sets the sources known so far, imported modules are added to them
+    pub fn with_sources(mut self, sources: SourceMap) -> Self {
+        self.sources = sources;
+        self
+    }
+
+    pub fn sources(&self) -> &SourceMap {
+        &self.sources
+    }
+
+    pub fn into_sources(self) -> SourceMap {
+        self.sources
+    }
+
+    /// Sets the path of the program, imports are resolved relative to it
+    pub fn with_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = path.into();
+        self
+    }
+
+    /// Sets the function used to read the source of imported modules
+    pub fn with_loader(
+        mut self,
+        loader: impl FnMut(&Path) -> std::result::Result<String, String> + 'tree,
+    ) -> Self {
+        self.loader = Box::new(loader);
+        self
+    }
+
+    /// Sets the storage for the trees of imported modules, it has to outlive the interpreter
+    pub fn with_module_trees(mut self, module_trees: &'tree ProgramArena) -> Self {
+        self.module_trees = Some(module_trees);
+        self
+    }
+
     pub fn run(&mut self, new_scope: bool) -> Result<RuntimeResult<'tree>> {
-        self.visit_program(self.program, new_scope)
+        // the program cannot be imported by the modules it imports
+        self.loading.push(modules::normalize(&self.path));
+        let res = self.visit_program(self.program, new_scope);
+        self.loading.pop();
+        res
     }
 
     fn push_scope(&mut self) {
-        self.scopes.push(HashMap::new());
-        self.scope_idx += 1;
+        self.scopes.push(Scope::default());
     }
 
     fn pop_scope(&mut self) {
         self.scopes.pop();
-        self.scope_idx -= 1;
     }
 
     fn add_var(&mut self, name: &'tree str, value: WrappedValue<'tree>) {
-        self.scopes.last_mut().unwrap().insert(name, value);
+        self.scopes
+            .last()
+            .unwrap()
+            .borrow_mut()
+            .insert(name, Variable::new(value));
     }
 
-    fn get_var(&self, name: &str, span: &Span) -> Result<(&WrappedValue<'tree>, usize)> {
-        for (idx, scope) in self.scopes.iter().enumerate().rev() {
-            if let Some(var) = scope.get(name) {
-                return Ok((var, idx));
+    fn get_var(&self, name: &str, span: &Span) -> Result<WrappedValue<'tree>> {
+        for scope in self.scopes.iter().rev() {
+            if let Some(var) = scope.borrow().get(name) {
+                return Ok(Rc::clone(&var.value));
             }
         }
         error!(
@@ -176,6 +251,66 @@ where
         );
     }
 
+    /// Like `get_var`, but fails if the variable is a constant
+    fn get_reassignable_var(&self, name: &str, span: &Span) -> Result<WrappedValue<'tree>> {
+        for scope in self.scopes.iter().rev() {
+            if let Some(var) = scope.borrow().get(name) {
+                if let Some(declared) = var.constant {
+                    error!(
+                        TypeError,
+                        *span,
+                        "Cannot reassign constant '{}' declared at {:?}",
+                        name,
+                        declared.start,
+                    );
+                }
+                return Ok(Rc::clone(&var.value));
+            }
+        }
+        error!(
+            ReferenceError,
+            *span, "Variable with name '{}' not found", name,
+        );
+    }
+
+    /// Binds the variable `name` to a new value
+    fn set_var(&self, name: &str, value: WrappedValue<'tree>, span: &Span) -> Result<()> {
+        for scope in self.scopes.iter().rev() {
+            if let Some(var) = scope.borrow_mut().get_mut(name) {
+                var.value = value;
+                return Ok(());
+            }
+        }
+        error!(
+            ReferenceError,
+            *span, "Variable with name '{}' not found", name,
+        );
+    }
+
+    /// Fails if another call would be nested too deeply
+    fn check_depth(&self, span: &Span) -> Result<()> {
+        if self.max_depth == Some(self.depth) {
+            error!(
+                RuntimeError,
+                *span, "Maximum call depth of {} exceeded", self.depth,
+            );
+        }
+        Ok(())
+    }
+
+    /// Runs `func` with the scopes of `env` in place of the current ones and
+    /// restores the current scopes afterwards, even if an error occurred.
+    fn with_env<T>(
+        &mut self,
+        env: &Environment<'tree>,
+        func: impl FnOnce(&mut Self) -> Result<T>,
+    ) -> Result<T> {
+        let scopes = mem::replace(&mut self.scopes, env.0.clone());
+        let res = func(self);
+        self.scopes = scopes;
+        res
+    }
+
     ////////////////////////////////////////
 
     #[inline]
@@ -222,9 +357,14 @@ where
             Statement::Var(node) => self.visit_var_stmt(node),
             Statement::Function(node) => self.visit_function_decl(node),
             Statement::Class(node) => self.visit_class_decl(node),
+            Statement::Enum(node) => self.visit_enum_decl(node),
             Statement::Break(node) => self.visit_break_stmt(node),
             Statement::Continue(node) => self.visit_continue_stmt(node),
             Statement::Return(node) => self.visit_return_stmt(node),
+            // generators run their statements themselves and the parser rejects a `yield`
+            // anywhere they cannot suspend
+            Statement::Yield(_) => unreachable!("'yield' should only be run by generators"),
+            Statement::Import(node) => self.visit_import_stmt(node),
             Statement::Expr(node) => self.visit_expression(node),
         }
     }
@@ -234,16 +374,114 @@ where
             Some(node) => try_visit!(self.visit_expression(node)?),
             None => Value::Null.wrapped(),
         };
-        self.add_var(&node.ident, val);
+        let mut bindings = vec![];
+        Self::destructure(&node.target, val, &mut bindings)?;
+        for (name, value) in bindings {
+            self.scopes.last().unwrap().borrow_mut().insert(
+                name,
+                Variable {
+                    value,
+                    constant: node.is_const.then_some(node.span),
+                },
+            );
+        }
         Ok(RuntimeResult::new(None))
     }
 
+    /// Collects the variables of a target and the parts of `value` bound to them
+    fn destructure(
+        target: &'tree Target,
+        value: WrappedValue<'tree>,
+        bindings: &mut Vec<(&'tree str, WrappedValue<'tree>)>,
+    ) -> Result<()> {
+        match target {
+            Target::Ident(name) => bindings.push((name, value)),
+            Target::List {
+                span,
+                before,
+                rest,
+                after,
+            } => {
+                let items = match &*value.borrow() {
+                    Value::List(items) => items.clone(),
+                    value => error!(
+                        TypeError,
+                        *span,
+                        "Cannot destructure type '{}' as a list",
+                        types::type_of(value),
+                    ),
+                };
+                let fixed = before.len() + after.len();
+                if rest.is_none() && items.len() != fixed {
+                    error!(
+                        ValueError,
+                        *span,
+                        "Expected {} items, found {}",
+                        fixed,
+                        items.len(),
+                    );
+                } else if items.len() < fixed {
+                    error!(
+                        ValueError,
+                        *span,
+                        "Expected at least {} items, found {}",
+                        fixed,
+                        items.len(),
+                    );
+                }
+                let rest_end = items.len() - after.len();
+                for (target, item) in before
+                    .iter()
+                    .zip(&items)
+                    .chain(after.iter().zip(&items[rest_end..]))
+                {
+                    Self::destructure(target, Rc::clone(item), bindings)?;
+                }
+                if let Some(Some(name)) = rest {
+                    let rest = items[before.len()..rest_end].to_vec();
+                    bindings.push((name, Value::List(rest).wrapped()));
+                }
+            }
+            Target::Object { span, fields } => {
+                let values = match &*value.borrow() {
+                    Value::Object { fields: object, .. } => fields
+                        .iter()
+                        .map(|(name, _)| match object.get(name.as_str()) {
+                            Some(field) => Ok(Rc::clone(field)),
+                            None => Err(Box::new(error_val!(
+                                ValueError,
+                                *span,
+                                "Object has no member called '{}'",
+                                name,
+                            ))),
+                        })
+                        .collect::<Result<Vec<_>>>()?,
+                    value => error!(
+                        TypeError,
+                        *span,
+                        "Cannot destructure type '{}' as an object",
+                        types::type_of(value),
+                    ),
+                };
+                for ((name, target), field) in fields.iter().zip(values) {
+                    match target {
+                        Some(target) => Self::destructure(target, field, bindings)?,
+                        None => bindings.push((name, field)),
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn visit_function_decl(&mut self, node: &'tree FunctionDecl) -> Result<RuntimeResult<'tree>> {
         self.add_var(
             &node.ident,
             Value::Function {
                 args: &node.args,
                 block: &node.block,
+                env: Environment(self.scopes.clone()),
+                is_generator: node.is_generator,
             }
             .wrapped(),
         );
@@ -251,53 +489,26 @@ where
     }
 
     fn visit_class_decl(&mut self, node: &'tree ClassDecl) -> Result<RuntimeResult<'tree>> {
-        let class = Value::Null.wrapped();
-        let mut statics: HashMap<&str, _> = HashMap::new();
-        let mut non_statics = vec![];
-        for member in &node.block.members {
-            match (member.is_static, &member.kind) {
-                (true, MemberKind::Attribute(node)) => {
-                    statics.insert(
-                        &node.ident,
-                        match &node.expr {
-                            Some(node) => try_visit!(self.visit_expression(node)?),
-                            None => Value::Null.wrapped(),
-                        },
-                    );
-                }
-                (true, MemberKind::Method(node)) => {
-                    statics.insert(
-                        &node.ident,
-                        Value::Function {
-                            args: &node.args,
-                            block: &node.block,
-                        }
-                        .wrapped(),
-                    );
-                }
-                (false, member) => {
-                    non_statics.push(member);
-                }
-            }
-        }
-        *class.borrow_mut() = Value::Class {
-            statics,
-            non_statics,
-        };
+        let class = try_visit!(self.make_class(node.parent.as_deref(), &node.block)?);
         self.add_var(&node.ident, class);
         Ok(RuntimeResult::new(None))
     }
 
+    fn visit_enum_decl(&mut self, node: &'tree EnumDecl) -> Result<RuntimeResult<'tree>> {
+        self.add_var(&node.ident, Value::Enum(node).wrapped());
+        Ok(RuntimeResult::new(None))
+    }
+
     fn visit_break_stmt(&mut self, node: &'tree BreakStmt) -> Result<RuntimeResult<'tree>> {
         let val = match &node.expr {
             Some(node) => try_visit!(self.visit_expression(node)?),
             None => Value::Null.wrapped(),
         };
-        Ok(RuntimeResult::success_break(val))
+        Ok(RuntimeResult::success_break(val, node.label.as_deref()))
     }
 
-    fn visit_continue_stmt(&mut self, _: &'tree ContinueStmt) -> Result<RuntimeResult<'tree>> {
-        Ok(RuntimeResult::success_continue())
+    fn visit_continue_stmt(&mut self, node: &'tree ContinueStmt) -> Result<RuntimeResult<'tree>> {
+        Ok(RuntimeResult::success_continue(node.label.as_deref()))
     }
 
     fn visit_return_stmt(&mut self, node: &'tree ReturnStmt) -> Result<RuntimeResult<'tree>> {
@@ -308,17 +519,115 @@ where
         Ok(RuntimeResult::success_return(val))
     }
 
+    fn visit_import_stmt(&mut self, node: &'tree ImportStmt) -> Result<RuntimeResult<'tree>> {
+        let importer = self
+            .module_paths
+            .get(&node.span.source())
+            .unwrap_or(&self.path);
+        let path = modules::normalize(
+            &importer
+                .parent()
+                .unwrap_or_else(|| Path::new(""))
+                .join(&node.path),
+        );
+
+        if let Some(module) = self.modules.get(&path) {
+            let module = Rc::clone(module);
+            self.add_var(&node.ident, module);
+            return Ok(RuntimeResult::new(None));
+        }
+        if let Some(idx) = self.loading.iter().position(|module| module == &path) {
+            error!(
+                ImportError,
+                node.span,
+                "Circular import: {} -> {}",
+                self.loading[idx..]
+                    .iter()
+                    .map(|module| module.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(" -> "),
+                path.display(),
+            );
+        }
+
+        let module_trees = match self.module_trees {
+            Some(module_trees) => module_trees,
+            None => error!(
+                ImportError,
+                node.span,
+                "Could not load module '{}': no storage for module trees was provided",
+                path.display(),
+            ),
+        };
+        let code = match (self.loader)(&path) {
+            Ok(code) => code,
+            Err(e) => error!(
+                ImportError,
+                node.span,
+                "Could not load module '{}': {}",
+                path.display(),
+                e,
+            ),
+        };
+        let source = self.sources.add(path.display().to_string(), code.as_str());
+        self.module_paths.insert(source, path.clone());
+        let program = match Parser::new(Lexer::new(&code, source)).parse() {
+            Ok(program) => program,
+            Err(mut errors) => {
+                let mut error = errors.remove(0);
+                error.related = errors;
+                return Err(Box::new(error));
+            }
+        };
+        let program = module_trees.add(program);
+
+        let scope = Scope::default();
+        let env = Environment(vec![Rc::clone(&self.scopes[0]), Rc::clone(&scope)]);
+        self.loading.push(path.clone());
+        let res = self.with_env(&env, |interpreter| {
+            interpreter.visit_program(program, false)
+        });
+        self.loading.pop();
+        res?;
+
+        let module = Value::Object {
+            fields: scope
+                .borrow()
+                .iter()
+                .map(|(name, var)| (*name, Rc::clone(&var.value)))
+                .collect(),
+            class: None,
+        }
+        .wrapped();
+        self.modules.insert(path, Rc::clone(&module));
+        self.add_var(&node.ident, module);
+        Ok(RuntimeResult::new(None))
+    }
+
     #[inline]
     fn visit_expression(&mut self, node: &'tree Expression) -> Result<RuntimeResult<'tree>> {
-        self.visit_range_expr(node)
+        self.visit_pipe_expr(node)
+    }
+
+    fn visit_pipe_expr(&mut self, node: &'tree PipeExpr) -> Result<RuntimeResult<'tree>> {
+        let mut value = try_visit!(self.visit_range_expr(&node.base)?);
+        for stage in &node.stages {
+            // the placeholder arguments of the stage refer to the previous value
+            self.push_scope();
+            self.add_var("_", value);
+            let res = self.visit_call_expr(stage);
+            self.pop_scope();
+            value = try_visit!(res?);
+        }
+        Ok(RuntimeResult::new(Some(value)))
     }
 
     fn visit_range_expr(&mut self, node: &'tree RangeExpr) -> Result<RuntimeResult<'tree>> {
         match node {
-            RangeExpr::None(node) => self.visit_or_expr(node),
+            RangeExpr::None(node) => self.visit_coalesce_expr(node),
             RangeExpr::Closed(left, tok, right, span) => {
-                let left = try_visit!(self.visit_or_expr(left)?);
-                let right = try_visit!(self.visit_or_expr(right)?);
+                let left = try_visit!(self.visit_coalesce_expr(left)?);
+                let right = try_visit!(self.visit_coalesce_expr(right)?);
                 let inclusive = tok == &TokenKind::DotsInclusive;
                 let range = match (&*left.borrow(), &*right.borrow()) {
                     (Value::Number(start), Value::Number(end)) => {
@@ -338,7 +647,7 @@ where
                 Ok(RuntimeResult::new(Some(range.wrapped())))
             }
             RangeExpr::OpenEnd(left, span) => {
-                let left = try_visit!(self.visit_or_expr(left)?);
+                let left = try_visit!(self.visit_coalesce_expr(left)?);
                 let range = match &*left.borrow() {
                     Value::Number(start) => {
                         if !start.fract().is_zero() {
@@ -355,7 +664,7 @@ where
                 Ok(RuntimeResult::new(Some(range.wrapped())))
             }
             RangeExpr::OpenStart(tok, right, span) => {
-                let right = try_visit!(self.visit_or_expr(right)?);
+                let right = try_visit!(self.visit_coalesce_expr(right)?);
                 let inclusive = tok == &TokenKind::DotsInclusive;
                 let range = match &*right.borrow() {
                     Value::Number(end) => {
@@ -383,6 +692,17 @@ where
         }
     }
 
+    fn visit_coalesce_expr(&mut self, node: &'tree CoalesceExpr) -> Result<RuntimeResult<'tree>> {
+        let mut base = try_visit!(self.visit_or_expr(&node.base)?);
+        for other in &node.following {
+            if !matches!(&*base.borrow(), Value::Null) {
+                break;
+            }
+            base = try_visit!(self.visit_or_expr(other)?);
+        }
+        Ok(RuntimeResult::new(Some(base)))
+    }
+
     fn visit_or_expr(&mut self, node: &'tree OrExpr) -> Result<RuntimeResult<'tree>> {
         let base = try_visit!(self.visit_and_expr(&node.base)?);
         if !node.following.is_empty() {
@@ -426,9 +746,22 @@ where
         let left = try_visit!(self.visit_rel_expr(&node.left)?);
         let out = if let Some((tok, right)) = &node.right {
             let right = try_visit!(self.visit_rel_expr(right)?);
+            let equal =
+                match self.call_special(&left, "__eq__", vec![Rc::clone(&right)], &node.span)? {
+                    Some(out) => out.borrow().is_true(),
+                    None => match self.call_special(
+                        &right,
+                        "__eq__",
+                        vec![Rc::clone(&left)],
+                        &node.span,
+                    )? {
+                        Some(out) => out.borrow().is_true(),
+                        None => left == right,
+                    },
+                };
             Value::Bool(match tok {
-                TokenKind::Equal => left == right,
-                TokenKind::NotEqual => left != right,
+                TokenKind::Equal => equal,
+                TokenKind::NotEqual => !equal,
                 _ => unreachable!(),
             })
             .wrapped()
@@ -442,14 +775,32 @@ where
         let left = try_visit!(self.visit_shift_expr(&node.left)?);
         let out = if let Some((tok, right)) = &node.right {
             let right = try_visit!(self.visit_shift_expr(right)?);
-            match tok {
-                TokenKind::LessThan => left.borrow().lt(&right.borrow(), &node.span),
-                TokenKind::LessThanOrEqual => left.borrow().le(&right.borrow(), &node.span),
-                TokenKind::GreaterThan => left.borrow().gt(&right.borrow(), &node.span),
-                TokenKind::GreaterThanOrEqual => left.borrow().ge(&right.borrow(), &node.span),
+            // if only the right operand overloads the comparison, the reflected method is used
+            let (name, reflected) = match tok {
+                TokenKind::LessThan => ("__lt__", "__gt__"),
+                TokenKind::LessThanOrEqual => ("__le__", "__ge__"),
+                TokenKind::GreaterThan => ("__gt__", "__lt__"),
+                TokenKind::GreaterThanOrEqual => ("__ge__", "__le__"),
                 _ => unreachable!(),
-            }?
-            .wrapped()
+            };
+            if let Some(out) =
+                self.call_special(&left, name, vec![Rc::clone(&right)], &node.span)?
+            {
+                out
+            } else if let Some(out) =
+                self.call_special(&right, reflected, vec![Rc::clone(&left)], &node.span)?
+            {
+                out
+            } else {
+                match tok {
+                    TokenKind::LessThan => left.borrow().lt(&right.borrow(), &node.span),
+                    TokenKind::LessThanOrEqual => left.borrow().le(&right.borrow(), &node.span),
+                    TokenKind::GreaterThan => left.borrow().gt(&right.borrow(), &node.span),
+                    TokenKind::GreaterThanOrEqual => left.borrow().ge(&right.borrow(), &node.span),
+                    _ => unreachable!(),
+                }?
+                .wrapped()
+            }
         } else {
             left
         };
@@ -501,63 +852,296 @@ where
         let mut base = try_visit!(self.visit_assign_expr(&node.base)?);
         if let Some(exponent) = &node.exponent {
             let exponent = try_visit!(self.visit_unary_expr(exponent)?);
-            let out = base.borrow().pow(&exponent.borrow(), &node.span)?.wrapped();
-            base = out;
+            base = self.binary_op(&base, &exponent, "__pow__", Value::pow, &node.span)?;
         }
         Ok(RuntimeResult::new(Some(base)))
     }
 
     fn visit_assign_expr(&mut self, node: &'tree AssignExpr) -> Result<RuntimeResult<'tree>> {
-        let left = try_visit!(self.visit_call_expr(&node.left)?);
-        if let Some((tok, right)) = &node.right {
-            let left_type = types::type_of(&left.borrow());
-            if let Type::Class | Type::Object | Type::Range = left_type {
-                error!(TypeError, node.span, "Cannot reassign type '{}'", left_type,);
-            }
-            let right = try_visit!(self.visit_expression(right)?);
-            let new_value = match tok {
-                TokenKind::Assign => right.borrow().clone(),
-                TokenKind::StarAssign => left.borrow().mul(&right.borrow(), &node.span)?,
-                TokenKind::SlashAssign => left.borrow().div(&right.borrow(), &node.span)?,
-                TokenKind::BackslashAssign => {
-                    left.borrow().div_floor(&right.borrow(), &node.span)?
-                }
-                TokenKind::RemAssign => left.borrow().rem(&right.borrow(), &node.span)?,
-                TokenKind::PlusAssign => left.borrow().add(&right.borrow(), &node.span)?,
-                TokenKind::MinusAssign => left.borrow().sub(&right.borrow(), &node.span)?,
-                TokenKind::ShiftLeftAssign => left.borrow().shl(&right.borrow(), &node.span)?,
-                TokenKind::ShiftRightAssign => left.borrow().shr(&right.borrow(), &node.span)?,
-                TokenKind::BitAndAssign => left.borrow().and(&right.borrow(), &node.span)?,
-                TokenKind::BitXorAssign => left.borrow().xor(&right.borrow(), &node.span)?,
-                TokenKind::BitOrAssign => left.borrow().or(&right.borrow(), &node.span)?,
-                _ => unreachable!(),
+        let (tok, right) = match &node.right {
+            Some((tok, right)) => (tok, right),
+            None => return self.visit_call_expr(&node.left),
+        };
+
+        if let (TokenKind::Assign, Atom::List(targets), [], []) = (
+            tok,
+            &node.left.base.base,
+            node.left.base.following.as_slice(),
+            node.left.following.as_slice(),
+        ) {
+            return self.visit_list_assign(targets, right, &node.span);
+        }
+
+        let place = match self.visit_place(&node.left)? {
+            ControlFlow::Continue(place) => place,
+            ControlFlow::Break(res) => return Ok(res),
+        };
+        let left = self.place_value(
+            &place,
+            matches!(tok, TokenKind::Assign | TokenKind::CoalesceAssign),
+            &node.span,
+        )?;
+
+        if tok == &TokenKind::CoalesceAssign && !matches!(&*left.borrow(), Value::Null) {
+            return Ok(RuntimeResult::new(Some(left)));
+        }
+
+        let left_type = types::type_of(&left.borrow());
+        // objects can still be updated by operators they overload
+        if matches!(left_type, Type::Class | Type::Range)
+            || (left_type == Type::Object && tok == &TokenKind::Assign)
+        {
+            error!(TypeError, node.span, "Cannot reassign type '{}'", left_type,);
+        }
+        let right = try_visit!(self.visit_expression(right)?);
+        macro_rules! op {
+            ($method:ident) => {
+                self.binary_op(
+                    &left,
+                    &right,
+                    concat!("__", stringify!($method), "__"),
+                    Value::$method,
+                    &node.span,
+                )?
+                .borrow()
+                .clone()
             };
-            let new_type = types::type_of(&new_value);
-            if left_type != new_type && left_type != Type::Null && new_type != Type::Null {
-                error!(
-                    TypeError,
-                    node.span, "Cannot change type by reassigning, create a new variable instead",
-                );
+        }
+        let new_value = match tok {
+            TokenKind::Assign | TokenKind::CoalesceAssign => right.borrow().clone(),
+            TokenKind::StarAssign => op!(mul),
+            TokenKind::SlashAssign => op!(div),
+            TokenKind::BackslashAssign => op!(div_floor),
+            TokenKind::RemAssign => op!(rem),
+            TokenKind::PlusAssign => op!(add),
+            TokenKind::MinusAssign => op!(sub),
+            TokenKind::ShiftLeftAssign => op!(shl),
+            TokenKind::ShiftRightAssign => op!(shr),
+            TokenKind::BitAndAssign => op!(and),
+            TokenKind::BitXorAssign => op!(xor),
+            TokenKind::BitOrAssign => op!(or),
+            TokenKind::PowAssign => op!(pow),
+            _ => unreachable!(),
+        };
+        let out = self.store(place, &left, new_value, &node.span)?;
+        Ok(RuntimeResult::new(Some(out)))
+    }
+
+    /// Assigns the items of a list to the targets of a list literal, like `[a, b] = [b, a]`
+    fn visit_list_assign(
+        &mut self,
+        targets: &'tree ListLiteral,
+        right: &'tree Expression,
+        span: &Span,
+    ) -> Result<RuntimeResult<'tree>> {
+        let right = try_visit!(self.visit_expression(right)?);
+        match self.assign_list(targets, &right, span)? {
+            ControlFlow::Continue(()) => Ok(RuntimeResult::new(Some(right))),
+            ControlFlow::Break(res) => Ok(res),
+        }
+    }
+
+    /// Assigns the items of `value` to `targets`, nested list literals are assigned the items
+    /// of the corresponding item
+    fn assign_list(
+        &mut self,
+        targets: &'tree ListLiteral,
+        value: &WrappedValue<'tree>,
+        span: &Span,
+    ) -> Result<ControlFlow<RuntimeResult<'tree>>> {
+        // the items are collected first, assigning replaces them instead of changing them,
+        // so that swapping variables does not see its own writes
+        let items = match &*value.borrow() {
+            Value::List(items) if items.len() == targets.len() => items.clone(),
+            Value::List(items) => error!(
+                ValueError,
+                *span,
+                "Expected {} items, found {}",
+                targets.len(),
+                items.len(),
+            ),
+            value => error!(
+                TypeError,
+                *span,
+                "Cannot destructure type '{}' as a list",
+                types::type_of(value),
+            ),
+        };
+        for (target, item) in targets.iter().zip(items) {
+            let target = target
+                .bare_assign()
+                .expect("the parser only allows assignable targets");
+            if let Some(Atom::List(targets)) = target.bare_target() {
+                if let ControlFlow::Break(res) = self.assign_list(targets, &item, span)? {
+                    return Ok(ControlFlow::Break(res));
+                }
+                continue;
+            }
+            let place = match self.visit_place(&target.left)? {
+                ControlFlow::Continue(place) => place,
+                ControlFlow::Break(res) => return Ok(ControlFlow::Break(res)),
+            };
+            let left = self.place_value(&place, true, span)?;
+            let left_type = types::type_of(&left.borrow());
+            if matches!(left_type, Type::Class | Type::Range | Type::Object) {
+                error!(TypeError, *span, "Cannot reassign type '{}'", left_type);
+            }
+            self.store(place, &left, item.borrow().clone(), span)?;
+        }
+        Ok(ControlFlow::Continue(()))
+    }
+
+    /// Evaluates the target of an assignment up to the place its value is stored in,
+    /// breaks with the result of a part which left the surrounding function or loop
+    fn visit_place(
+        &mut self,
+        node: &'tree CallExpr,
+    ) -> Result<ControlFlow<RuntimeResult<'tree>, Place<'tree>>> {
+        let (base, part) = match (
+            node.following.split_last(),
+            node.base.following.split_last(),
+        ) {
+            (Some((CallPart::Member(part), parts)), _) => (
+                try_visit!(self.visit_call_parts(node, parts)?; ControlFlow::Break),
+                part,
+            ),
+            (None, Some((part, parts))) => (
+                match self.visit_member_parts(&node.base, parts)? {
+                    Some((_, res)) => try_visit!(res; ControlFlow::Break),
+                    None => Value::Null.wrapped(),
+                },
+                part,
+            ),
+            (None, None) => {
+                let Atom::Identifier { name, .. } = &node.base.base else {
+                    unreachable!("the parser only allows assignable targets")
+                };
+                return Ok(ControlFlow::Continue(Place::Variable(name)));
+            }
+            (Some((CallPart::Args(_), _)), _) => {
+                unreachable!("the parser only allows assignable targets")
+            }
+        };
+        Ok(ControlFlow::Continue(match part {
+            MemberPart::Field(ident) => Place::Field(base, ident),
+            MemberPart::OptionalField(_) => {
+                unreachable!("the parser only allows assignable targets")
+            }
+            MemberPart::Index(index) => {
+                let index = try_visit!(self.visit_expression(index)?; ControlFlow::Break);
+                Place::Index(base, index)
+            }
+        }))
+    }
+
+    /// Returns the value `place` currently holds and fails if it is a constant. If `insert` is set,
+    /// a key which a map does not contain yet holds `null`, so that assigning to it inserts it.
+    fn place_value(
+        &mut self,
+        place: &Place<'tree>,
+        insert: bool,
+        span: &Span,
+    ) -> Result<WrappedValue<'tree>> {
+        Ok(match place {
+            Place::Variable(name) => self.get_reassignable_var(name, span)?,
+            Place::Field(base, name) => Value::get_field(base, name, &self.built_in_methods, span)?,
+            Place::Index(base, index) => {
+                let new_key = match &*base.borrow() {
+                    Value::Map(map) if insert => {
+                        !map.contains_key(&index.borrow().to_map_key(span)?)
+                    }
+                    _ => false,
+                };
+                if new_key {
+                    Value::Null.wrapped()
+                } else {
+                    self.index_value(base, index, span)?
+                }
             }
-            *left.borrow_mut() = new_value;
+        })
+    }
+
+    /// Replaces the value `left` held by `place` with `new_value` and returns the stored value.
+    /// Other variables, items and fields bound to `left` keep it.
+    fn store(
+        &mut self,
+        place: Place<'tree>,
+        left: &WrappedValue<'tree>,
+        new_value: Value<'tree>,
+        span: &Span,
+    ) -> Result<WrappedValue<'tree>> {
+        let left_type = types::type_of(&left.borrow());
+        let new_type = types::type_of(&new_value);
+        if left_type != new_type && left_type != Type::Null && new_type != Type::Null {
+            error!(
+                TypeError,
+                *span, "Cannot change type by reassigning, create a new variable instead",
+            );
         }
-        Ok(RuntimeResult::new(Some(left)))
+        let value = new_value.wrapped();
+        match place {
+            Place::Variable(name) => self.set_var(name, Rc::clone(&value), span)?,
+            Place::Field(base, name) => {
+                self.locks.check_mutate(&base, span)?;
+                Value::set_field(&base, name, Rc::clone(&value), span)?;
+            }
+            Place::Index(base, index) => {
+                self.locks.check_mutate(&base, span)?;
+                Value::set_index(&base, &index, Rc::clone(&value), span)?;
+            }
+        }
+        Ok(value)
     }
 
+    #[inline]
     fn visit_call_expr(&mut self, node: &'tree CallExpr) -> Result<RuntimeResult<'tree>> {
-        let (mut parent, result) = self.visit_member_expr(&node.base)?;
+        self.visit_call_parts(node, &node.following)
+    }
+
+    fn visit_call_parts(
+        &mut self,
+        node: &'tree CallExpr,
+        parts: &'tree [CallPart],
+    ) -> Result<RuntimeResult<'tree>> {
+        let Some((mut parent, result)) =
+            self.visit_member_parts(&node.base, &node.base.following)?
+        else {
+            return Ok(RuntimeResult::new(Some(Value::Null.wrapped())));
+        };
         if result.should_return() {
             return Ok(result);
         }
         let mut base = result.take_value();
-        for part in &node.following {
+        // the name of the called value for stack traces, if known at the call site
+        let mut callee = match (node.base.following.last(), &node.base.base) {
+            (Some(MemberPart::Field(ident) | MemberPart::OptionalField(ident)), _) => {
+                Some(ident.as_str())
+            }
+            (None, Atom::Identifier { name, .. } | Atom::Super { name, .. }) => Some(name.as_str()),
+            _ => None,
+        };
+        for part in parts {
             let out = match part {
-                CallPart::Args(args) => self.call_value(&base, args, &parent, &node.span)?,
+                CallPart::Args(args) => {
+                    let out = self.call_value(&base, args, &parent, callee, &node.span)?;
+                    callee = None;
+                    out
+                }
                 CallPart::Member(MemberPart::Field(ident)) => {
+                    callee = Some(ident);
+                    Value::get_field(&base, ident, &self.built_in_methods, &node.span)?
+                }
+                CallPart::Member(MemberPart::OptionalField(ident)) => {
+                    if matches!(&*base.borrow(), Value::Null) {
+                        return Ok(RuntimeResult::new(Some(Value::Null.wrapped())));
+                    }
+                    callee = Some(ident);
                     Value::get_field(&base, ident, &self.built_in_methods, &node.span)?
                 }
                 CallPart::Member(MemberPart::Index(expr)) => {
-                    Value::index(&base, &try_visit!(self.visit_expression(expr)?), &node.span)?
+                    callee = None;
+                    let index = try_visit!(self.visit_expression(expr)?);
+                    self.index_value(&base, &index, &node.span)?
                 }
             };
             mem::swap(&mut base, &mut parent);
@@ -571,41 +1155,56 @@ where
         value: &WrappedValue<'tree>,
         call_args: &'tree Args,
         parent: &WrappedValue<'tree>,
+        name: Option<&str>,
         span: &Span,
     ) -> Result<WrappedValue<'tree>> {
         match &*value.borrow() {
-            Value::Function { args, block } => {
-                if args.len() != call_args.len() {
+            Value::Function {
+                args,
+                block,
+                env,
+                is_generator,
+            } => {
+                let values = self.visit_args(call_args)?;
+                self.call_function(
+                    args,
+                    block,
+                    *is_generator,
+                    env,
+                    values,
+                    parent,
+                    "Function",
+                    name,
+                    span,
+                )
+            }
+            Value::BuiltIn(func) => {
+                if let Some(arg) = call_args.iter().find(|arg| arg.name.is_some()) {
                     error!(
                         TypeError,
-                        *span,
-                        "Function takes {} arguments, however {} were supllied",
-                        args.len(),
-                        call_args.len(),
+                        arg.span, "Built-in functions do not take named arguments",
                     );
                 }
-                self.push_scope();
-                if *parent.borrow() != Value::Null {
-                    self.add_var("this", Rc::clone(parent));
-                }
-                for (idx, arg) in args.iter().enumerate() {
-                    let val = self.visit_expression(&call_args[idx])?.take_value();
-                    self.add_var(arg, val);
-                }
-                let res = self.visit_block(block, false)?;
-                self.pop_scope();
-                Ok(if let Some(val) = res.return_value {
-                    val
-                } else {
-                    res.take_value()
-                })
-            }
-            Value::BuiltIn(func) => {
-                let mut args = vec![];
-                for arg in call_args {
-                    args.push(self.visit_expression(arg)?.take_value());
+                let mut args: Vec<_> = self
+                    .visit_args(call_args)?
+                    .into_iter()
+                    .map(|(_, arg)| arg)
+                    .collect();
+                if matches!(func, BuiltIn::Print { .. }) {
+                    for arg in &mut args {
+                        if let Some(str) = self.str_value(arg, span)? {
+                            *arg = Value::String(str).wrapped();
+                        }
+                    }
+                } else if args.is_empty() && Rc::ptr_eq(value, &self.built_in_methods.to_string) {
+                    if let Some(str) = self.str_value(parent, span)? {
+                        return Ok(Value::String(str).wrapped());
+                    }
                 }
 
+                if self.built_in_methods.mutates(value) {
+                    self.locks.check_mutate(parent, span)?;
+                }
                 let out = match func {
                     BuiltIn::Function(func) => func(args, span)?,
                     BuiltIn::Method(func) => func(parent, args, span)?,
@@ -621,46 +1220,80 @@ where
                         built_in::exit(args, self.exit_callback.take().unwrap(), span)?
                     }
                     BuiltIn::Debug => built_in::debug(args, &mut self.stderr, span)?,
+                    BuiltIn::Freeze => built_in::freeze(args, &mut self.locks, span)?,
+                    BuiltIn::Throw => built_in::throw(args, &mut self.thrown, &self.locks, span)?,
                 };
                 Ok(out)
             }
-            Value::Class { non_statics, .. } => {
-                if !call_args.is_empty() {
+            Value::Class { .. } => {
+                let init = Self::find_method(value, "init");
+                if init.is_none() && !call_args.is_empty() {
                     error!(
                         TypeError,
                         *span,
-                        "Class constructors take no arguments, however {} were supplied",
+                        "Class without 'init' method takes no arguments, however {} were supplied",
                         call_args.len(),
                     );
                 }
-                let object = Value::Null.wrapped();
-                let mut fields: HashMap<&str, _> = HashMap::new();
-                for member in non_statics {
-                    match member {
-                        MemberKind::Attribute(node) => {
-                            fields.insert(
-                                &node.ident,
-                                match &node.expr {
-                                    Some(node) => self.visit_expression(node)?.take_value(),
-                                    None => Value::Null.wrapped(),
-                                },
-                            );
-                        }
-                        MemberKind::Method(node) => {
-                            fields.insert(
-                                &node.ident,
-                                Value::Function {
-                                    args: &node.args,
-                                    block: &node.block,
-                                }
-                                .wrapped(),
-                            );
-                        }
-                    }
+                let mut fields = HashMap::new();
+                self.instantiate(value, &mut fields)?;
+                let object = Value::Object {
+                    fields,
+                    class: Some(ClassRef(Rc::clone(value))),
+                }
+                .wrapped();
+                if let Some((init, env)) = init {
+                    let values = self.visit_args(call_args)?;
+                    self.call_function(
+                        &init.args,
+                        &init.block,
+                        init.is_generator,
+                        &env,
+                        values,
+                        &object,
+                        "Constructor",
+                        name,
+                        span,
+                    )?;
                 }
-                *object.borrow_mut() = Value::Object(fields);
                 Ok(object)
             }
+            Value::Variant {
+                decl,
+                idx,
+                payload: None,
+            } if !decl.variants[*idx].fields.is_empty() => {
+                let variant = &decl.variants[*idx];
+                if let Some(arg) = call_args.iter().find(|arg| arg.name.is_some()) {
+                    error!(
+                        TypeError,
+                        arg.span, "Enum variants do not take named arguments",
+                    );
+                }
+                if call_args.len() != variant.fields.len() {
+                    error!(
+                        TypeError,
+                        *span,
+                        "Variant '{}.{}' takes {} arguments ({}), however {} were supplied",
+                        decl.ident,
+                        variant.ident,
+                        variant.fields.len(),
+                        variant.fields.join(", "),
+                        call_args.len(),
+                    );
+                }
+                let payload = self
+                    .visit_args(call_args)?
+                    .into_iter()
+                    .map(|(_, value)| value)
+                    .collect();
+                Ok(Value::Variant {
+                    decl,
+                    idx: *idx,
+                    payload: Some(payload),
+                }
+                .wrapped())
+            }
             _ => error!(
                 TypeError,
                 *span,
@@ -670,33 +1303,356 @@ where
         }
     }
 
+    /// Evaluates the non-static members of `class` and its parents into `fields`,
+    /// members of subclasses override those of their parents.
+    fn instantiate(
+        &mut self,
+        class: &WrappedValue<'tree>,
+        fields: &mut HashMap<&'tree str, WrappedValue<'tree>>,
+    ) -> Result<()> {
+        let (non_statics, parent, env) = match &*class.borrow() {
+            Value::Class {
+                non_statics,
+                parent,
+                env,
+                ..
+            } => (non_statics.clone(), parent.clone(), env.clone()),
+            _ => unreachable!("only classes can be instantiated"),
+        };
+        if let Some(parent) = &parent {
+            self.instantiate(parent, fields)?;
+        }
+        self.with_env(&env, |interpreter| {
+            for member in non_statics {
+                match member {
+                    MemberKind::Attribute(node) => {
+                        let Target::Ident(ident) = &node.target else {
+                            unreachable!("attributes cannot be destructured")
+                        };
+                        fields.insert(
+                            ident,
+                            match &node.expr {
+                                Some(node) => interpreter.visit_expression(node)?.take_value(),
+                                None => Value::Null.wrapped(),
+                            },
+                        );
+                    }
+                    MemberKind::Method(node) => {
+                        fields.insert(
+                            &node.ident,
+                            Value::Function {
+                                args: &node.args,
+                                block: &node.block,
+                                env: env.clone(),
+                                is_generator: node.is_generator,
+                            }
+                            .wrapped(),
+                        );
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Searches `class` and its parents for a non-static method called `name`
+    fn find_method(
+        class: &WrappedValue<'tree>,
+        name: &str,
+    ) -> Option<(&'tree FunctionDecl, Environment<'tree>)> {
+        match &*class.borrow() {
+            Value::Class {
+                non_statics,
+                parent,
+                env,
+                ..
+            } => non_statics
+                .iter()
+                .find_map(|member| match member {
+                    MemberKind::Method(node) if node.ident == name => Some((node, env.clone())),
+                    _ => None,
+                })
+                .or_else(|| {
+                    parent
+                        .as_ref()
+                        .and_then(|parent| Self::find_method(parent, name))
+                }),
+            _ => None,
+        }
+    }
+
+    /// Calls a function in its environment, `kind` names what is called in arity errors
+    #[allow(clippy::too_many_arguments)]
+    fn call_function(
+        &mut self,
+        params: &'tree Params,
+        block: &'tree Block,
+        is_generator: bool,
+        env: &Environment<'tree>,
+        args: Vec<Argument<'tree>>,
+        this: &WrappedValue<'tree>,
+        kind: &str,
+        name: Option<&str>,
+        span: &Span,
+    ) -> Result<WrappedValue<'tree>> {
+        self.check_depth(span)?;
+        self.depth += 1;
+        let res = self.with_env(env, |interpreter| {
+            interpreter.push_scope();
+            if *this.borrow() != Value::Null {
+                interpreter.add_var("this", Rc::clone(this));
+            }
+            interpreter.bind_params(params, args, kind, span)?;
+            if is_generator {
+                let generator = Generator::new(interpreter.scopes.clone(), block, name);
+                return Ok(RuntimeResult::new(Some(
+                    Value::Generator(generator).wrapped(),
+                )));
+            }
+            interpreter
+                .visit_block(block, false)
+                .map_err(|e| e.with_frame(name, *span))
+        });
+        self.depth -= 1;
+        let res = res?;
+        Ok(if let Some(val) = res.return_value {
+            val
+        } else {
+            res.take_value()
+        })
+    }
+
+    /// Binds positional arguments in order and named ones by name, the parameters which
+    /// are left out get their default value, which can refer to earlier parameters
+    fn bind_params(
+        &mut self,
+        params: &'tree Params,
+        args: Vec<Argument<'tree>>,
+        kind: &str,
+        span: &Span,
+    ) -> Result<()> {
+        let supplied = args.len();
+        macro_rules! arity_error {
+            () => {
+                error!(
+                    TypeError,
+                    *span,
+                    "{} takes {} arguments {}, however {} were supplied",
+                    kind,
+                    params.count(),
+                    params,
+                    supplied,
+                )
+            };
+        }
+
+        let mut values = vec![None; params.params.len()];
+        let mut rest = vec![];
+        let mut positional = 0;
+        for (name, value) in args {
+            match name {
+                None if positional < values.len() => {
+                    values[positional] = Some(value);
+                    positional += 1;
+                }
+                None if params.rest.is_some() => rest.push(value),
+                None => arity_error!(),
+                Some(name) => {
+                    let Some(idx) = params.params.iter().position(|param| param.ident == name)
+                    else {
+                        error!(
+                            TypeError,
+                            *span, "{} has no parameter called '{}' {}", kind, name, params,
+                        );
+                    };
+                    if values[idx].is_some() {
+                        error!(
+                            TypeError,
+                            *span, "Argument '{}' was supplied more than once", name,
+                        );
+                    }
+                    values[idx] = Some(value);
+                }
+            }
+        }
+        for (param, value) in params.params.iter().zip(values) {
+            let value = match (value, &param.default) {
+                (Some(value), _) => value,
+                (None, Some(default)) => self.visit_expression(default)?.take_value(),
+                (None, None) => arity_error!(),
+            };
+            self.add_var(&param.ident, value);
+        }
+        if let Some(name) = &params.rest {
+            self.add_var(name, Value::List(rest).wrapped());
+        }
+        Ok(())
+    }
+
+    fn visit_args(&mut self, args: &'tree Args) -> Result<Vec<Argument<'tree>>> {
+        let mut values = vec![];
+        for arg in args {
+            let value = self.visit_expression(&arg.expr)?.take_value();
+            values.push((arg.name.as_deref(), value));
+        }
+        Ok(values)
+    }
+
+    /// Calls the special method `name` on `value` if it is an object defining it
+    fn call_special(
+        &mut self,
+        value: &WrappedValue<'tree>,
+        name: &str,
+        values: Vec<WrappedValue<'tree>>,
+        span: &Span,
+    ) -> Result<Option<WrappedValue<'tree>>> {
+        let method = match &*value.borrow() {
+            Value::Object { fields, .. } => match fields.get(name) {
+                Some(method) => Rc::clone(method),
+                None => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+        let out = match &*method.borrow() {
+            Value::Function {
+                args,
+                block,
+                env,
+                is_generator,
+            } => {
+                if !args.accepts(values.len()) {
+                    error!(
+                        TypeError,
+                        *span,
+                        "Method '{}' has to take {} arguments, however it takes {}",
+                        name,
+                        values.len(),
+                        args.count(),
+                    );
+                }
+                let values = values.into_iter().map(|value| (None, value)).collect();
+                self.call_function(
+                    args,
+                    block,
+                    *is_generator,
+                    env,
+                    values,
+                    value,
+                    "Method",
+                    Some(name),
+                    span,
+                )?
+            }
+            _ => error!(
+                TypeError,
+                *span, "Special member '{}' has to be a method", name
+            ),
+        };
+        Ok(Some(out))
+    }
+
+    /// Returns the result of `__str__` if `value` defines it
+    fn str_value(&mut self, value: &WrappedValue<'tree>, span: &Span) -> Result<Option<String>> {
+        Ok(self
+            .call_special(value, "__str__", vec![], span)?
+            .map(|str| str.borrow().to_string()))
+    }
+
+    fn binary_op(
+        &mut self,
+        left: &WrappedValue<'tree>,
+        right: &WrappedValue<'tree>,
+        name: &str,
+        op: impl FnOnce(&Value<'tree>, &Value<'tree>, &Span) -> Result<Value<'tree>>,
+        span: &Span,
+    ) -> Result<WrappedValue<'tree>> {
+        if let Some(out) = self.call_special(left, name, vec![Rc::clone(right)], span)? {
+            return Ok(out);
+        }
+        // objects defining `__str__` can be concatenated with strings
+        if name == "__add__" {
+            if let Value::String(left) = &*left.borrow() {
+                if let Some(right) = self.str_value(right, span)? {
+                    return Ok(Value::String(left.clone() + &right).wrapped());
+                }
+            }
+            if let Value::String(right) = &*right.borrow() {
+                if let Some(left) = self.str_value(left, span)? {
+                    return Ok(Value::String(left + right).wrapped());
+                }
+            }
+        }
+        Ok(op(&left.borrow(), &right.borrow(), span)?.wrapped())
+    }
+
+    fn index_value(
+        &mut self,
+        base: &WrappedValue<'tree>,
+        index: &WrappedValue<'tree>,
+        span: &Span,
+    ) -> Result<WrappedValue<'tree>> {
+        match self.call_special(base, "__index__", vec![Rc::clone(index)], span)? {
+            Some(out) => Ok(out),
+            None => Value::index(base, index, span),
+        }
+    }
+
+    #[inline]
     fn visit_member_expr(
         &mut self,
         node: &'tree MemberExpr,
     ) -> Result<(WrappedValue<'tree>, RuntimeResult<'tree>)> {
+        Ok(self
+            .visit_member_parts(node, &node.following)?
+            .unwrap_or_else(|| {
+                (
+                    Value::Null.wrapped(),
+                    RuntimeResult::new(Some(Value::Null.wrapped())),
+                )
+            }))
+    }
+
+    /// Returns `None` if an optional field access on `null` cut the chain short
+    fn visit_member_parts(
+        &mut self,
+        node: &'tree MemberExpr,
+        parts: &'tree [MemberPart],
+    ) -> Result<Option<(WrappedValue<'tree>, RuntimeResult<'tree>)>> {
         let res = self.visit_atom(&node.base)?;
         if res.should_return() {
-            return Ok((Value::Null.wrapped(), res));
+            return Ok(Some((Value::Null.wrapped(), res)));
         }
         let mut base = res.take_value();
-        let mut parent = Value::Null.wrapped();
-        for part in &node.following {
+        // methods accessed through `super` are called on the current `this`
+        let mut parent = match &node.base {
+            Atom::Super { .. } => self
+                .get_var("this", &node.span)
+                .unwrap_or_else(|_| Value::Null.wrapped()),
+            _ => Value::Null.wrapped(),
+        };
+        for part in parts {
             let out = match part {
                 MemberPart::Field(ident) => {
                     Value::get_field(&base, ident, &self.built_in_methods, &node.span)?
                 }
+                MemberPart::OptionalField(ident) => {
+                    if matches!(&*base.borrow(), Value::Null) {
+                        return Ok(None);
+                    }
+                    Value::get_field(&base, ident, &self.built_in_methods, &node.span)?
+                }
                 MemberPart::Index(expr) => {
                     let res = self.visit_expression(expr)?;
                     if res.should_return() {
-                        return Ok((Value::Null.wrapped(), res));
+                        return Ok(Some((Value::Null.wrapped(), res)));
                     }
-                    Value::index(&base, &res.take_value(), &node.span)?
+                    self.index_value(&base, &res.take_value(), &node.span)?
                 }
             };
             mem::swap(&mut parent, &mut base);
             base = out;
         }
-        Ok((parent, RuntimeResult::new(Some(base))))
+        Ok(Some((parent, RuntimeResult::new(Some(base)))))
     }
 
     fn visit_atom(&mut self, node: &'tree Atom) -> Result<RuntimeResult<'tree>> {
@@ -704,8 +1660,43 @@ where
             Atom::Number(val) => Value::Number(*val).wrapped(),
             Atom::Bool(val) => Value::Bool(*val).wrapped(),
             Atom::String(val) => Value::String(val.clone()).wrapped(),
+            Atom::InterpolatedString(node) => {
+                let mut string = String::new();
+                for part in &node.parts {
+                    match part {
+                        StringPart::Literal(literal) => string += literal,
+                        StringPart::Expr(expr) => {
+                            let value = try_visit!(self.visit_expression(expr)?);
+                            match self.str_value(&value, &node.span)? {
+                                Some(str) => string += &str,
+                                None => string += &value.borrow().to_string(),
+                            }
+                        }
+                    }
+                }
+                Value::String(string).wrapped()
+            }
             Atom::Null => Value::Null.wrapped(),
-            Atom::Identifier { span, name } => Rc::clone(self.get_var(name, span)?.0),
+            Atom::Identifier { span, name } => self.get_var(name, span)?,
+            Atom::Super { span, name } => {
+                let class = match self.get_var("super", span) {
+                    Ok(class) => class,
+                    Err(_) => error!(
+                        ReferenceError,
+                        *span, "'super' can only be used inside of classes with a parent",
+                    ),
+                };
+                match Self::find_method(&class, name) {
+                    Some((node, env)) => Value::Function {
+                        args: &node.args,
+                        block: &node.block,
+                        env,
+                        is_generator: node.is_generator,
+                    }
+                    .wrapped(),
+                    None => Value::get_field(&class, name, &self.built_in_methods, span)?,
+                }
+            }
             Atom::Expr(node) => try_visit!(self.visit_expression(node)?),
             Atom::List(nodes) => {
                 let results = nodes
@@ -721,6 +1712,17 @@ where
                 }
                 Value::List(values).wrapped()
             }
+            Atom::Comprehension(node) => try_visit!(self.visit_comprehension(node)?),
+            Atom::Map(nodes) => {
+                let mut map = IndexMap::new();
+                for node in nodes {
+                    let key = try_visit!(self.visit_expression(&node.key)?);
+                    let key = key.borrow().to_map_key(&node.span)?;
+                    let val = try_visit!(self.visit_expression(&node.value)?);
+                    map.insert(key, val);
+                }
+                Value::Map(map).wrapped()
+            }
             Atom::IfExpr(node) => try_visit!(self.visit_if_expr(node)?),
             Atom::ForExpr(node) => try_visit!(self.visit_for_expr(node)?),
             Atom::WhileExpr(node) => try_visit!(self.visit_while_expr(node)?),
@@ -728,6 +1730,7 @@ where
             Atom::FunExpr(node) => try_visit!(self.visit_fun_expr(node)?),
             Atom::ClassExpr(node) => try_visit!(self.visit_class_expr(node)?),
             Atom::TryExpr(node) => try_visit!(self.visit_try_expr(node)?),
+            Atom::MatchExpr(node) => try_visit!(self.visit_match_expr(node)?),
             Atom::BlockExpr(node) => try_visit!(self.visit_block_expr(node)?),
         };
         Ok(RuntimeResult::new(Some(out)))
@@ -747,17 +1750,44 @@ where
 
     fn visit_for_expr(&mut self, node: &'tree ForExpr) -> Result<RuntimeResult<'tree>> {
         let iter = try_visit!(self.visit_expression(&node.iter)?);
-        let iter = iter.borrow();
-        let iter = iter.to_iter(&node.span)?;
+        let iter = self
+            .call_special(&iter, "__iter__", vec![], &node.span)?
+            .unwrap_or(iter);
+        // generators are resumed by the interpreter, other values have an iterator
+        let generator = match &*iter.borrow() {
+            Value::Generator(generator) => Some(generator.clone()),
+            _ => None,
+        };
+        // the body may change the value, so its items are taken before running it
+        let mut items = match generator {
+            Some(_) => None,
+            None => Some(iter.borrow().to_owned_iter(&node.span)?),
+        };
         let mut out = Value::Null.wrapped();
-        for item in iter {
+        loop {
+            let item = match (&mut items, &generator) {
+                (Some(items), _) => items.next(),
+                (None, Some(generator)) => self.resume(generator, &node.span)?,
+                (None, None) => unreachable!(),
+            };
+            let Some(item) = item else {
+                break;
+            };
+            let mut bindings = vec![];
+            Self::destructure(&node.target, item, &mut bindings)?;
             self.push_scope();
-            self.add_var(&node.ident, item);
+            for (name, value) in bindings {
+                self.add_var(name, value);
+            }
             let res = self.visit_block(&node.block, false)?;
-            if res.should_continue {
+            self.pop_scope();
+            if !res.targets(node.label.as_deref()) {
+                return Ok(res);
+            } else if res.should_continue {
                 continue;
             } else if let Some(val) = res.break_value {
                 out = val;
+                break;
             } else if res.return_value.is_some() {
                 return Ok(res);
             }
@@ -765,6 +1795,85 @@ where
         Ok(RuntimeResult::new(Some(out)))
     }
 
+    fn visit_comprehension(&mut self, node: &'tree Comprehension) -> Result<RuntimeResult<'tree>> {
+        let mut values = vec![];
+        if let Some(res) = self.visit_clauses(node, &node.clauses, &mut values)? {
+            return Ok(res);
+        }
+        Ok(RuntimeResult::new(Some(Value::List(values).wrapped())))
+    }
+
+    /// Pushes the values of a comprehension for the remaining clauses, returns the result
+    /// which interrupted the comprehension if it should be returned
+    fn visit_clauses(
+        &mut self,
+        node: &'tree Comprehension,
+        clauses: &'tree [ComprehensionClause],
+        values: &mut Vec<WrappedValue<'tree>>,
+    ) -> Result<Option<RuntimeResult<'tree>>> {
+        let (target, iter, rest) = match clauses.split_first() {
+            None => {
+                let res = self.visit_expression(&node.expr)?;
+                if res.should_return() {
+                    return Ok(Some(res));
+                }
+                values.push(res.take_value());
+                return Ok(None);
+            }
+            Some((ComprehensionClause::If(cond), rest)) => {
+                let res = self.visit_expression(cond)?;
+                if res.should_return() {
+                    return Ok(Some(res));
+                }
+                if res.take_value().borrow().is_false() {
+                    return Ok(None);
+                }
+                return self.visit_clauses(node, rest, values);
+            }
+            Some((ComprehensionClause::For { target, iter }, rest)) => (target, iter, rest),
+        };
+
+        let res = self.visit_expression(iter)?;
+        if res.should_return() {
+            return Ok(Some(res));
+        }
+        let iter = res.take_value();
+        let iter = self
+            .call_special(&iter, "__iter__", vec![], &node.span)?
+            .unwrap_or(iter);
+        let generator = match &*iter.borrow() {
+            Value::Generator(generator) => Some(generator.clone()),
+            _ => None,
+        };
+        // the body may change the value, so its items are taken before running it
+        let mut items = match generator {
+            Some(_) => None,
+            None => Some(iter.borrow().to_owned_iter(&node.span)?),
+        };
+        loop {
+            let item = match (&mut items, &generator) {
+                (Some(items), _) => items.next(),
+                (None, Some(generator)) => self.resume(generator, &node.span)?,
+                (None, None) => unreachable!(),
+            };
+            let Some(item) = item else {
+                break;
+            };
+            let mut bindings = vec![];
+            Self::destructure(target, item, &mut bindings)?;
+            self.push_scope();
+            for (name, value) in bindings {
+                self.add_var(name, value);
+            }
+            let res = self.visit_clauses(node, rest, values)?;
+            self.pop_scope();
+            if res.is_some() {
+                return Ok(res);
+            }
+        }
+        Ok(None)
+    }
+
     fn visit_while_expr(&mut self, node: &'tree WhileExpr) -> Result<RuntimeResult<'tree>> {
         let out = loop {
             let cond = try_visit!(self.visit_expression(&node.cond)?);
@@ -773,7 +1882,9 @@ where
             }
 
             let res = self.visit_block(&node.block, true)?;
-            if res.should_continue {
+            if !res.targets(node.label.as_deref()) {
+                return Ok(res);
+            } else if res.should_continue {
                 continue;
             } else if let Some(val) = res.break_value {
                 break val;
@@ -787,7 +1898,9 @@ where
     fn visit_loop_expr(&mut self, node: &'tree LoopExpr) -> Result<RuntimeResult<'tree>> {
         let out = loop {
             let res = self.visit_block(&node.block, true)?;
-            if res.should_continue {
+            if !res.targets(node.label.as_deref()) {
+                return Ok(res);
+            } else if res.should_continue {
                 continue;
             } else if let Some(val) = res.break_value {
                 break val;
@@ -802,20 +1915,59 @@ where
         let out = Value::Function {
             args: &node.args,
             block: &node.block,
+            env: Environment(self.scopes.clone()),
+            is_generator: node.is_generator,
         }
         .wrapped();
         Ok(RuntimeResult::new(Some(out)))
     }
 
     fn visit_class_expr(&mut self, node: &'tree ClassExpr) -> Result<RuntimeResult<'tree>> {
-        let class = Value::Null.wrapped();
+        self.make_class(node.parent.as_deref(), &node.block)
+    }
+
+    fn make_class(
+        &mut self,
+        parent: Option<&'tree MemberExpr>,
+        block: &'tree MemberBlock,
+    ) -> Result<RuntimeResult<'tree>> {
+        let parent = match parent {
+            Some(node) => {
+                let (_, res) = self.visit_member_expr(node)?;
+                if res.should_return() {
+                    return Ok(res);
+                }
+                let parent = res.take_value();
+                if !matches!(&*parent.borrow(), Value::Class { .. }) {
+                    error!(
+                        TypeError,
+                        node.span,
+                        "Cannot extend type '{}'",
+                        types::type_of(&parent.borrow()),
+                    );
+                }
+                Some(parent)
+            }
+            None => None,
+        };
+        let mut env = Environment(self.scopes.clone());
+        if let Some(parent) = &parent {
+            env.0.push(Rc::new(RefCell::new(HashMap::from([(
+                "super",
+                Variable::new(Rc::clone(parent)),
+            )]))));
+        }
+
         let mut statics: HashMap<&str, _> = HashMap::new();
         let mut non_statics = vec![];
-        for member in &node.block.members {
+        for member in &block.members {
             match (member.is_static, &member.kind) {
                 (true, MemberKind::Attribute(node)) => {
+                    let Target::Ident(ident) = &node.target else {
+                        unreachable!("attributes cannot be destructured")
+                    };
                     statics.insert(
-                        &node.ident,
+                        ident,
                         match &node.expr {
                             Some(node) => try_visit!(self.visit_expression(node)?),
                             None => Value::Null.wrapped(),
@@ -828,6 +1980,8 @@ where
                         Value::Function {
                             args: &node.args,
                             block: &node.block,
+                            env: env.clone(),
+                            is_generator: node.is_generator,
                         }
                         .wrapped(),
                     );
@@ -837,23 +1991,241 @@ where
                 }
             }
         }
-        *class.borrow_mut() = Value::Class {
-            statics,
-            non_statics,
-        };
-        Ok(RuntimeResult::new(Some(class)))
+        Ok(RuntimeResult::new(Some(
+            Value::Class {
+                statics,
+                non_statics,
+                parent,
+                env,
+            }
+            .wrapped(),
+        )))
     }
 
     fn visit_try_expr(&mut self, node: &'tree TryExpr) -> Result<RuntimeResult<'tree>> {
-        let res = self.visit_block(&node.try_block, true);
-        if let Err(e) = res {
+        let scope_count = self.scopes.len();
+        let res = match self.visit_block(&node.try_block, true) {
+            Err(error) => {
+                self.scopes.truncate(scope_count);
+                match self.find_catch(&node.catches, &error) {
+                    Ok(Some((clause, value))) => {
+                        self.push_scope();
+                        self.add_var(&clause.ident, value);
+                        self.visit_block(&clause.block, false)
+                    }
+                    Ok(None) => Err(error),
+                    Err(error) => Err(error),
+                }
+            }
+            res => res,
+        };
+        self.scopes.truncate(scope_count);
+
+        let Some(finally_block) = &node.finally_block else {
+            return res;
+        };
+        let finally_res = self.visit_block(finally_block, true)?;
+        // leaving the finally block with `break`, `continue` or `return` discards the outcome
+        // of the other blocks
+        if finally_res.should_return() {
+            if let Err(error) = &res {
+                self.thrown.remove(error);
+            }
+            return Ok(finally_res);
+        }
+        res
+    }
+
+    /// Returns the first of `catches` which catches `error` and the value it receives
+    fn find_catch(
+        &mut self,
+        catches: &'tree [CatchClause],
+        error: &Error,
+    ) -> Result<Option<(&'tree CatchClause, WrappedValue<'tree>)>> {
+        let value = self.thrown.value(error);
+        for clause in catches {
+            let catches = match &clause.filter {
+                None => true,
+                Some(CatchFilter::Kind(kind)) => error.kind == *kind,
+                Some(CatchFilter::Class(node)) => {
+                    let (_, res) = self.visit_member_expr(node)?;
+                    let class = res.take_value();
+                    if !matches!(&*class.borrow(), Value::Class { .. }) {
+                        error!(
+                            TypeError,
+                            node.span,
+                            "Catch filters have to be error kinds or classes, found type '{}'",
+                            types::type_of(&class.borrow()),
+                        );
+                    }
+                    Self::is_instance(&value, &class)
+                }
+            };
+            if catches {
+                self.thrown.remove(error);
+                return Ok(Some((clause, value)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Checks whether `value` was instantiated from `class` or one of its subclasses
+    fn is_instance(value: &WrappedValue<'tree>, class: &WrappedValue<'tree>) -> bool {
+        let mut curr = match &*value.borrow() {
+            Value::Object {
+                class: Some(ClassRef(class)),
+                ..
+            } => Some(Rc::clone(class)),
+            _ => None,
+        };
+        while let Some(parent) = curr {
+            if Rc::ptr_eq(&parent, class) {
+                return true;
+            }
+            curr = match &*parent.borrow() {
+                Value::Class { parent, .. } => parent.clone(),
+                _ => None,
+            };
+        }
+        false
+    }
+
+    fn visit_match_expr(&mut self, node: &'tree MatchExpr) -> Result<RuntimeResult<'tree>> {
+        let value = try_visit!(self.visit_expression(&node.expr)?);
+        let arm = match self.enter_arm(node, &value)? {
+            ControlFlow::Continue(arm) => arm,
+            ControlFlow::Break(res) => return Ok(res),
+        };
+        let res = self.visit_block(&arm.block, false)?;
+        self.pop_scope();
+        Ok(res)
+    }
+
+    /// Finds the first arm matching `value` and pushes a scope with the variables its pattern
+    /// binds, breaks with the result of a guard which left the surrounding function or loop
+    fn enter_arm(
+        &mut self,
+        node: &'tree MatchExpr,
+        value: &WrappedValue<'tree>,
+    ) -> Result<ControlFlow<RuntimeResult<'tree>, &'tree MatchArm>> {
+        for arm in &node.arms {
+            let mut bindings = vec![];
+            if !Self::match_pattern(&arm.pattern, value, &mut bindings) {
+                continue;
+            }
             self.push_scope();
-            self.add_var(&node.ident, e.to_value().wrapped());
-            let out = try_visit!(self.visit_block(&node.catch_block, false)?);
-            self.pop_scope();
-            Ok(RuntimeResult::new(Some(out)))
-        } else {
-            res
+            for (name, value) in bindings {
+                self.add_var(name, value);
+            }
+            if let Some(guard) = &arm.guard {
+                let res = self.visit_expression(guard)?;
+                if res.should_return() {
+                    self.pop_scope();
+                    return Ok(ControlFlow::Break(res));
+                }
+                if res.take_value().borrow().is_false() {
+                    self.pop_scope();
+                    continue;
+                }
+            }
+            return Ok(ControlFlow::Continue(arm));
+        }
+        error!(
+            ValueError,
+            node.span,
+            "No pattern matched the value '{}'",
+            value.borrow(),
+        );
+    }
+
+    /// Checks whether `value` matches `pattern` and collects the variables it binds
+    fn match_pattern(
+        pattern: &'tree Pattern,
+        value: &WrappedValue<'tree>,
+        bindings: &mut Vec<(&'tree str, WrappedValue<'tree>)>,
+    ) -> bool {
+        match (pattern, &*value.borrow()) {
+            (Pattern::Wildcard, _) => true,
+            (Pattern::Binding(name), _) => {
+                bindings.push((name, Rc::clone(value)));
+                true
+            }
+            (Pattern::Number(expected), Value::Number(num)) => expected == num,
+            (Pattern::Bool(expected), Value::Bool(val)) => expected == val,
+            (Pattern::String(expected), Value::String(str)) => expected == str,
+            (Pattern::Null, Value::Null) => true,
+            (
+                Pattern::Variant {
+                    enum_name,
+                    variant,
+                    fields,
+                },
+                Value::Variant { decl, idx, payload },
+            ) => {
+                if decl.ident != *enum_name || decl.variants[*idx].ident != *variant {
+                    return false;
+                }
+                match (fields, payload) {
+                    (None, _) => true,
+                    (Some(fields), Some(payload)) => {
+                        fields.len() == payload.len()
+                            && fields
+                                .iter()
+                                .zip(payload)
+                                .all(|(field, item)| Self::match_pattern(field, item, bindings))
+                    }
+                    (Some(fields), None) => fields.is_empty(),
+                }
+            }
+            (
+                Pattern::Range {
+                    start,
+                    end,
+                    inclusive,
+                },
+                Value::Number(num),
+            ) => {
+                start.is_none_or(|start| *num >= start)
+                    && end.is_none_or(|end| if *inclusive { *num <= end } else { *num < end })
+            }
+            (
+                Pattern::List {
+                    before,
+                    rest,
+                    after,
+                },
+                Value::List(items),
+            ) => {
+                let fixed = before.len() + after.len();
+                if items.len() < fixed || (rest.is_none() && items.len() != fixed) {
+                    return false;
+                }
+                let rest_end = items.len() - after.len();
+                if !before
+                    .iter()
+                    .zip(items)
+                    .chain(after.iter().zip(&items[rest_end..]))
+                    .all(|(pattern, item)| Self::match_pattern(pattern, item, bindings))
+                {
+                    return false;
+                }
+                if let Some(Some(name)) = rest {
+                    let rest = items[before.len()..rest_end].to_vec();
+                    bindings.push((name, Value::List(rest).wrapped()));
+                }
+                true
+            }
+            (Pattern::Object(patterns), Value::Object { fields, .. }) => patterns.iter().all(
+                |(name, pattern)| match (fields.get(name.as_str()), pattern) {
+                    (Some(field), Some(pattern)) => Self::match_pattern(pattern, field, bindings),
+                    (Some(field), None) => {
+                        bindings.push((name, Rc::clone(field)));
+                        true
+                    }
+                    (None, _) => false,
+                },
+            ),
+            _ => false,
         }
     }
 
@@ -1,13 +1,17 @@
 use rust_decimal::prelude::ToPrimitive;
 
-use crate::error::{Result, Span};
+use crate::error::{Error, ErrorKind, Result, Span};
 #[cfg(feature = "no_std_io")]
 use crate::io::Write;
 #[cfg(not(feature = "no_std_io"))]
 use std::io::Write;
 use std::rc::Rc;
 
-use super::value::{types, Value, WrappedValue};
+use super::{
+    locks::Locks,
+    thrown::Thrown,
+    value::{types, BuiltIn, Value, WrappedValue},
+};
 
 #[macro_export]
 macro_rules! expect_len {
@@ -28,6 +32,53 @@ macro_rules! expect_len {
     (@plural $num:literal) => { "s" };
 }
 
+macro_rules! built_in {
+    ($name:literal, $($built_in:tt)*) => {
+        ($name, Value::BuiltIn(BuiltIn::$($built_in)*).wrapped())
+    };
+}
+
+/// The global variables which are available in every program
+pub(crate) fn built_ins<'tree>() -> [(&'static str, WrappedValue<'tree>); 11] {
+    [
+        built_in!(
+            "print",
+            Print {
+                newline: false,
+                stderr: false
+            },
+        ),
+        built_in!(
+            "printl",
+            Print {
+                newline: true,
+                stderr: false
+            },
+        ),
+        built_in!(
+            "eprint",
+            Print {
+                newline: false,
+                stderr: true
+            },
+        ),
+        built_in!(
+            "eprintl",
+            Print {
+                newline: true,
+                stderr: true
+            },
+        ),
+        built_in!("typeOf", Function(type_of)),
+        built_in!("assert", Function(assert)),
+        built_in!("throw", Throw),
+        built_in!("exit", Exit),
+        built_in!("debug", Debug),
+        built_in!("freeze", Freeze),
+        ("answer", Value::Number(42.into()).wrapped()),
+    ]
+}
+
 #[cfg(not(feature = "no_std_io"))]
 pub fn print<'tree>(
     args: Vec<WrappedValue<'tree>>,
@@ -87,6 +138,17 @@ pub fn exit<'tree>(
     Ok(Value::Null.wrapped())
 }
 
+/// Makes the argument deeply read-only and returns it
+pub fn freeze<'tree>(
+    args: Vec<WrappedValue<'tree>>,
+    locks: &mut Locks<'tree>,
+    span: &Span,
+) -> Result<WrappedValue<'tree>> {
+    expect_len!(args, 1, "freeze", span);
+    locks.freeze(&args[0]);
+    Ok(Rc::clone(&args[0]))
+}
+
 pub fn type_of<'tree>(args: Vec<WrappedValue<'tree>>, span: &Span) -> Result<WrappedValue<'tree>> {
     expect_len!(args, 1, "typeOf", span);
     Ok(Value::String(types::type_of(&args[0].borrow()).to_string()).wrapped())
@@ -100,17 +162,57 @@ pub fn assert<'tree>(args: Vec<WrappedValue<'tree>>, span: &Span) -> Result<Wrap
     Ok(Value::Null.wrapped())
 }
 
-pub fn throw<'tree>(args: Vec<WrappedValue<'tree>>, span: &Span) -> Result<WrappedValue<'tree>> {
-    expect_len!(args, 1, "throw", span);
-    let borrow = args[0].borrow();
-    let str = match &*borrow {
-        Value::String(str) => str,
-        _ => error!(
+/// Throws the first argument, an optional second argument becomes the `cause` field of
+/// the thrown object. Error objects keep their kind when they are thrown again.
+pub fn throw<'tree>(
+    mut args: Vec<WrappedValue<'tree>>,
+    thrown: &mut Thrown<'tree>,
+    locks: &Locks<'tree>,
+    span: &Span,
+) -> Result<WrappedValue<'tree>> {
+    if args.is_empty() || args.len() > 2 {
+        error!(
             TypeError,
-            *span, "First argument of function 'throw' has to be of type 'string'",
+            *span,
+            "Function 'throw' takes 1 or 2 arguments, however {} were supplied",
+            args.len(),
+        );
+    }
+    if args.len() == 2 {
+        let cause = args.pop().unwrap();
+        locks.check_mutate(&args[0], span)?;
+        match &mut *args[0].borrow_mut() {
+            Value::Object { fields, .. } => {
+                fields.insert("cause", cause);
+            }
+            value => error!(
+                TypeError,
+                *span,
+                "Only objects can be thrown with a cause, found type '{}'",
+                types::type_of(value),
+            ),
+        }
+    }
+    let value = args.pop().unwrap();
+    let (kind, message) = match &*value.borrow() {
+        Value::Object { fields, .. } => (
+            fields
+                .get("kind")
+                .and_then(|kind| match &*kind.borrow() {
+                    Value::String(name) => ErrorKind::from_name(name),
+                    _ => None,
+                })
+                .unwrap_or(ErrorKind::RuntimeError),
+            fields.get("message").map_or_else(
+                || value.borrow().to_string(),
+                |msg| msg.borrow().to_string(),
+            ),
         ),
+        value => (ErrorKind::RuntimeError, value.to_string()),
     };
-    error!(RuntimeError, *span, "{str}",)
+    let mut error = Error::new(kind, message, *span);
+    error.thrown = Some(thrown.add(value));
+    Err(Box::new(error))
 }
 
 #[cfg(not(feature = "no_std_io"))]
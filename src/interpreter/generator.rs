@@ -0,0 +1,430 @@
+use std::{cell::RefCell, mem, ops::ControlFlow, rc::Rc};
+
+#[cfg(feature = "no_std_io")]
+use crate::io::Write;
+use crate::{
+    error::{Error, Result, Span},
+    nodes::*,
+};
+#[cfg(not(feature = "no_std_io"))]
+use std::io::Write;
+
+use super::{
+    runtime_result::RuntimeResult,
+    value::{Scope, Value, WrappedValue},
+    Interpreter,
+};
+
+/// A suspended call of a function containing `yield`. Copies of a generator share its
+/// state, so every yielded value is only produced once.
+#[derive(Clone)]
+pub struct Generator<'tree> {
+    state: Rc<RefCell<State<'tree>>>,
+    /// The name of the function, which errors raised inside of the generator are traced to
+    name: Option<Rc<str>>,
+}
+
+impl PartialEq for Generator<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.state, &other.state)
+    }
+}
+
+impl<'tree> Generator<'tree> {
+    /// Creates a generator which runs `block` in `scopes` once it is first resumed
+    pub(super) fn new(scopes: Vec<Scope<'tree>>, block: &'tree Block, name: Option<&str>) -> Self {
+        Self {
+            state: Rc::new(RefCell::new(State::Suspended {
+                scopes,
+                frames: vec![Frame::Block {
+                    stmts: block,
+                    idx: 0,
+                    scoped: false,
+                }],
+            })),
+            name: name.map(Rc::from),
+        }
+    }
+}
+
+enum State<'tree> {
+    Suspended {
+        scopes: Vec<Scope<'tree>>,
+        frames: Vec<Frame<'tree>>,
+    },
+    Running,
+    Done,
+}
+
+/// The blocks and loops a suspended generator is inside of. They are kept here instead
+/// of on the Rust stack, so that they can be left at a `yield` and entered again later.
+enum Frame<'tree> {
+    /// The statements of a block and the index of the next one,
+    /// leaving a `scoped` block pops the innermost scope
+    Block {
+        stmts: &'tree [Statement],
+        idx: usize,
+        scoped: bool,
+    },
+    While(&'tree WhileExpr),
+    Loop(&'tree LoopExpr),
+    For {
+        node: &'tree ForExpr,
+        items: Items<'tree>,
+    },
+    /// Catches errors of the frames above it, `scopes` is the number of scopes it started with
+    Try {
+        node: &'tree TryExpr,
+        scopes: usize,
+        stage: Stage<'tree>,
+    },
+}
+
+/// The block of a `try` expression which is running
+enum Stage<'tree> {
+    Try,
+    Catch,
+    /// The finally block, which continues with the error or the `break`, `continue`
+    /// or `return` that entered it once it is done
+    Finally(Option<Result<RuntimeResult<'tree>>>),
+}
+
+enum Items<'tree> {
+    Values(Box<dyn Iterator<Item = WrappedValue<'tree>> + 'tree>),
+    Generator(Generator<'tree>),
+}
+
+enum Step<'tree> {
+    Next,
+    Yield(WrappedValue<'tree>),
+    Done,
+}
+
+/// Evaluates an expression, or unwinds the frames and ends the step if it breaks,
+/// continues or returns
+macro_rules! value {
+    ($self:ident, $frames:ident, $node:expr) => {{
+        let res = $self.visit_expression($node)?;
+        if res.should_return() {
+            return Ok($self.unwind($frames, res));
+        }
+        res.take_value()
+    }};
+}
+
+impl<'tree, StdOut, StdErr, Exit> Interpreter<'tree, StdOut, StdErr, Exit>
+where
+    StdOut: Write,
+    StdErr: Write,
+    Exit: FnOnce(i32),
+{
+    /// Runs a generator up to its next `yield` and returns the yielded value,
+    /// or `None` if the generator has finished
+    pub(super) fn resume(
+        &mut self,
+        generator: &Generator<'tree>,
+        span: &Span,
+    ) -> Result<Option<WrappedValue<'tree>>> {
+        // resuming the generator continues the call of its function
+        self.check_depth(span)?;
+        let state = mem::replace(&mut *generator.state.borrow_mut(), State::Running);
+        let (scopes, mut frames) = match state {
+            State::Suspended { scopes, frames } => (scopes, frames),
+            State::Running => error!(ValueError, *span, "Generator is already running"),
+            State::Done => {
+                *generator.state.borrow_mut() = State::Done;
+                return Ok(None);
+            }
+        };
+
+        self.depth += 1;
+        let outer = mem::replace(&mut self.scopes, scopes);
+        let res = self
+            .run_frames(&mut frames)
+            .map_err(|e| e.with_frame(generator.name.as_deref(), *span));
+        let scopes = mem::replace(&mut self.scopes, outer);
+        self.depth -= 1;
+        *generator.state.borrow_mut() = match res {
+            Ok(Some(_)) => State::Suspended { scopes, frames },
+            _ => State::Done,
+        };
+        res
+    }
+
+    fn run_frames(
+        &mut self,
+        frames: &mut Vec<Frame<'tree>>,
+    ) -> Result<Option<WrappedValue<'tree>>> {
+        loop {
+            match self.step(frames) {
+                Ok(Step::Next) => {}
+                Ok(Step::Yield(value)) => return Ok(Some(value)),
+                Ok(Step::Done) => return Ok(None),
+                Err(error) => self.catch(frames, error)?,
+            }
+        }
+    }
+
+    /// Enters the catch or finally block of the innermost `try` which handles `error`
+    fn catch(&mut self, frames: &mut Vec<Frame<'tree>>, mut error: Box<Error>) -> Result<()> {
+        loop {
+            let Some(idx) = frames
+                .iter()
+                .rposition(|frame| matches!(frame, Frame::Try { .. }))
+            else {
+                return Err(error);
+            };
+            frames.truncate(idx + 1);
+            let Some(Frame::Try {
+                node,
+                scopes,
+                stage,
+            }) = frames.last()
+            else {
+                unreachable!()
+            };
+            let (node, in_try, in_finally) = (
+                *node,
+                matches!(stage, Stage::Try),
+                matches!(stage, Stage::Finally(_)),
+            );
+            self.scopes.truncate(*scopes);
+
+            if in_try {
+                match self.find_catch(&node.catches, &error) {
+                    Ok(Some((clause, value))) => {
+                        set_stage(frames, Stage::Catch);
+                        self.push_scope();
+                        self.add_var(&clause.ident, value);
+                        frames.push(Frame::Block {
+                            stmts: &clause.block,
+                            idx: 0,
+                            scoped: true,
+                        });
+                        return Ok(());
+                    }
+                    Ok(None) => {}
+                    Err(filter_error) => error = filter_error,
+                }
+            }
+            match &node.finally_block {
+                Some(block) if !in_finally => {
+                    set_stage(frames, Stage::Finally(Some(Err(error))));
+                    self.enter_block(frames, block);
+                    return Ok(());
+                }
+                _ => {
+                    frames.pop();
+                }
+            }
+        }
+    }
+
+    fn step(&mut self, frames: &mut Vec<Frame<'tree>>) -> Result<Step<'tree>> {
+        match frames.last_mut() {
+            None => return Ok(Step::Done),
+            Some(Frame::Block { stmts, idx, scoped }) => match stmts.get(*idx) {
+                Some(stmt) => {
+                    *idx += 1;
+                    return self.step_statement(stmt, frames);
+                }
+                None => {
+                    if *scoped {
+                        self.pop_scope();
+                    }
+                    frames.pop();
+                }
+            },
+            Some(Frame::While(node)) => {
+                let node = *node;
+                if value!(self, frames, &node.cond).borrow().is_true() {
+                    self.enter_block(frames, &node.block);
+                } else {
+                    frames.pop();
+                }
+            }
+            Some(Frame::Loop(node)) => {
+                let node = *node;
+                self.enter_block(frames, &node.block);
+            }
+            Some(Frame::For { node, items }) => {
+                let node = *node;
+                let item = match items {
+                    Items::Values(values) => values.next(),
+                    Items::Generator(generator) => {
+                        let generator = generator.clone();
+                        self.resume(&generator, &node.span)?
+                    }
+                };
+                match item {
+                    Some(item) => {
+                        let mut bindings = vec![];
+                        Self::destructure(&node.target, item, &mut bindings)?;
+                        self.push_scope();
+                        for (name, value) in bindings {
+                            self.add_var(name, value);
+                        }
+                        frames.push(Frame::Block {
+                            stmts: &node.block,
+                            idx: 0,
+                            scoped: true,
+                        });
+                    }
+                    None => {
+                        frames.pop();
+                    }
+                }
+            }
+            // the try or catch block was left without an error, or the finally block is done
+            Some(Frame::Try { node, stage, .. }) => {
+                let node = *node;
+                match (stage, &node.finally_block) {
+                    (Stage::Finally(pending), _) => {
+                        let pending = pending.take();
+                        frames.pop();
+                        match pending {
+                            Some(Ok(res)) => return Ok(self.unwind(frames, res)),
+                            Some(Err(error)) => return Err(error),
+                            None => {}
+                        }
+                    }
+                    (stage, Some(block)) => {
+                        *stage = Stage::Finally(None);
+                        self.enter_block(frames, block);
+                    }
+                    (_, None) => {
+                        frames.pop();
+                    }
+                }
+            }
+        }
+        Ok(Step::Next)
+    }
+
+    /// Runs a statement, statements which can contain a `yield` are entered as new frames
+    fn step_statement(
+        &mut self,
+        stmt: &'tree Statement,
+        frames: &mut Vec<Frame<'tree>>,
+    ) -> Result<Step<'tree>> {
+        let atom = match stmt {
+            Statement::Yield(node) => {
+                let value = match &node.expr {
+                    Some(expr) => value!(self, frames, expr),
+                    None => Value::Null.wrapped(),
+                };
+                return Ok(Step::Yield(value));
+            }
+            Statement::Expr(node) => node.bare_atom(),
+            _ => None,
+        };
+
+        match atom {
+            Some(Atom::IfExpr(node)) => {
+                if value!(self, frames, &node.cond).borrow().is_true() {
+                    self.enter_block(frames, &node.block);
+                } else if let Some(block) = &node.else_block {
+                    self.enter_block(frames, block);
+                }
+            }
+            Some(Atom::WhileExpr(node)) => frames.push(Frame::While(node)),
+            Some(Atom::LoopExpr(node)) => frames.push(Frame::Loop(node)),
+            Some(Atom::ForExpr(node)) => {
+                let iter = value!(self, frames, &node.iter);
+                let iter = self
+                    .call_special(&iter, "__iter__", vec![], &node.span)?
+                    .unwrap_or(iter);
+                let items = match &*iter.borrow() {
+                    Value::Generator(generator) => Items::Generator(generator.clone()),
+                    value => Items::Values(value.to_owned_iter(&node.span)?),
+                };
+                frames.push(Frame::For { node, items });
+            }
+            Some(Atom::TryExpr(node)) => {
+                frames.push(Frame::Try {
+                    node,
+                    scopes: self.scopes.len(),
+                    stage: Stage::Try,
+                });
+                self.enter_block(frames, &node.try_block);
+            }
+            Some(Atom::MatchExpr(node)) => {
+                let value = value!(self, frames, &node.expr);
+                match self.enter_arm(node, &value)? {
+                    // the scope of the arm was already pushed with the bindings of its pattern
+                    ControlFlow::Continue(arm) => frames.push(Frame::Block {
+                        stmts: &arm.block,
+                        idx: 0,
+                        scoped: true,
+                    }),
+                    ControlFlow::Break(res) => return Ok(self.unwind(frames, res)),
+                }
+            }
+            Some(Atom::BlockExpr(block)) => self.enter_block(frames, block),
+            _ => {
+                let res = self.visit_statement(stmt)?;
+                if res.should_return() {
+                    return Ok(self.unwind(frames, res));
+                }
+            }
+        }
+        Ok(Step::Next)
+    }
+
+    fn enter_block(&mut self, frames: &mut Vec<Frame<'tree>>, block: &'tree Block) {
+        self.push_scope();
+        frames.push(Frame::Block {
+            stmts: block,
+            idx: 0,
+            scoped: true,
+        });
+    }
+
+    /// Leaves the frames up to the loop a `break` or `continue` refers to, or all of them
+    /// on `return`. Finally blocks on the way are run first.
+    fn unwind(&mut self, frames: &mut Vec<Frame<'tree>>, res: RuntimeResult<'tree>) -> Step<'tree> {
+        while let Some(frame) = frames.last() {
+            let label = match frame {
+                Frame::While(node) => Some(&node.label),
+                Frame::Loop(node) => Some(&node.label),
+                Frame::For { node, .. } => Some(&node.label),
+                _ => None,
+            };
+            if res.return_value.is_none()
+                && label.is_some_and(|label| res.targets(label.as_deref()))
+            {
+                if res.break_value.is_some() {
+                    frames.pop();
+                }
+                return Step::Next;
+            }
+            match frame {
+                Frame::Block { scoped: true, .. } => self.pop_scope(),
+                Frame::Try {
+                    node:
+                        TryExpr {
+                            finally_block: Some(block),
+                            ..
+                        },
+                    scopes,
+                    stage: Stage::Try | Stage::Catch,
+                } => {
+                    self.scopes.truncate(*scopes);
+                    set_stage(frames, Stage::Finally(Some(Ok(res))));
+                    self.enter_block(frames, block);
+                    return Step::Next;
+                }
+                _ => {}
+            }
+            frames.pop();
+        }
+        Step::Done
+    }
+}
+
+/// Changes the stage of the `try` frame on top of `frames`
+fn set_stage<'tree>(frames: &mut [Frame<'tree>], new_stage: Stage<'tree>) {
+    if let Some(Frame::Try { stage, .. }) = frames.last_mut() {
+        *stage = new_stage;
+    }
+}
@@ -0,0 +1,58 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    rc::{Rc, Weak},
+};
+
+use crate::error::{Result, Span};
+
+use super::value::{Value, WrappedValue};
+
+/// Keeps track of the frozen values, which cannot be changed in place. Values are identified
+/// by their address, the weak references keep the allocations alive so that addresses are
+/// not reused until the entries of dropped values are removed.
+#[derive(Default)]
+pub struct Locks<'tree> {
+    values: HashMap<*const RefCell<Value<'tree>>, Weak<RefCell<Value<'tree>>>>,
+    /// The number of entries at which the entries of dropped values are removed next
+    prune_at: usize,
+}
+
+impl<'tree> Locks<'tree> {
+    /// Makes `value` and all items and fields it contains read-only
+    pub fn freeze(&mut self, value: &WrappedValue<'tree>) {
+        if self.values.contains_key(&Rc::as_ptr(value)) {
+            return;
+        }
+        self.insert(value);
+        match &*value.borrow() {
+            Value::List(items) => items.iter().for_each(|item| self.freeze(item)),
+            Value::Map(map) => map.values().for_each(|item| self.freeze(item)),
+            Value::Object { fields, .. } => fields.values().for_each(|field| self.freeze(field)),
+            Value::Variant {
+                payload: Some(payload),
+                ..
+            } => payload.iter().for_each(|item| self.freeze(item)),
+            _ => {}
+        }
+    }
+
+    /// Locks `value`, the entries of dropped values are removed whenever the table has doubled
+    /// in size since they were last removed
+    fn insert(&mut self, value: &WrappedValue<'tree>) {
+        if self.values.len() >= self.prune_at {
+            self.values.retain(|_, weak| weak.strong_count() > 0);
+            self.prune_at = (self.values.len() * 2).max(64);
+        }
+        self.values.insert(Rc::as_ptr(value), Rc::downgrade(value));
+    }
+
+    /// Fails if `value` is frozen. Variables holding a frozen value can still be reassigned,
+    /// only its items and fields cannot.
+    pub fn check_mutate(&self, value: &WrappedValue<'tree>, span: &Span) -> Result<()> {
+        if self.values.contains_key(&Rc::as_ptr(value)) {
+            error!(TypeError, *span, "Cannot change a frozen value");
+        }
+        Ok(())
+    }
+}
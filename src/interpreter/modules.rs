@@ -0,0 +1,55 @@
+#[cfg(not(feature = "no_std_io"))]
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use typed_arena::Arena;
+
+use crate::nodes::Program;
+
+pub type Loader<'tree> = Box<dyn FnMut(&Path) -> Result<String, String> + 'tree>;
+
+/// Owns the trees of imported modules. Values created by a module can reference its tree,
+/// so the storage has to outlive the interpreter which runs the import.
+#[derive(Default)]
+pub struct ProgramArena(Arena<Program>);
+
+impl ProgramArena {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moves `program` into the storage and returns a reference that lives as long as it
+    pub fn add(&self, program: Program) -> &Program {
+        self.0.alloc(program)
+    }
+}
+
+#[cfg(not(feature = "no_std_io"))]
+pub(crate) fn default_loader(path: &Path) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| e.to_string())
+}
+
+#[cfg(feature = "no_std_io")]
+pub(crate) fn default_loader(_path: &Path) -> Result<String, String> {
+    Err("no module loader was provided".to_string())
+}
+
+/// Resolves `.` and `..` components without accessing the file system, so that
+/// the same module is always cached under the same path
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match out.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    out.pop();
+                }
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => out.push(".."),
+            },
+            component => out.push(component),
+        }
+    }
+    out
+}
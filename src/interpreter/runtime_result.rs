@@ -4,6 +4,8 @@ use super::value::{Value, WrappedValue};
 pub struct RuntimeResult<'tree> {
     pub should_continue: bool,
     pub break_value: Option<WrappedValue<'tree>>,
+    /// The label of the loop a `break` or `continue` refers to, `None` for the innermost one
+    pub label: Option<&'tree str>,
     pub return_value: Option<WrappedValue<'tree>>,
     pub value: Option<WrappedValue<'tree>>,
 }
@@ -16,16 +18,18 @@ impl<'tree> RuntimeResult<'tree> {
         }
     }
 
-    pub fn success_continue() -> Self {
+    pub fn success_continue(label: Option<&'tree str>) -> Self {
         Self {
             should_continue: true,
+            label,
             ..Default::default()
         }
     }
 
-    pub fn success_break(value: WrappedValue<'tree>) -> Self {
+    pub fn success_break(value: WrappedValue<'tree>, label: Option<&'tree str>) -> Self {
         Self {
             break_value: Some(value),
+            label,
             ..Default::default()
         }
     }
@@ -41,6 +45,11 @@ impl<'tree> RuntimeResult<'tree> {
         self.should_continue || self.break_value.is_some() || self.return_value.is_some()
     }
 
+    /// Checks whether the loop with the given label is left or continued by this result
+    pub fn targets(&self, label: Option<&str>) -> bool {
+        self.label.is_none() || self.label == label
+    }
+
     pub fn take_value(self) -> WrappedValue<'tree> {
         self.value.unwrap_or_else(|| Value::Null.wrapped())
     }
@@ -0,0 +1,38 @@
+use std::{collections::HashMap, rc::Rc};
+
+use crate::error::Error;
+
+use super::value::{ToValue, WrappedValue};
+
+/// Keeps the values passed to `throw` while the errors carrying them propagate.
+/// Errors refer to their value by the id in [`Error::thrown`].
+#[derive(Default)]
+pub struct Thrown<'tree> {
+    values: HashMap<usize, WrappedValue<'tree>>,
+    next_id: usize,
+}
+
+impl<'tree> Thrown<'tree> {
+    /// Stores a thrown value and returns its id
+    pub fn add(&mut self, value: WrappedValue<'tree>) -> usize {
+        self.next_id += 1;
+        self.values.insert(self.next_id, value);
+        self.next_id
+    }
+
+    /// Returns the value `catch` receives for `error`, which is the thrown value
+    /// or an object describing the error if nothing was thrown
+    pub fn value(&self, error: &Error) -> WrappedValue<'tree> {
+        match error.thrown.and_then(|id| self.values.get(&id)) {
+            Some(value) => Rc::clone(value),
+            None => error.to_value().wrapped(),
+        }
+    }
+
+    /// Forgets the value of an error which was caught
+    pub fn remove(&mut self, error: &Error) {
+        if let Some(id) = error.thrown {
+            self.values.remove(&id);
+        }
+    }
+}
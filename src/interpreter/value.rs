@@ -7,10 +7,13 @@ pub mod relational_operations;
 pub mod truth;
 pub mod types;
 
+use super::generator::Generator;
 use crate::{
     error::{Result, Span},
-    nodes::{Block, MemberKind},
+    nodes::{Block, EnumDecl, MemberKind, Params},
+    vm,
 };
+use indexmap::IndexMap;
 use rust_decimal::Decimal;
 use std::{
     cell::RefCell,
@@ -20,6 +23,52 @@ use std::{
 };
 
 pub type WrappedValue<'tree> = Rc<RefCell<Value<'tree>>>;
+pub type Scope<'tree> = Rc<RefCell<HashMap<&'tree str, Variable<'tree>>>>;
+
+/// A variable of a scope. Assigning to it replaces the value it holds instead of changing
+/// the value, so that other variables bound to the same value are not affected.
+#[derive(Clone)]
+pub struct Variable<'tree> {
+    pub value: WrappedValue<'tree>,
+    /// The span of the declaration if the variable is a constant
+    pub constant: Option<Span>,
+}
+
+impl<'tree> Variable<'tree> {
+    pub fn new(value: WrappedValue<'tree>) -> Self {
+        Self {
+            value,
+            constant: None,
+        }
+    }
+}
+
+/// The scopes a function or class was defined in. Two environments are only
+/// equal if they share the exact same scopes.
+#[derive(Clone, Default)]
+pub struct Environment<'tree>(pub Vec<Scope<'tree>>);
+
+impl PartialEq for Environment<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.len() == other.0.len()
+            && self
+                .0
+                .iter()
+                .zip(&other.0)
+                .all(|(left, right)| Rc::ptr_eq(left, right))
+    }
+}
+
+/// The class of an instance. Two references are only equal if they point to the exact same class,
+/// so that comparing objects does not compare their classes.
+#[derive(Clone)]
+pub struct ClassRef<'tree>(pub WrappedValue<'tree>);
+
+impl PartialEq for ClassRef<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
 
 #[derive(PartialEq, Clone)]
 pub enum Value<'tree> {
@@ -31,19 +80,58 @@ pub enum Value<'tree> {
         end: Option<i128>,
     },
     List(Vec<WrappedValue<'tree>>),
+    Map(IndexMap<MapKey, WrappedValue<'tree>>),
     Function {
-        args: &'tree [String],
+        args: &'tree Params,
         block: &'tree Block,
+        env: Environment<'tree>,
+        is_generator: bool,
+    },
+    Generator(Generator<'tree>),
+    /// A function compiled to bytecode, only created by the [`Vm`](crate::vm::Vm)
+    Closure {
+        function: &'tree vm::Function,
+        frames: vm::Frames<'tree>,
     },
     BuiltIn(BuiltIn),
     Class {
         statics: HashMap<&'tree str, WrappedValue<'tree>>,
         non_statics: Vec<&'tree MemberKind>,
+        parent: Option<WrappedValue<'tree>>,
+        env: Environment<'tree>,
+    },
+    /// An object, `class` is the class it was instantiated from if it is an instance
+    Object {
+        fields: HashMap<&'tree str, WrappedValue<'tree>>,
+        class: Option<ClassRef<'tree>>,
+    },
+    /// An enum declaration, its variants are accessed as its fields
+    Enum(&'tree EnumDecl),
+    /// A variant of an enum, variants with fields are constructors until they are called
+    /// and fill in the `payload`
+    Variant {
+        decl: &'tree EnumDecl,
+        idx: usize,
+        payload: Option<Vec<WrappedValue<'tree>>>,
     },
-    Object(HashMap<&'tree str, WrappedValue<'tree>>),
     Null,
 }
 
+#[derive(PartialEq, Eq, Hash, Clone)]
+pub enum MapKey {
+    String(String),
+    Number(Decimal),
+}
+
+impl ToValue for MapKey {
+    fn to_value<'tree>(&self) -> Value<'tree> {
+        match self {
+            MapKey::String(str) => Value::String(str.clone()),
+            MapKey::Number(num) => Value::Number(*num),
+        }
+    }
+}
+
 macro_rules! unwrap_fns {
     ($($name:ident: $([$mut:tt])? $variant:ident => $type:tt,)*) => {$(
         pub fn $name(&$($mut)? self) -> unwrap_fns!(@type $type $($mut)?) {
@@ -78,6 +166,8 @@ impl<'tree> Value<'tree> {
         unwrap_string: String => (String),
         unwrap_range: Range => { start: Option<i128>, end: Option<i128> },
         unwrap_list_mut: [mut] List => (Vec<WrappedValue<'tree>>),
+        unwrap_map: Map => (IndexMap<MapKey, WrappedValue<'tree>>),
+        unwrap_map_mut: [mut] Map => (IndexMap<MapKey, WrappedValue<'tree>>),
     }
 }
 
@@ -99,6 +189,8 @@ pub enum BuiltIn {
     },
     Exit,
     Debug,
+    Freeze,
+    Throw,
 }
 
 impl PartialEq for BuiltIn {
@@ -151,11 +243,37 @@ impl Display for Value<'_> {
                     .collect::<Vec<_>>()
                     .join(", ")
             ),
-            Value::Function { .. } | Value::BuiltIn(..) => {
+            Value::Map(map) if map.is_empty() => write!(f, "[:]"),
+            Value::Map(map) => write!(
+                f,
+                "[{}]",
+                map.iter()
+                    .map(|(key, val)| format!("{}: {}", key.to_value(), val.borrow()))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Value::Function { .. } | Value::Closure { .. } | Value::BuiltIn(..) => {
                 write!(f, "<function>")
             }
+            Value::Generator(_) => write!(f, "<generator>"),
             Value::Class { statics, .. } => write!(f, "<class> {{\n{}}}", dbg_map!(statics)),
-            Value::Object(fields) => write!(f, "<object> {{\n{}}}", dbg_map!(fields)),
+            Value::Object { fields, .. } => write!(f, "<object> {{\n{}}}", dbg_map!(fields)),
+            Value::Enum(decl) => write!(f, "<enum> {}", decl.ident),
+            Value::Variant { decl, idx, payload } => {
+                write!(f, "{}.{}", decl.ident, decl.variants[*idx].ident)?;
+                match payload {
+                    Some(payload) => write!(
+                        f,
+                        "({})",
+                        payload
+                            .iter()
+                            .map(|val| val.borrow().to_string())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                    None => Ok(()),
+                }
+            }
             Value::Null => write!(f, "null"),
         }
     }
@@ -183,15 +301,41 @@ impl Debug for Value<'_> {
                     .collect::<Vec<_>>()
                     .join(", ")
             ),
-            Value::Function { .. } | Value::BuiltIn(..) => {
+            Value::Map(map) if map.is_empty() => write!(f, "[:]"),
+            Value::Map(map) => write!(
+                f,
+                "[{}]",
+                map.iter()
+                    .map(|(key, val)| format!("{:?}: {:?}", key.to_value(), val.borrow()))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Value::Function { .. } | Value::Closure { .. } | Value::BuiltIn(..) => {
                 write!(f, "\x1b[1m<function>\x1b[0m")
             }
+            Value::Generator(_) => write!(f, "\x1b[1m<generator>\x1b[0m"),
             Value::Class { statics, .. } => {
                 write!(f, "\x1b[1m<class>\x1b[0m {{\n{}}}", dbg_map!(:? statics))
             }
-            Value::Object(fields) => {
+            Value::Object { fields, .. } => {
                 write!(f, "\x1b[1m<object>\x1b[0m {{\n{}}}", dbg_map!(:? fields))
             }
+            Value::Enum(decl) => write!(f, "\x1b[1m<enum>\x1b[0m {}", decl.ident),
+            Value::Variant { decl, idx, payload } => {
+                write!(f, "{}.{}", decl.ident, decl.variants[*idx].ident)?;
+                match payload {
+                    Some(payload) => write!(
+                        f,
+                        "({})",
+                        payload
+                            .iter()
+                            .map(|val| format!("{:?}", val.borrow()))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                    None => Ok(()),
+                }
+            }
             Value::Null => write!(f, "\x1b[90mnull\x1b[0m"),
         }
     }
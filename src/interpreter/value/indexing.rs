@@ -3,7 +3,7 @@ use std::rc::Rc;
 
 use crate::error::{Result, Span};
 
-use super::{types, Value, WrappedValue};
+use super::{types, MapKey, Value, WrappedValue};
 
 pub(crate) trait ToListIndex {
     fn to_list_index(&self, len: usize, span: &Span) -> Result<usize>;
@@ -28,6 +28,25 @@ impl<'tree> ToListIndex for Value<'tree> {
     }
 }
 
+pub(crate) trait ToMapKey {
+    fn to_map_key(&self, span: &Span) -> Result<MapKey>;
+}
+
+impl<'tree> ToMapKey for Value<'tree> {
+    fn to_map_key(&self, span: &Span) -> Result<MapKey> {
+        match self {
+            Value::String(str) => Ok(MapKey::String(str.clone())),
+            Value::Number(num) => Ok(MapKey::Number(num.normalize())),
+            _ => error!(
+                TypeError,
+                *span,
+                "Type '{}' cannot be used as map key",
+                types::type_of(self),
+            ),
+        }
+    }
+}
+
 impl ToListIndex for i128 {
     fn to_list_index(&self, len: usize, span: &Span) -> Result<usize> {
         let len = len as i128;
@@ -76,6 +95,10 @@ impl<'tree> Value<'tree> {
                 }
                 (None, None) => Value::List(list[..].into()).wrapped(),
             },
+            (Value::Map(map), key) => match map.get(&key.to_map_key(span)?) {
+                Some(val) => Rc::clone(val),
+                None => error!(ValueError, *span, "Key '{}' not found in map", key),
+            },
             _ => error!(
                 TypeError,
                 *span,
@@ -86,3 +109,37 @@ impl<'tree> Value<'tree> {
         })
     }
 }
+
+impl<'tree> Value<'tree> {
+    /// Replaces the item of `this` at `index`, a map gets a new entry if it does not contain
+    /// the key yet
+    pub fn set_index(
+        this: &WrappedValue<'tree>,
+        index: &WrappedValue<'tree>,
+        value: WrappedValue<'tree>,
+        span: &Span,
+    ) -> Result<()> {
+        // the index is read before `this` is borrowed mutably, as it could be `this` itself
+        let (idx, key) = match (&*this.borrow(), &*index.borrow()) {
+            (Value::List(list), index @ Value::Number(_)) => {
+                (index.to_list_index(list.len(), span)?, None)
+            }
+            (Value::Map(_), key) => (0, Some(key.to_map_key(span)?)),
+            _ => error!(
+                TypeError,
+                *span,
+                "Cannot assign to items of type '{}' indexed by type '{}'",
+                types::type_of(&this.borrow()),
+                types::type_of(&index.borrow()),
+            ),
+        };
+        match (&mut *this.borrow_mut(), key) {
+            (Value::List(list), None) => list[idx] = value,
+            (Value::Map(map), Some(key)) => {
+                map.insert(key, value);
+            }
+            _ => unreachable!(),
+        }
+        Ok(())
+    }
+}
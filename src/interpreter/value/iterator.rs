@@ -1,8 +1,10 @@
 use std::{marker::PhantomData, rc::Rc, slice::Iter, str::Chars};
 
+use indexmap::{map::Keys, IndexMap};
+
 use crate::error::{Result, Span};
 
-use super::{types, Value, WrappedValue};
+use super::{types, MapKey, ToValue, Value, WrappedValue};
 
 impl<'tree> Value<'tree> {
     pub fn to_iter(
@@ -20,6 +22,15 @@ impl<'tree> Value<'tree> {
                 ),
             },
             Value::List(list) => Ok(Box::new(ListIterator::new(list))),
+            Value::Map(map) => Ok(Box::new(MapIterator::new(map))),
+            Value::Enum(decl) => Ok(Box::new((0..decl.variants.len()).map(|idx| {
+                Value::Variant {
+                    decl,
+                    idx,
+                    payload: None,
+                }
+                .wrapped()
+            }))),
             _ => error!(
                 TypeError,
                 *span,
@@ -28,6 +39,28 @@ impl<'tree> Value<'tree> {
             ),
         }
     }
+
+    /// Like [`to_iter`](Self::to_iter), but the iterator does not borrow the value,
+    /// so that the value can be changed while iterating and the iteration can be
+    /// suspended by a generator
+    pub fn to_owned_iter(
+        &self,
+        span: &Span,
+    ) -> Result<Box<dyn Iterator<Item = WrappedValue<'tree>> + 'tree>> {
+        match self {
+            Value::Range {
+                start: Some(start),
+                end: Some(end),
+            } => Ok(Box::new(RangeIterator::new(*start..=*end))),
+            Value::Range {
+                start: Some(start),
+                end: None,
+            } => Ok(Box::new(RangeIterator::new(*start..))),
+            _ => Ok(Box::new(
+                self.to_iter(span)?.collect::<Vec<_>>().into_iter(),
+            )),
+        }
+    }
 }
 
 struct StringIterator<'src, 'tree> {
@@ -108,3 +141,21 @@ impl<'tree> Iterator for ListIterator<'_, 'tree> {
         self.inner.next().map(Rc::clone)
     }
 }
+
+struct MapIterator<'src, 'tree> {
+    inner: Keys<'src, MapKey, WrappedValue<'tree>>,
+}
+
+impl<'src, 'tree> MapIterator<'src, 'tree> {
+    fn new(map: &'src IndexMap<MapKey, WrappedValue<'tree>>) -> Self {
+        Self { inner: map.keys() }
+    }
+}
+
+impl<'tree> Iterator for MapIterator<'_, 'tree> {
+    type Item = WrappedValue<'tree>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|key| key.to_value().wrapped())
+    }
+}
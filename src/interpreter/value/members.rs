@@ -15,13 +15,42 @@ impl<'tree> Value<'tree> {
         span: &Span,
     ) -> Result<WrappedValue<'tree>> {
         Ok(match &*this.borrow() {
-            Value::Object(fields)
-            | Value::Class {
-                statics: fields, ..
-            } => match fields.get(name) {
+            Value::Object { fields, .. } => match fields.get(name) {
                 Some(field) => Rc::clone(field),
                 None => Self::get_common_field(this, name, built_in_methods, span)?,
             },
+            Value::Class {
+                statics, parent, ..
+            } => match (statics.get(name), parent) {
+                (Some(field), _) => Rc::clone(field),
+                (None, Some(parent)) => Self::get_field(parent, name, built_in_methods, span)?,
+                (None, None) => Self::get_common_field(this, name, built_in_methods, span)?,
+            },
+            Value::Enum(decl) => match decl
+                .variants
+                .iter()
+                .position(|variant| variant.ident == name)
+            {
+                Some(idx) => Value::Variant {
+                    decl,
+                    idx,
+                    payload: None,
+                }
+                .wrapped(),
+                None => Self::get_common_field(this, name, built_in_methods, span)?,
+            },
+            Value::Variant {
+                decl,
+                idx,
+                payload: Some(payload),
+            } => match decl.variants[*idx]
+                .fields
+                .iter()
+                .position(|field| field == name)
+            {
+                Some(field) => Rc::clone(&payload[field]),
+                None => Self::get_common_field(this, name, built_in_methods, span)?,
+            },
             Value::String(val) => match name {
                 "length" => Value::Number(val.len().into()).wrapped(),
                 "toInt" => Rc::clone(&*built_in_methods.str_to_int),
@@ -47,10 +76,68 @@ impl<'tree> Value<'tree> {
                 "remove" => Rc::clone(&*built_in_methods.list_remove),
                 _ => Self::get_common_field(this, name, built_in_methods, span)?,
             },
+            Value::Map(_) => match name {
+                "keys" => Rc::clone(&*built_in_methods.map_keys),
+                "values" => Rc::clone(&*built_in_methods.map_values),
+                "entries" => Rc::clone(&*built_in_methods.map_entries),
+                "has" => Rc::clone(&*built_in_methods.map_has),
+                "remove" => Rc::clone(&*built_in_methods.map_remove),
+                _ => Self::get_common_field(this, name, built_in_methods, span)?,
+            },
             _ => Self::get_common_field(this, name, built_in_methods, span)?,
         })
     }
 
+    /// Replaces the field `name` of `this`, which has to be a field of an object or variant
+    /// or a static member of a class or one of its parents
+    pub fn set_field(
+        this: &WrappedValue<'tree>,
+        name: &str,
+        value: WrappedValue<'tree>,
+        span: &Span,
+    ) -> Result<()> {
+        match &mut *this.borrow_mut() {
+            Value::Object { fields, .. } => {
+                if let Some(field) = fields.get_mut(name) {
+                    *field = value;
+                    return Ok(());
+                }
+            }
+            Value::Class {
+                statics, parent, ..
+            } => match (statics.get_mut(name), parent) {
+                (Some(field), _) => {
+                    *field = value;
+                    return Ok(());
+                }
+                (None, Some(parent)) => return Self::set_field(parent, name, value, span),
+                (None, None) => {}
+            },
+            Value::Variant {
+                decl,
+                idx,
+                payload: Some(payload),
+            } => {
+                if let Some(field) = decl.variants[*idx]
+                    .fields
+                    .iter()
+                    .position(|field| field == name)
+                {
+                    payload[field] = value;
+                    return Ok(());
+                }
+            }
+            _ => {}
+        }
+        error!(
+            TypeError,
+            *span,
+            "Cannot assign to member '{}' of type '{}'",
+            name,
+            types::type_of(&this.borrow()),
+        );
+    }
+
     fn get_common_field(
         this: &WrappedValue<'tree>,
         name: &str,
@@ -67,7 +154,7 @@ impl<'tree> Value<'tree> {
                 "{} has no member called '{}'",
                 match &*this.borrow() {
                     Value::Class { .. } => Cow::Borrowed("Class"),
-                    Value::Object(..) => Cow::Borrowed("Object"),
+                    Value::Object { .. } => Cow::Borrowed("Object"),
                     _ => Cow::Owned(format!("Type '{}'", types::type_of(&this.borrow()))),
                 },
                 name,
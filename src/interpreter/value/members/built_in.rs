@@ -2,7 +2,10 @@ use std::{rc::Rc, str::FromStr};
 
 use crate::{
     error::{Result, Span},
-    interpreter::value::{indexing::ToListIndex, BuiltIn, Value, WrappedValue},
+    interpreter::value::{
+        indexing::{ToListIndex, ToMapKey},
+        BuiltIn, ToValue, Value, WrappedValue,
+    },
 };
 use once_cell::unsync::Lazy;
 use rust_decimal::{prelude::ToPrimitive, Decimal};
@@ -20,7 +23,7 @@ macro_rules! parse_err {
 }
 
 pub struct BuiltInMethods<'tree> {
-    pub(super) to_string: Lazy<WrappedValue<'tree>>,
+    pub(crate) to_string: Lazy<WrappedValue<'tree>>,
     pub(super) to_bool: Lazy<WrappedValue<'tree>>,
     pub(super) clone: Lazy<WrappedValue<'tree>>,
     pub(super) str_to_int: Lazy<WrappedValue<'tree>>,
@@ -38,6 +41,11 @@ pub struct BuiltInMethods<'tree> {
     pub(super) list_pop: Lazy<WrappedValue<'tree>>,
     pub(super) list_insert: Lazy<WrappedValue<'tree>>,
     pub(super) list_remove: Lazy<WrappedValue<'tree>>,
+    pub(super) map_keys: Lazy<WrappedValue<'tree>>,
+    pub(super) map_values: Lazy<WrappedValue<'tree>>,
+    pub(super) map_entries: Lazy<WrappedValue<'tree>>,
+    pub(super) map_has: Lazy<WrappedValue<'tree>>,
+    pub(super) map_remove: Lazy<WrappedValue<'tree>>,
 }
 
 impl<'tree> BuiltInMethods<'tree> {
@@ -67,8 +75,26 @@ impl<'tree> BuiltInMethods<'tree> {
             list_pop: Lazy::new(|| Value::BuiltIn(BuiltIn::Method(list_pop)).wrapped()),
             list_insert: Lazy::new(|| Value::BuiltIn(BuiltIn::Method(list_insert)).wrapped()),
             list_remove: Lazy::new(|| Value::BuiltIn(BuiltIn::Method(list_remove)).wrapped()),
+            map_keys: Lazy::new(|| Value::BuiltIn(BuiltIn::Method(map_keys)).wrapped()),
+            map_values: Lazy::new(|| Value::BuiltIn(BuiltIn::Method(map_values)).wrapped()),
+            map_entries: Lazy::new(|| Value::BuiltIn(BuiltIn::Method(map_entries)).wrapped()),
+            map_has: Lazy::new(|| Value::BuiltIn(BuiltIn::Method(map_has)).wrapped()),
+            map_remove: Lazy::new(|| Value::BuiltIn(BuiltIn::Method(map_remove)).wrapped()),
         }
     }
+
+    /// Whether `method` changes the value it is called on
+    pub(crate) fn mutates(&self, method: &WrappedValue<'tree>) -> bool {
+        [
+            &self.list_push,
+            &self.list_pop,
+            &self.list_insert,
+            &self.list_remove,
+            &self.map_remove,
+        ]
+        .into_iter()
+        .any(|mutating| Rc::ptr_eq(method, mutating))
+    }
 }
 
 fn to_string<'tree>(
@@ -108,7 +134,7 @@ fn str_to_int<'tree>(
     if args.len() > 1 {
         expect_len!(args, 1, "toInt", span);
     }
-    let radix = match args.get(0) {
+    let radix = match args.first() {
         Some(val) => match &*val.borrow() {
             Value::Number(radix) => match radix.to_u32() {
                 Some(num) => num,
@@ -152,7 +178,7 @@ fn str_to_bool<'tree>(
     let borrow = this.borrow();
     let str = borrow.unwrap_string();
     expect_len!(args, 0, "toBool", span);
-    Ok(Value::Bool(str.to_ascii_lowercase() == "true").wrapped())
+    Ok(Value::Bool(str.eq_ignore_ascii_case("true")).wrapped())
 }
 
 fn str_to_bool_strict<'tree>(
@@ -319,3 +345,66 @@ fn list_remove<'tree>(
     list.remove(args[0].borrow().to_list_index(list.len(), span)?);
     Ok(Value::Null.wrapped())
 }
+
+fn map_keys<'tree>(
+    this: &WrappedValue<'tree>,
+    args: Vec<WrappedValue<'tree>>,
+    span: &Span,
+) -> Result<WrappedValue<'tree>> {
+    let borrow = this.borrow();
+    let map = borrow.unwrap_map();
+    expect_len!(args, 0, "keys", span);
+    Ok(Value::List(map.keys().map(|key| key.to_value().wrapped()).collect()).wrapped())
+}
+
+fn map_values<'tree>(
+    this: &WrappedValue<'tree>,
+    args: Vec<WrappedValue<'tree>>,
+    span: &Span,
+) -> Result<WrappedValue<'tree>> {
+    let borrow = this.borrow();
+    let map = borrow.unwrap_map();
+    expect_len!(args, 0, "values", span);
+    Ok(Value::List(map.values().map(Rc::clone).collect()).wrapped())
+}
+
+fn map_entries<'tree>(
+    this: &WrappedValue<'tree>,
+    args: Vec<WrappedValue<'tree>>,
+    span: &Span,
+) -> Result<WrappedValue<'tree>> {
+    let borrow = this.borrow();
+    let map = borrow.unwrap_map();
+    expect_len!(args, 0, "entries", span);
+    Ok(Value::List(
+        map.iter()
+            .map(|(key, val)| Value::List(vec![key.to_value().wrapped(), Rc::clone(val)]).wrapped())
+            .collect(),
+    )
+    .wrapped())
+}
+
+fn map_has<'tree>(
+    this: &WrappedValue<'tree>,
+    args: Vec<WrappedValue<'tree>>,
+    span: &Span,
+) -> Result<WrappedValue<'tree>> {
+    let borrow = this.borrow();
+    let map = borrow.unwrap_map();
+    expect_len!(args, 1, "has", span);
+    Ok(Value::Bool(map.contains_key(&args[0].borrow().to_map_key(span)?)).wrapped())
+}
+
+fn map_remove<'tree>(
+    this: &WrappedValue<'tree>,
+    args: Vec<WrappedValue<'tree>>,
+    span: &Span,
+) -> Result<WrappedValue<'tree>> {
+    expect_len!(args, 1, "remove", span);
+    let key = args[0].borrow().to_map_key(span)?;
+    let mut borrow = this.borrow_mut();
+    let map = borrow.unwrap_map_mut();
+    Ok(map
+        .shift_remove(&key)
+        .unwrap_or_else(|| Value::Null.wrapped()))
+}
@@ -9,6 +9,7 @@ impl Value<'_> {
             Value::Range { start, end } => start != end,
             Value::Null => false,
             Value::List(vec) if vec.is_empty() => false,
+            Value::Map(map) if map.is_empty() => false,
             _ => true,
         }
     }
@@ -8,9 +8,14 @@ pub enum Type {
     String,
     Range,
     List,
+    Map,
     Function,
+    Generator,
     Class,
     Object,
+    Enum,
+    /// A value of the enum with the given name
+    EnumValue(String),
     Null,
 }
 
@@ -20,14 +25,18 @@ impl Display for Type {
             f,
             "{}",
             match self {
+                Type::EnumValue(name) => name,
                 Type::Number => "number",
                 Type::Bool => "bool",
                 Type::String => "string",
                 Type::Range => "range",
                 Type::List => "list",
+                Type::Map => "map",
                 Type::Function => "function",
+                Type::Generator => "generator",
                 Type::Class => "class",
                 Type::Object => "object",
+                Type::Enum => "enum",
                 Type::Null => "null",
             }
         )
@@ -41,9 +50,18 @@ pub fn type_of(value: &Value) -> Type {
         Value::String(_) => Type::String,
         Value::Range { .. } => Type::Range,
         Value::List(_) => Type::List,
-        Value::Function { .. } | Value::BuiltIn(_) => Type::Function,
+        Value::Map(_) => Type::Map,
+        Value::Function { .. } | Value::Closure { .. } | Value::BuiltIn(_) => Type::Function,
+        Value::Generator(_) => Type::Generator,
         Value::Class { .. } => Type::Class,
-        Value::Object(_) => Type::Object,
+        Value::Object { .. } => Type::Object,
+        Value::Enum(_) => Type::Enum,
+        Value::Variant {
+            decl,
+            idx,
+            payload: None,
+        } if !decl.variants[*idx].fields.is_empty() => Type::Function,
+        Value::Variant { decl, .. } => Type::EnumValue(decl.ident.clone()),
         Value::Null => Type::Null,
     }
 }
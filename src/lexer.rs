@@ -1,7 +1,8 @@
 use crate::{
-    error::{Error, Location, Span},
+    error::{Error, Location, SourceId, Span},
     tokens::{Token, TokenKind},
 };
+use rust_decimal::Decimal;
 use std::{
     mem,
     str::{self, Chars},
@@ -19,18 +20,17 @@ const LETTERS: [char; 53] = [
     'T', 't', 'U', 'u', 'V', 'v', 'W', 'w', 'X', 'x', 'Y', 'y', 'Z', 'z', '_',
 ];
 
-const ESCAPE_CHAR: [char; 10] = ['\\', '\'', '"', 'a', 'b', 'f', 'n', 'r', 't', 'v'];
+const ESCAPE_CHAR: [char; 12] = ['\\', '\'', '"', '{', '}', 'a', 'b', 'f', 'n', 'r', 't', 'v'];
 
 macro_rules! lex_error {
-    ($self:ident, $start:ident, $($arg:tt)*) => {
-        return Err((
+    ($self:ident, $start:ident, $kind:expr => $value:expr; $($arg:tt)*) => {
+        return Err(Box::new((
             error_val!(SyntaxError, ($start, $self.location), $($arg)*),
-            Token::new(
-                TokenKind::Unknown,
-                "Unknown".to_string(),
-                Span::new($start, $self.location),
-            ),
-        ))
+            Token::new($kind, $value.to_string(), Span::new($start, $self.location)),
+        )))
+    };
+    ($self:ident, $start:ident, $($arg:tt)*) => {
+        lex_error!($self, $start, TokenKind::Unknown => "Unknown"; $($arg)*)
     };
 }
 
@@ -47,7 +47,8 @@ macro_rules! char_construct {
     (@optional $kind:ident) => { Some(TokenKind::$kind) };
 }
 
-type LexResult<T> = std::result::Result<T, (Error, Token)>;
+/// The error is returned together with a token which stands in for the invalid input
+type LexResult<T> = std::result::Result<T, Box<(Error, Token)>>;
 
 #[derive(Debug, Clone)]
 pub struct Lexer<'i> {
@@ -55,15 +56,19 @@ pub struct Lexer<'i> {
     curr_char: Option<char>,
     next_char: Option<char>,
     location: Location,
+    /// The quote and the depth of nested braces of every interpolated expression
+    /// which is currently being lexed, the innermost one is last
+    interpolations: Vec<(char, usize)>,
 }
 
 impl<'i> Lexer<'i> {
-    pub fn new(input: &'i str) -> Self {
+    pub fn new(input: &'i str, source: SourceId) -> Self {
         let mut lexer = Lexer {
             input: input.chars(),
             curr_char: None,
             next_char: None,
-            location: Location::new(),
+            location: Location::new(source),
+            interpolations: vec![],
         };
         lexer.advance();
         lexer.advance();
@@ -83,8 +88,10 @@ impl<'i> Lexer<'i> {
                         Span::new(start, self.location),
                     ));
                 }
+                '@' => return self.make_label(),
                 '"' | '\'' => return self.make_string(),
                 '.' => return self.make_dot(),
+                '?' => return self.make_question(),
                 '/' => {
                     if let Some(token) = self.make_slash() {
                         return Ok(token);
@@ -93,15 +100,48 @@ impl<'i> Lexer<'i> {
                 '*' => char_construct!(self, Star, StarAssign, Pow, PowAssign),
                 '(' => char_construct!(self, LParen, _, _, _),
                 ')' => char_construct!(self, RParen, _, _, _),
-                '{' => char_construct!(self, LBrace, _, _, _),
-                '}' => char_construct!(self, RBrace, _, _, _),
+                '{' => {
+                    if let Some((_, depth)) = self.interpolations.last_mut() {
+                        *depth += 1;
+                    }
+                    char_construct!(self, LBrace, _, _, _)
+                }
+                '}' => match self.interpolations.last_mut() {
+                    Some((_, 0)) => return self.make_string(),
+                    Some((_, depth)) => {
+                        *depth -= 1;
+                        char_construct!(self, RBrace, _, _, _)
+                    }
+                    None => char_construct!(self, RBrace, _, _, _),
+                },
                 '[' => char_construct!(self, LBrack, _, _, _),
                 ']' => char_construct!(self, RBrack, _, _, _),
                 ',' => char_construct!(self, Comma, _, _, _),
+                ':' => char_construct!(self, Colon, _, _, _),
                 ';' => char_construct!(self, Semicolon, _, _, _),
+                '|' if self.next_char == Some('>') => {
+                    let start = self.location;
+                    self.advance();
+                    self.advance();
+                    return Ok(Token::new(
+                        TokenKind::Pipe,
+                        "|>".to_string(),
+                        Span::new(start, self.location),
+                    ));
+                }
                 '|' => char_construct!(self, BitOr, BitOrAssign, Or, _),
                 '^' => char_construct!(self, BitXor, BitXorAssign, _, _),
                 '&' => char_construct!(self, BitAnd, BitAndAssign, And, _),
+                '=' if self.next_char == Some('>') => {
+                    let start = self.location;
+                    self.advance();
+                    self.advance();
+                    return Ok(Token::new(
+                        TokenKind::Arrow,
+                        "=>".to_string(),
+                        Span::new(start, self.location),
+                    ));
+                }
                 '=' => char_construct!(self, Assign, Equal, _, _),
                 '!' => char_construct!(self, Not, NotEqual, _, _),
                 '<' => char_construct!(self, LessThan, LessThanOrEqual, ShiftLeft, ShiftLeftAssign),
@@ -118,7 +158,7 @@ impl<'i> Lexer<'i> {
                 '\\' => char_construct!(self, Backslash, BackslashAssign, _, _),
                 _ => {
                     if DIGITS.contains(&curr_char) {
-                        return Ok(self.make_number());
+                        return self.make_number();
                     } else if LETTERS.contains(&curr_char) {
                         return Ok(self.make_name());
                     } else {
@@ -199,29 +239,39 @@ impl<'i> Lexer<'i> {
         }
     }
 
+    /// Lexes a string starting at its opening quote or, if it contains interpolated
+    /// expressions, the part of it starting at the `}` which ends the previous expression
     fn make_string(&mut self) -> LexResult<Token> {
         let start = self.location;
-        let start_quote = self.curr_char;
+        let (quote, continued) = match self.curr_char {
+            Some('}') => (self.interpolations.pop().unwrap().0, true),
+            quote => (quote.unwrap(), false),
+        };
         let mut string = String::new();
 
-        self.advance(); // start quote
-        while ![start_quote, Some('\\'), None].contains(&self.curr_char) {
-            string.push(self.curr_char.unwrap());
-            self.advance();
-        }
-        while self.curr_char == Some('\\') {
+        self.advance(); // start quote or closing brace
+        loop {
+            match self.curr_char {
+                None => break,
+                Some(char) if char == quote => break,
+                Some('{') if quote == '"' => break,
+                Some('\\') => {}
+                Some(char) => {
+                    string.push(char);
+                    self.advance();
+                    continue;
+                }
+            }
+
             let escape_pos = self.location;
             self.advance(); // backslash
-            if self.curr_char == None {
+            if self.curr_char.is_none() {
                 lex_error!(self, escape_pos, "Invalid escape sequence")
             }
             let curr_char = self.curr_char.unwrap();
 
             if ESCAPE_CHAR.contains(&curr_char) {
                 string.push(match curr_char {
-                    '\\' => '\\',
-                    '\'' => '\'',
-                    '"' => '"',
                     'a' => '\x07',
                     'b' => '\x08',
                     'f' => '\x0c',
@@ -229,7 +279,7 @@ impl<'i> Lexer<'i> {
                     'r' => '\r',
                     't' => '\t',
                     'v' => '\x0b',
-                    _ => unreachable!(),
+                    char => char,
                 });
                 self.advance();
             } else if OCTAL_DIGITS.contains(&curr_char) {
@@ -243,16 +293,20 @@ impl<'i> Lexer<'i> {
             } else {
                 lex_error!(self, escape_pos, "Invalid escape sequence");
             }
-
-            while ![start_quote, Some('\\'), None].contains(&self.curr_char) {
-                string.push(self.curr_char.unwrap());
-                self.advance();
-            }
         }
-        self.advance(); // end quote
+        let interpolates = self.curr_char == Some('{');
+        self.advance(); // end quote or opening brace
+        if interpolates {
+            self.interpolations.push((quote, 0));
+        }
 
         Ok(Token::new(
-            TokenKind::String,
+            match (continued, interpolates) {
+                (false, false) => TokenKind::String,
+                (false, true) => TokenKind::StringStart,
+                (true, true) => TokenKind::StringMiddle,
+                (true, false) => TokenKind::StringEnd,
+            },
             string,
             Span::new(start, self.location),
         ))
@@ -272,7 +326,7 @@ impl<'i> Lexer<'i> {
         };
         self.advance();
         for _ in 0..digits {
-            if self.curr_char == None
+            if self.curr_char.is_none()
                 || if is_hex {
                     !HEX_DIGITS.contains(&self.curr_char.unwrap())
                 } else {
@@ -290,13 +344,21 @@ impl<'i> Lexer<'i> {
         }
     }
 
-    fn make_number(&mut self) -> Token {
+    fn make_number(&mut self) -> LexResult<Token> {
         let start = self.location;
+        if self.curr_char == Some('0') {
+            match self.next_char {
+                Some('x' | 'X') => return self.make_radix_number(16, "hexadecimal"),
+                Some('o' | 'O') => return self.make_radix_number(8, "octal"),
+                Some('b' | 'B') => return self.make_radix_number(2, "binary"),
+                _ => {}
+            }
+        }
         let mut number = String::new();
         number.push(self.curr_char.unwrap());
         self.advance();
 
-        while self.curr_char != None
+        while self.curr_char.is_some()
             && (DIGITS.contains(&self.curr_char.unwrap()) || self.curr_char.unwrap() == '_')
         {
             if self.curr_char.unwrap() != '_' {
@@ -305,37 +367,159 @@ impl<'i> Lexer<'i> {
             self.advance();
         }
 
-        let next_char = self.next_char;
-        if self.curr_char == Some('.') && next_char != None && DIGITS.contains(&next_char.unwrap())
+        if let (Some('.'), Some(next_char)) = (self.curr_char, self.next_char) {
+            if DIGITS.contains(&next_char) {
+                number.push('.');
+                self.advance();
+                number.push(next_char);
+                self.advance();
+
+                while self.curr_char.is_some()
+                    && (DIGITS.contains(&self.curr_char.unwrap()) || self.curr_char.unwrap() == '_')
+                {
+                    if self.curr_char.unwrap() != '_' {
+                        number.push(self.curr_char.unwrap());
+                    }
+                    self.advance();
+                }
+            }
+        }
+
+        if self.at_exponent() {
+            return self.make_exponent(start, number);
+        }
+        Ok(Token::new(
+            TokenKind::Number,
+            number,
+            Span::new(start, self.location),
+        ))
+    }
+
+    /// Lexes a number with a `0x`, `0o` or `0b` prefix. Its token value is the number
+    /// in decimal notation.
+    fn make_radix_number(&mut self, radix: u32, name: &str) -> LexResult<Token> {
+        let start = self.location;
+        self.advance(); // '0'
+        let prefix = self.curr_char.unwrap();
+        self.advance();
+
+        let mut digits = String::new();
+        while let Some(char) = self
+            .curr_char
+            .filter(|char| LETTERS.contains(char) || DIGITS.contains(char))
         {
-            number.push('.');
-            self.advance();
-            number.push(next_char.unwrap());
+            if char != '_' {
+                digits.push(char);
+            }
             self.advance();
+        }
 
-            while self.curr_char != None
-                && (DIGITS.contains(&self.curr_char.unwrap()) || self.curr_char.unwrap() == '_')
+        if digits.is_empty() {
+            lex_error!(self, start, TokenKind::Number => "0"; "Expected {name} digits after '0{prefix}'");
+        }
+        if let Some(invalid) = digits.chars().find(|char| !char.is_digit(radix)) {
+            lex_error!(self, start, TokenKind::Number => "0"; "Invalid digit '{invalid}' in {name} number");
+        }
+        // Decimal::from_str_radix silently loses precision on overflow, so the value is
+        // accumulated with checked arithmetic instead
+        let mut number = Decimal::ZERO;
+        for digit in digits.chars().filter_map(|char| char.to_digit(radix)) {
+            match number
+                .checked_mul(Decimal::from(radix))
+                .and_then(|number| number.checked_add(Decimal::from(digit)))
             {
-                if self.curr_char.unwrap() != '_' {
-                    number.push(self.curr_char.unwrap());
+                Some(next) => number = next,
+                None => {
+                    lex_error!(self, start, TokenKind::Number => "0"; "Number '0{prefix}{digits}' is out of range")
                 }
-                self.advance();
             }
         }
+        Ok(Token::new(
+            TokenKind::Number,
+            number.to_string(),
+            Span::new(start, self.location),
+        ))
+    }
 
-        Token::new(TokenKind::Number, number, Span::new(start, self.location))
+    /// Checks whether an exponent like `e10` or `E-3` follows the digits of a number
+    fn at_exponent(&self) -> bool {
+        let is_digit = |char: Option<char>| char.is_some_and(|char| DIGITS.contains(&char));
+        match (self.curr_char, self.next_char) {
+            (Some('e' | 'E'), Some('+' | '-')) => is_digit(self.input.clone().next()),
+            (Some('e' | 'E'), next_char) => is_digit(next_char),
+            _ => false,
+        }
+    }
+
+    /// Lexes the exponent of a number in scientific notation. Its token value is the number
+    /// in decimal notation.
+    fn make_exponent(&mut self, start: Location, mut number: String) -> LexResult<Token> {
+        number.push('e');
+        self.advance();
+        if let Some(sign @ ('+' | '-')) = self.curr_char {
+            number.push(sign);
+            self.advance();
+        }
+        while let Some(char) = self
+            .curr_char
+            .filter(|char| DIGITS.contains(char) || *char == '_')
+        {
+            if char != '_' {
+                number.push(char);
+            }
+            self.advance();
+        }
+
+        match Decimal::from_scientific(&number) {
+            Ok(number) => Ok(Token::new(
+                TokenKind::Number,
+                number.normalize().to_string(),
+                Span::new(start, self.location),
+            )),
+            Err(_) => {
+                lex_error!(self, start, TokenKind::Number => "0"; "Number '{number}' is out of range")
+            }
+        }
+    }
+
+    fn make_question(&mut self) -> LexResult<Token> {
+        let start = self.location;
+        self.advance();
+
+        let (kind, value) = match self.curr_char {
+            Some('.') => (TokenKind::QuestionDot, "?."),
+            Some('?') => {
+                self.advance();
+                if self.curr_char == Some('=') {
+                    (TokenKind::CoalesceAssign, "??=")
+                } else {
+                    return Ok(Token::new(
+                        TokenKind::Coalesce,
+                        "??".to_string(),
+                        Span::new(start, self.location),
+                    ));
+                }
+            }
+            _ => lex_error!(self, start, "Illegal character '?'"),
+        };
+        self.advance();
+        Ok(Token::new(
+            kind,
+            value.to_string(),
+            Span::new(start, self.location),
+        ))
     }
 
     fn make_dot(&mut self) -> LexResult<Token> {
         let start = self.location;
         self.advance();
 
-        if self.curr_char != None && DIGITS.contains(&self.curr_char.unwrap()) {
+        if self.curr_char.is_some() && DIGITS.contains(&self.curr_char.unwrap()) {
             let mut number = String::from("0.");
             number.push(self.curr_char.unwrap());
             self.advance();
 
-            while self.curr_char != None
+            while self.curr_char.is_some()
                 && (DIGITS.contains(&self.curr_char.unwrap()) || self.curr_char.unwrap() == '_')
             {
                 if self.curr_char.unwrap() != '_' {
@@ -344,6 +528,9 @@ impl<'i> Lexer<'i> {
                 self.advance();
             }
 
+            if self.at_exponent() {
+                return self.make_exponent(start, number);
+            }
             return Ok(Token::new(
                 TokenKind::Number,
                 number,
@@ -413,12 +600,35 @@ impl<'i> Lexer<'i> {
         }
     }
 
+    fn make_label(&mut self) -> LexResult<Token> {
+        let start = self.location;
+        let mut label = String::from('@');
+        self.advance();
+        if !self.curr_char.is_some_and(|char| LETTERS.contains(&char)) {
+            lex_error!(self, start, "Expected the name of a label after '@'");
+        }
+
+        while let Some(char) = self
+            .curr_char
+            .filter(|char| LETTERS.contains(char) || DIGITS.contains(char))
+        {
+            label.push(char);
+            self.advance();
+        }
+
+        Ok(Token::new(
+            TokenKind::Label,
+            label,
+            Span::new(start, self.location),
+        ))
+    }
+
     fn make_name(&mut self) -> Token {
         let start = self.location;
         let mut name = String::from(self.curr_char.unwrap());
         self.advance();
 
-        while self.curr_char != None
+        while self.curr_char.is_some()
             && (LETTERS.contains(&self.curr_char.unwrap())
                 || DIGITS.contains(&self.curr_char.unwrap()))
         {
@@ -428,6 +638,7 @@ impl<'i> Lexer<'i> {
 
         let kind = match name.as_str() {
             "var" => TokenKind::Var,
+            "const" => TokenKind::Const,
             "true" => TokenKind::True,
             "false" => TokenKind::False,
             "null" => TokenKind::Null,
@@ -436,15 +647,23 @@ impl<'i> Lexer<'i> {
             "fun" => TokenKind::Fun,
             "static" => TokenKind::Static,
             "class" => TokenKind::Class,
+            "enum" => TokenKind::Enum,
+            "extends" => TokenKind::Extends,
+            "super" => TokenKind::Super,
             "loop" => TokenKind::Loop,
             "while" => TokenKind::While,
             "for" => TokenKind::For,
             "in" => TokenKind::In,
             "return" => TokenKind::Return,
+            "yield" => TokenKind::Yield,
             "break" => TokenKind::Break,
             "continue" => TokenKind::Continue,
             "try" => TokenKind::Try,
             "catch" => TokenKind::Catch,
+            "finally" => TokenKind::Finally,
+            "match" => TokenKind::Match,
+            "import" => TokenKind::Import,
+            "as" => TokenKind::As,
             _ => TokenKind::Identifier,
         };
         Token::new(kind, name, Span::new(start, self.location))
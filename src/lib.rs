@@ -1,5 +1,6 @@
 #[macro_use]
-mod error;
+pub mod error;
+pub mod checker;
 pub mod interpreter;
 #[cfg(feature = "no_std_io")]
 pub mod io;
@@ -7,3 +8,4 @@ pub mod lexer;
 pub mod nodes;
 pub mod parser;
 pub mod tokens;
+pub mod vm;
@@ -1,6 +1,9 @@
-use std::fmt::Debug;
+use std::fmt::{self, Debug, Display, Formatter};
 
-use crate::{error::Span, tokens::TokenKind};
+use crate::{
+    error::{ErrorKind, Span},
+    tokens::TokenKind,
+};
 use rust_decimal::Decimal;
 
 macro_rules! node {
@@ -22,17 +25,27 @@ pub enum Statement {
     Var(VarStmt),
     Function(FunctionDecl),
     Class(ClassDecl),
+    Enum(EnumDecl),
     Break(BreakStmt),
     Continue(ContinueStmt),
     Return(ReturnStmt),
+    Yield(YieldStmt),
+    Import(ImportStmt),
     Expr(Expression),
 }
-node! { VarStmt; ident: String, expr: Option<Expression> }
-node! { FunctionDecl; ident: String, args: Params, block: Block }
-node! { ClassDecl; ident: String, block: MemberBlock }
-node! { BreakStmt; expr: Option<Expression> }
-node! { ContinueStmt; }
+// type annotations are only used by the checker and ignored when running the program
+node! { VarStmt; is_const: bool, target: Target, annotation: Option<TypeAnnotation>, expr: Option<Expression> }
+// functions containing a `yield` statement return a generator when called
+node! { FunctionDecl; ident: String, args: Params, return_type: Option<TypeAnnotation>, block: Block, is_generator: bool }
+node! { ClassDecl; ident: String, parent: Option<Box<MemberExpr>>, block: MemberBlock }
+node! { EnumDecl; ident: String, variants: Vec<EnumVariant> }
+node! { EnumVariant; ident: String, fields: Vec<String> }
+// the labels of `break` and `continue` are names of enclosing loops without the quote
+node! { BreakStmt; label: Option<String>, expr: Option<Expression> }
+node! { ContinueStmt; label: Option<String> }
 node! { ReturnStmt; expr: Option<Expression> }
+node! { YieldStmt; expr: Option<Expression> }
+node! { ImportStmt; path: String, ident: String }
 
 node! { Member; is_static: bool, kind: MemberKind }
 #[derive(Debug, PartialEq, Clone)]
@@ -42,15 +55,18 @@ pub enum MemberKind {
 }
 node! { MemberBlock; members: Vec<Member> }
 
-pub type Expression = RangeExpr;
+pub type Expression = PipeExpr;
+// every stage is a call referring to the value of the previous stage as `_`
+node! { PipeExpr; base: RangeExpr, stages: Vec<CallExpr> }
 #[derive(Debug, PartialEq, Clone)]
 pub enum RangeExpr {
-    None(Box<OrExpr>),
-    Closed(Box<OrExpr>, TokenKind, Box<OrExpr>, Span),
-    OpenEnd(Box<OrExpr>, Span),
-    OpenStart(TokenKind, Box<OrExpr>, Span),
+    None(Box<CoalesceExpr>),
+    Closed(Box<CoalesceExpr>, TokenKind, Box<CoalesceExpr>, Span),
+    OpenEnd(Box<CoalesceExpr>, Span),
+    OpenStart(TokenKind, Box<CoalesceExpr>, Span),
     Open,
 }
+node! { CoalesceExpr; base: OrExpr, following: Vec<OrExpr> }
 node! { OrExpr; base: AndExpr, following: Vec<AndExpr> }
 node! { AndExpr; base: BitOrExpr, following: Vec<BitOrExpr> }
 node! { BitOrExpr; base: BitXorExpr, following: Vec<BitXorExpr> }
@@ -79,10 +95,14 @@ pub enum Atom {
     Number(Decimal),
     Bool(bool),
     String(String),
+    InterpolatedString(InterpolatedString),
     Null,
     Identifier { span: Span, name: String },
+    Super { span: Span, name: String },
     Expr(Expression),
     List(ListLiteral),
+    Comprehension(Comprehension),
+    Map(MapLiteral),
     IfExpr(IfExpr),
     ForExpr(ForExpr),
     WhileExpr(WhileExpr),
@@ -90,27 +110,305 @@ pub enum Atom {
     FunExpr(FunExpr),
     ClassExpr(ClassExpr),
     TryExpr(TryExpr),
+    MatchExpr(MatchExpr),
     BlockExpr(BlockExpr),
 }
+node! { InterpolatedString; parts: Vec<StringPart> }
+#[derive(Debug, PartialEq, Clone)]
+pub enum StringPart {
+    Literal(String),
+    Expr(Expression),
+}
 pub type ListLiteral = Vec<Expression>;
+// the clauses are nested from left to right like loops, the variables they bind are only
+// visible inside of the comprehension
+node! { Comprehension; expr: Expression, clauses: Vec<ComprehensionClause> }
+#[derive(Debug, PartialEq, Clone)]
+pub enum ComprehensionClause {
+    For { target: Target, iter: Expression },
+    If(Expression),
+}
+pub type MapLiteral = Vec<MapEntry>;
+node! { MapEntry; key: Expression, value: Expression }
 node! { IfExpr; cond: Expression, block: Block, else_block: Option<Block> }
-node! { ForExpr; ident: String, iter: Expression, block: Block }
-node! { WhileExpr; cond: Expression, block: Block }
-node! { LoopExpr; block: Block }
-node! { FunExpr; args: Params, block: Block }
-node! { ClassExpr; block: MemberBlock }
-node! { TryExpr; try_block: Block, ident: String, catch_block: Block }
+node! { ForExpr; label: Option<String>, target: Target, iter: Expression, block: Block }
+node! { WhileExpr; label: Option<String>, cond: Expression, block: Block }
+node! { LoopExpr; label: Option<String>, block: Block }
+node! { FunExpr; args: Params, return_type: Option<TypeAnnotation>, block: Block, is_generator: bool }
+node! { ClassExpr; parent: Option<Box<MemberExpr>>, block: MemberBlock }
+node! { TryExpr; try_block: Block, catches: Vec<CatchClause>, finally_block: Option<Block> }
+node! { CatchClause; ident: String, filter: Option<CatchFilter>, block: Block }
+node! { MatchExpr; expr: Expression, arms: Vec<MatchArm> }
+node! { MatchArm; pattern: Pattern, guard: Option<Expression>, block: Block }
 pub type BlockExpr = Block;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum MemberPart {
     Field(String),
+    /// Like `Field`, but evaluates the whole chain to `null` if the base is `null`
+    OptionalField(String),
     Index(Expression),
 }
 #[derive(Debug, PartialEq, Clone)]
+pub enum CatchFilter {
+    /// Catches errors of a built-in kind, including thrown error objects of that kind
+    Kind(ErrorKind),
+    /// Catches instances of a class and its subclasses
+    Class(Box<MemberExpr>),
+}
+#[derive(Debug, PartialEq, Clone)]
 pub enum CallPart {
     Member(MemberPart),
     Args(Args),
 }
-pub type Args = Vec<Expression>;
-pub type Params = Vec<String>;
+pub type Args = Vec<Arg>;
+// named arguments are bound to the parameter of the same name
+node! { Arg; name: Option<String>, expr: Expression }
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Pattern {
+    Wildcard,
+    Binding(String),
+    Number(Decimal),
+    Bool(bool),
+    String(String),
+    Null,
+    /// The bounds are integers and at least one of them is present
+    Range {
+        start: Option<Decimal>,
+        end: Option<Decimal>,
+        inclusive: bool,
+    },
+    /// The optional rest is matched by `..` or `..name` and can be anywhere in the list
+    List {
+        before: Vec<Pattern>,
+        rest: Option<Option<String>>,
+        after: Vec<Pattern>,
+    },
+    /// Fields without a pattern are bound to a variable of the same name
+    Object(Vec<(String, Option<Pattern>)>),
+    /// Matches a variant of the enum called `enum_name`, any payload if `fields` is absent
+    Variant {
+        enum_name: String,
+        variant: String,
+        fields: Option<Vec<Pattern>>,
+    },
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Target {
+    Ident(String),
+    /// The optional rest is written as `..` or `..name` and can be anywhere in the list
+    List {
+        span: Span,
+        before: Vec<Target>,
+        rest: Option<Option<String>>,
+        after: Vec<Target>,
+    },
+    /// Fields without a target are bound to a variable of the same name
+    Object {
+        span: Span,
+        fields: Vec<(String, Option<Target>)>,
+    },
+}
+
+// parameters with a default value only follow ones without, the rest parameter collects
+// any additional positional arguments into a list
+node! { Params; params: Vec<Param>, rest: Option<String> }
+node! { Param; ident: String, annotation: Option<TypeAnnotation>, default: Option<Expression> }
+// the names of the types a value is allowed to have, like `number | null`
+node! { TypeAnnotation; types: Vec<String> }
+
+impl PipeExpr {
+    /// Returns the atom an expression consists of, if it has no operators or member accesses
+    pub fn bare_atom(&self) -> Option<&Atom> {
+        let node = self.bare_assign()?;
+        node.right.is_none().then_some(())?;
+        node.bare_target()
+    }
+
+    /// Returns the assignment an expression consists of, if it has no other operators
+    pub fn bare_assign(&self) -> Option<&AssignExpr> {
+        let RangeExpr::None(node) = self.stages.is_empty().then_some(&self.base)? else {
+            return None;
+        };
+        node.bare_assign()
+    }
+}
+
+impl CoalesceExpr {
+    /// Returns the assignment an operand consists of, if it has no other operators
+    pub fn bare_assign(&self) -> Option<&AssignExpr> {
+        let node = self.following.is_empty().then_some(&self.base)?;
+        let node = node.following.is_empty().then_some(&node.base)?;
+        let node = node.following.is_empty().then_some(&node.base)?;
+        let node = node.following.is_empty().then_some(&node.base)?;
+        let node = node.following.is_empty().then_some(&node.base)?;
+        let node = node.following.is_empty().then_some(&node.base)?;
+        let node = node.right.is_none().then_some(&node.left)?;
+        let node = node.right.is_none().then_some(&node.left)?;
+        let node = node.following.is_empty().then_some(&node.base)?;
+        let node = node.following.is_empty().then_some(&node.base)?;
+        let node = node.following.is_empty().then_some(&node.base)?;
+        let UnaryExpr::Done(node) = node else {
+            return None;
+        };
+        node.exponent.is_none().then_some(&node.base)
+    }
+}
+
+impl AssignExpr {
+    /// Returns the atom on the left side, if it has no calls or member accesses
+    pub fn bare_target(&self) -> Option<&Atom> {
+        let node = self.left.following.is_empty().then_some(&self.left.base)?;
+        node.following.is_empty().then_some(&node.base)
+    }
+}
+
+impl Arg {
+    /// The `_` argument of a pipeline stage which receives the value of the previous stage
+    pub fn placeholder(span: Span) -> Self {
+        let base = Atom::Identifier {
+            span,
+            name: "_".to_string(),
+        };
+        let base = MemberExpr {
+            span,
+            base,
+            following: vec![],
+        };
+        let left = CallExpr {
+            span,
+            base,
+            following: vec![],
+        };
+        let base = AssignExpr {
+            span,
+            left,
+            right: None,
+        };
+        let base = UnaryExpr::Done(Box::new(ExpExpr {
+            span,
+            base,
+            exponent: None,
+        }));
+        let base = MulExpr {
+            span,
+            base,
+            following: vec![],
+        };
+        let base = AddExpr {
+            span,
+            base,
+            following: vec![],
+        };
+        let left = ShiftExpr {
+            span,
+            base,
+            following: vec![],
+        };
+        let left = RelExpr {
+            span,
+            left,
+            right: None,
+        };
+        let base = EqExpr {
+            span,
+            left,
+            right: None,
+        };
+        let base = BitAndExpr {
+            span,
+            base,
+            following: vec![],
+        };
+        let base = BitXorExpr {
+            span,
+            base,
+            following: vec![],
+        };
+        let base = BitOrExpr {
+            span,
+            base,
+            following: vec![],
+        };
+        let base = AndExpr {
+            span,
+            base,
+            following: vec![],
+        };
+        let base = OrExpr {
+            span,
+            base,
+            following: vec![],
+        };
+        let base = RangeExpr::None(Box::new(CoalesceExpr {
+            span,
+            base,
+            following: vec![],
+        }));
+        Self {
+            span,
+            name: None,
+            expr: PipeExpr {
+                span,
+                base,
+                stages: vec![],
+            },
+        }
+    }
+
+    /// Whether the argument is a `_` placeholder
+    pub fn is_placeholder(&self) -> bool {
+        matches!(self.expr.bare_atom(), Some(Atom::Identifier { name, .. }) if name == "_")
+    }
+}
+
+impl Params {
+    /// The number of parameters without a default value
+    pub fn required(&self) -> usize {
+        self.params
+            .iter()
+            .take_while(|param| param.default.is_none())
+            .count()
+    }
+
+    /// Whether `count` positional arguments can be bound to these parameters
+    pub fn accepts(&self, count: usize) -> bool {
+        count >= self.required() && (count <= self.params.len() || self.rest.is_some())
+    }
+
+    /// Describes how many arguments are accepted, like `1 to 2`
+    pub fn count(&self) -> String {
+        let required = self.required();
+        match (self.rest.is_some(), self.params.len()) {
+            (true, _) => format!("at least {required}"),
+            (false, total) if total > required => format!("{required} to {total}"),
+            (false, total) => total.to_string(),
+        }
+    }
+}
+
+/// Formats the parameters like `(a, [b], ..rest)`, optional ones are in brackets
+impl Display for Params {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let params = self
+            .params
+            .iter()
+            .map(|param| match param.default {
+                Some(_) => format!("[{}]", param.ident),
+                None => param.ident.clone(),
+            })
+            .chain(self.rest.iter().map(|rest| format!("..{rest}")))
+            .collect::<Vec<_>>()
+            .join(", ");
+        write!(f, "({params})")
+    }
+}
+
+/// Formats the annotation like `number | null`
+impl Display for TypeAnnotation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.types.join(" | "))
+    }
+}
@@ -1,7 +1,9 @@
 use std::{mem, result};
 
+use rust_decimal::Decimal;
+
 use crate::{
-    error::{Error, Result, Span},
+    error::{Error, ErrorKind, Location, Result, Span},
     lexer::Lexer,
     nodes::*,
     tokens::{Token, TokenKind},
@@ -82,36 +84,36 @@ macro_rules! of_kinds {
 
 macro_rules! simple_expr {
     ($name:ident -> $type:ident : $($tok:ident),+ => $next:ident $kind:tt) => {
-        fn $name(&mut self, expects_stmt: bool) -> Result<$type> {
+        fn $name(&mut self, pos: Position) -> Result<$type> {
             let start = self.curr_tok.span.start;
-            simple_expr!(@kind self, start, expects_stmt, $type, $($tok),+ | $next, $kind)
+            simple_expr!(@kind self, start, pos, $type, $($tok),+ | $next, $kind)
         }
     };
-    (@kind $self:ident, $start:ident, $expects_stmt:ident, $type:ident, $($tok:ident),+ | $next:ident, *) => {{
-        let base = $self.$next($expects_stmt)?;
+    (@kind $self:ident, $start:ident, $pos:ident, $type:ident, $($tok:ident),+ | $next:ident, *) => {{
+        let base = $self.$next($pos)?;
         let mut following = vec![];
         while of_kinds!($self, $($tok),+) {
-            following.push(simple_expr!(@inner $self, $next, $($tok),+));
+            following.push(simple_expr!(@inner $self, $pos, $next, $($tok),+));
         }
         done!($type, $start, $self; base, following)
     }};
-    (@kind $self:ident, $start:ident, $expects_stmt:ident, $type:ident, $($tok:ident),+ | $next:ident, ?) => {{
-        let left = $self.$next($expects_stmt)?;
+    (@kind $self:ident, $start:ident, $pos:ident, $type:ident, $($tok:ident),+ | $next:ident, ?) => {{
+        let left = $self.$next($pos)?;
         let right = if of_kinds!($self, $($tok),+) {
-            Some(simple_expr!(@inner $self, $next, $($tok),+))
+            Some(simple_expr!(@inner $self, $pos, $next, $($tok),+))
         } else {
             None
         };
         done!($type, $start, $self; left, right)
     }};
-    (@inner $self:ident, $next:ident, $_:ident) => {{
+    (@inner $self:ident, $pos:ident, $next:ident, $_:ident) => {{
         $self.advance();
-        $self.$next(false)?
+        $self.$next($pos.operand())?
     }};
-    (@inner $self:ident, $next:ident, $($_:ident),+) => {{
+    (@inner $self:ident, $pos:ident, $next:ident, $($_:ident),+) => {{
         let tok = $self.curr_tok.kind;
         $self.advance();
-        (tok, $self.$next(false)?)
+        (tok, $self.$next($pos.operand())?)
     }};
 }
 
@@ -124,11 +126,48 @@ macro_rules! done {
     };
 }
 
+/// Where an expression is parsed, which decides how some of its tokens are read
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Position {
+    /// At the start of a statement
+    Statement,
+    /// Anywhere else, for example inside of brackets or as the value of a declaration
+    Expression,
+    /// In the guard of a match arm, where `=>` begins the arm instead of an arrow function
+    Guard,
+}
+
+impl Position {
+    /// The position of the operands which follow the first one of an expression
+    fn operand(self) -> Self {
+        match self {
+            Self::Guard => Self::Guard,
+            _ => Self::Expression,
+        }
+    }
+}
+
+/// An item of a parenthesized group with its span and the type annotation and default value
+/// which are only allowed if the group turns out to be the parameters of an arrow function
+type GroupItem = (Span, Expression, Option<TypeAnnotation>, Option<Expression>);
+
 pub struct Parser<'i> {
     lexer: Lexer<'i>,
     prev_tok: Token,
     curr_tok: Token,
     errors: Vec<Error>,
+    /// The labels of the loops enclosing the current position in the innermost function
+    labels: Vec<String>,
+    /// Whether the body of the innermost function contains a `yield`, `None` outside of functions
+    yields: Option<bool>,
+    /// Whether a `yield` at the current position can suspend the generator, which is only
+    /// the case in the blocks of loops and `if`, `try` or block expressions used as statements
+    can_suspend: bool,
+    /// The spans of the `yield` statements in the innermost function which can suspend it,
+    /// as long as the statements enclosing them are not part of a larger expression
+    suspend_points: Vec<Span>,
+    /// The spans of the `_` identifiers in the innermost pipeline stage, `None` outside of stages
+    placeholders: Option<Vec<Span>>,
 }
 
 impl<'i> Parser<'i> {
@@ -138,6 +177,11 @@ impl<'i> Parser<'i> {
             prev_tok: Token::dummy(),
             curr_tok: Token::dummy(),
             errors: vec![],
+            labels: vec![],
+            yields: None,
+            can_suspend: false,
+            suspend_points: vec![],
+            placeholders: None,
         }
     }
 
@@ -146,7 +190,7 @@ impl<'i> Parser<'i> {
         let statements = match Self::program(self) {
             Ok(statements) => statements,
             Err(error) => {
-                self.errors.push(error);
+                self.errors.push(*error);
                 return Err(mem::take(&mut self.errors));
             }
         };
@@ -165,13 +209,32 @@ impl<'i> Parser<'i> {
         mem::swap(&mut self.prev_tok, &mut self.curr_tok);
         self.curr_tok = match self.lexer.next_token() {
             Ok(token) => token,
-            Err((error, token)) => {
+            Err(error) => {
+                let (error, token) = *error;
                 self.errors.push(error);
                 token
             }
         };
     }
 
+    /// Returns the kind of the token after the current one without consuming it
+    fn peek_kind(&self) -> TokenKind {
+        match self.lexer.clone().next_token() {
+            Ok(token) => token.kind,
+            Err(error) => error.1.kind,
+        }
+    }
+
+    /// Returns the kind of the token after the colon which follows a label
+    fn labeled_kind(&self) -> TokenKind {
+        let mut lexer = self.lexer.clone();
+        let _colon = lexer.next_token();
+        match lexer.next_token() {
+            Ok(token) => token.kind,
+            Err(error) => error.1.kind,
+        }
+    }
+
     // ---------------------------------------
 
     #[inline]
@@ -207,30 +270,64 @@ impl<'i> Parser<'i> {
 
     fn statement(&mut self) -> Result<Statement> {
         Ok(match self.curr_tok.kind {
-            TokenKind::Var => Statement::Var(self.var_stmt()?),
+            TokenKind::Var | TokenKind::Const => Statement::Var(self.var_stmt()?),
             TokenKind::Fun => Statement::Function(self.function_decl()?),
             TokenKind::Class => Statement::Class(self.class_decl()?),
+            TokenKind::Enum => Statement::Enum(self.enum_decl()?),
             TokenKind::Break => Statement::Break(self.break_stmt()?),
             TokenKind::Continue => Statement::Continue(self.continue_stmt()?),
             TokenKind::Return => Statement::Return(self.return_stmt()?),
-            _ => Statement::Expr(self.expression(true)?),
+            TokenKind::Yield => Statement::Yield(self.yield_stmt()?),
+            TokenKind::Import => Statement::Import(self.import_stmt()?),
+            _ => {
+                let points = self.suspend_points.len();
+                let expr = self.expression(Position::Statement)?;
+                if !matches!(
+                    expr.bare_atom(),
+                    Some(
+                        Atom::IfExpr(_)
+                            | Atom::ForExpr(_)
+                            | Atom::WhileExpr(_)
+                            | Atom::LoopExpr(_)
+                            | Atom::TryExpr(_)
+                            | Atom::MatchExpr(_)
+                            | Atom::BlockExpr(_)
+                    )
+                ) {
+                    if let Some(span) = self.suspend_points.get(points) {
+                        error!(
+                            SyntaxError,
+                            *span, "'yield' cannot be used inside of expressions",
+                        );
+                    }
+                }
+                Statement::Expr(expr)
+            }
         })
     }
 
     fn var_stmt(&mut self) -> Result<VarStmt> {
         let start = self.curr_tok.span.start;
 
-        expect!(self, Var, "'var'");
-        let ident = expect_ident!(self);
+        let is_const = of_kinds!(self, Const);
+        if is_const {
+            self.advance();
+        } else {
+            expect!(self, Var, "'var'");
+        }
+        let target = self.target()?;
+        let annotation = self.annotation()?;
 
         let expr = if of_kinds!(self, Assign) {
             self.advance();
-            Some(self.expression(false)?)
+            Some(self.expression(Position::Expression)?)
+        } else if is_const {
+            syntax_err!(self, "Constants have to be initialized");
         } else {
             None
         };
 
-        done!(VarStmt, start, self; ident, expr)
+        done!(VarStmt, start, self; is_const, target, annotation, expr)
     }
 
     fn function_decl(&mut self) -> Result<FunctionDecl> {
@@ -239,9 +336,26 @@ impl<'i> Parser<'i> {
         expect!(self, Fun, "'fun'");
         let ident = expect_ident!(self);
         let args = self.params()?;
-        let block = self.block()?;
+        let return_type = self.annotation()?;
+        let (block, is_generator) = self.fun_body()?;
+
+        done!(FunctionDecl, start, self; ident, args, return_type, block, is_generator)
+    }
 
-        done!(FunctionDecl, start, self; ident, args, block)
+    /// Parses the block of a function and whether it is a generator
+    fn fun_body(&mut self) -> Result<(Block, bool)> {
+        let outer = self.yields.replace(false);
+        let can_suspend = mem::replace(&mut self.can_suspend, true);
+        let suspend_points = mem::take(&mut self.suspend_points);
+        // loops outside of the function cannot be left from inside of it
+        let labels = mem::take(&mut self.labels);
+        let block = self.block();
+        self.labels = labels;
+        self.can_suspend = can_suspend;
+        self.suspend_points = suspend_points;
+        let block = block?;
+        let is_generator = mem::replace(&mut self.yields, outer) == Some(true);
+        Ok((block, is_generator))
     }
 
     fn class_decl(&mut self) -> Result<ClassDecl> {
@@ -249,37 +363,100 @@ impl<'i> Parser<'i> {
 
         expect!(self, Class, "'class'");
         let ident = expect_ident!(self);
+        let parent = self.extends()?;
         let block = self.member_block()?;
 
-        done!(ClassDecl, start, self; ident, block)
+        done!(ClassDecl, start, self; ident, parent, block)
+    }
+
+    fn enum_decl(&mut self) -> Result<EnumDecl> {
+        let start = self.curr_tok.span.start;
+
+        expect!(self, Enum, "'enum'");
+        let ident = expect_ident!(self);
+        expect!(self, LBrace, "'{'");
+        let mut variants: Vec<EnumVariant> = vec![];
+        while !of_kinds!(self, RBrace) {
+            let variant = self.enum_variant()?;
+            if variants.iter().any(|other| other.ident == variant.ident) {
+                error!(
+                    SyntaxError,
+                    variant.span, "Duplicate variant '{}'", variant.ident,
+                );
+            }
+            variants.push(variant);
+            if !of_kinds!(self, Comma) {
+                break;
+            }
+            self.advance();
+        }
+        expect!(self, RBrace, "'}'");
+
+        done!(EnumDecl, start, self; ident, variants)
+    }
+
+    fn enum_variant(&mut self) -> Result<EnumVariant> {
+        let start = self.curr_tok.span.start;
+
+        let ident = expect_ident!(self);
+        let mut fields = vec![];
+        if of_kinds!(self, LParen) {
+            self.advance();
+            while !of_kinds!(self, RParen) {
+                fields.push(expect_ident!(self));
+                if !of_kinds!(self, Comma) {
+                    break;
+                }
+                self.advance();
+            }
+            expect!(self, RParen, "')'");
+        }
+
+        done!(EnumVariant, start, self; ident, fields)
     }
 
     fn break_stmt(&mut self) -> Result<BreakStmt> {
         let start = self.curr_tok.span.start;
 
         expect!(self, Break, "'break'");
-        let expr = if !of_kinds!(self, Eof, Eol, RBrace) {
-            Some(self.expression(false)?)
+        let label = self.jump_label()?;
+        let expr = if !(of_kinds!(self, Eof, RBrace) || of_kinds!(self, Eol, Semicolon)) {
+            Some(self.expression(Position::Expression)?)
         } else {
             None
         };
 
-        done!(BreakStmt, start, self; expr)
+        done!(BreakStmt, start, self; label, expr)
     }
 
     fn continue_stmt(&mut self) -> Result<ContinueStmt> {
         let start = self.curr_tok.span.start;
 
         expect!(self, Continue, "'continue'");
+        let label = self.jump_label()?;
 
-        done!(ContinueStmt, start, self;)
+        done!(ContinueStmt, start, self; label)
     }
+
+    /// Parses the optional label of `break` or `continue`, which has to belong to an enclosing loop
+    fn jump_label(&mut self) -> Result<Option<String>> {
+        if !of_kinds!(self, Label) {
+            return Ok(None);
+        }
+        let label = self.curr_tok.value()[1..].to_string();
+        if !self.labels.contains(&label) {
+            syntax_err!(self, "Unknown label '{}'", label);
+        }
+        self.advance();
+        Ok(Some(label))
+    }
+
     fn return_stmt(&mut self) -> Result<ReturnStmt> {
         let start = self.curr_tok.span.start;
 
         expect!(self, Return, "'return'");
-        let expr = if !of_kinds!(self, Eof, Eol, RBrace) {
-            Some(self.expression(false)?)
+        let expr = if !(of_kinds!(self, Eof, RBrace) || of_kinds!(self, Eol, Semicolon)) {
+            Some(self.expression(Position::Expression)?)
         } else {
             None
         };
@@ -287,6 +464,70 @@ impl<'i> Parser<'i> {
         done!(ReturnStmt, start, self; expr)
     }
 
+    fn yield_stmt(&mut self) -> Result<YieldStmt> {
+        let start = self.curr_tok.span.start;
+
+        expect!(self, Yield, "'yield'");
+        match &mut self.yields {
+            Some(yields) => *yields = true,
+            None => error!(
+                SyntaxError,
+                self.prev_tok.span, "'yield' can only be used inside of functions",
+            ),
+        }
+        if !self.can_suspend {
+            error!(
+                SyntaxError,
+                self.prev_tok.span, "'yield' cannot be used inside of expressions",
+            );
+        }
+        self.suspend_points.push(self.prev_tok.span);
+        let expr = if !(of_kinds!(self, Eof, RBrace) || of_kinds!(self, Eol, Semicolon)) {
+            Some(self.expression(Position::Expression)?)
+        } else {
+            None
+        };
+
+        done!(YieldStmt, start, self; expr)
+    }
+
+    fn import_stmt(&mut self) -> Result<ImportStmt> {
+        let start = self.curr_tok.span.start;
+
+        expect!(self, Import, "'import'");
+        if !of_kinds!(self, String) {
+            syntax_err!(self, "Expected string, found '{}'", self.curr_tok.value());
+        }
+        let path = self.curr_tok.take_value();
+        let path_span = self.curr_tok.span;
+        self.advance();
+        let ident = if self.curr_tok.kind == TokenKind::As {
+            self.advance();
+            expect_ident!(self)
+        } else {
+            let stem = path
+                .rsplit(['/', '\\'])
+                .next()
+                .unwrap_or_default()
+                .trim_end_matches(".ro");
+            let mut chars = stem.chars();
+            match chars.next() {
+                Some(first)
+                    if (first.is_ascii_alphabetic() || first == '_')
+                        && chars.all(|char| char.is_ascii_alphanumeric() || char == '_') =>
+                {
+                    stem.to_string()
+                }
+                _ => error!(
+                    SyntaxError,
+                    path_span, "Cannot use '{}' as module name, specify one with 'as'", stem,
+                ),
+            }
+        };
+
+        done!(ImportStmt, start, self; path, ident)
+    }
+
     fn member(&mut self) -> Result<Member> {
         let start = self.curr_tok.span.start;
 
@@ -295,7 +536,14 @@ impl<'i> Parser<'i> {
             self.advance();
         }
         let kind = if of_kinds!(self, Var) {
-            MemberKind::Attribute(self.var_stmt()?)
+            let node = self.var_stmt()?;
+            if !matches!(node.target, Target::Ident(_)) {
+                error!(
+                    SyntaxError,
+                    node.span, "Class attributes cannot be destructured",
+                );
+            }
+            MemberKind::Attribute(node)
         } else {
             MemberKind::Method(self.function_decl()?)
         };
@@ -318,11 +566,64 @@ impl<'i> Parser<'i> {
     }
 
     #[inline]
-    fn expression(&mut self, expects_stmt: bool) -> Result<Expression> {
-        self.range_expr(expects_stmt)
+    fn expression(&mut self, pos: Position) -> Result<Expression> {
+        self.pipe_expr(pos)
     }
 
-    fn range_expr(&mut self, expects_stmt: bool) -> Result<RangeExpr> {
+    fn pipe_expr(&mut self, pos: Position) -> Result<PipeExpr> {
+        let start = self.curr_tok.span.start;
+
+        let base = self.range_expr(pos)?;
+        let mut stages = vec![];
+        while of_kinds!(self, Pipe) {
+            self.advance();
+            stages.push(self.pipe_stage(pos.operand())?);
+        }
+
+        done!(PipeExpr, start, self; base, stages)
+    }
+
+    /// Parses a stage of a pipeline and passes the previous value as the first argument
+    /// of its last call, unless the stage starts with `_` or has a `_` argument
+    fn pipe_stage(&mut self, pos: Position) -> Result<CallExpr> {
+        let outer = self.placeholders.replace(vec![]);
+        let stage = self.call_expr(pos);
+        let placeholders = mem::replace(&mut self.placeholders, outer).unwrap_or_default();
+        let mut stage = stage?;
+
+        // only a `_` at the start or as an argument of the last call is replaced
+        let mut direct = vec![];
+        if let Atom::Identifier { name, span } = &stage.base.base {
+            if name == "_" {
+                direct.push(*span);
+            }
+        }
+        if let Some(CallPart::Args(args)) = stage.following.last() {
+            for arg in args.iter().filter(|arg| arg.is_placeholder()) {
+                if let Some(Atom::Identifier { span, .. }) = arg.expr.bare_atom() {
+                    direct.push(*span);
+                }
+            }
+        }
+        if let Some(span) = placeholders.iter().find(|span| !direct.contains(span)) {
+            error!(
+                SyntaxError,
+                *span, "'_' can only start a pipeline stage or be an argument of its last call",
+            );
+        }
+
+        if !direct.is_empty() {
+            return Ok(stage);
+        }
+        let placeholder = Arg::placeholder(stage.span);
+        match stage.following.last_mut() {
+            Some(CallPart::Args(args)) => args.insert(0, placeholder),
+            _ => stage.following.push(CallPart::Args(vec![placeholder])),
+        }
+        Ok(stage)
+    }
+
+    fn range_expr(&mut self, pos: Position) -> Result<RangeExpr> {
         let start = self.curr_tok.span.start;
 
         if of_kinds!(self, Dots, DotsInclusive) {
@@ -340,7 +641,7 @@ impl<'i> Parser<'i> {
                 }
                 Ok(RangeExpr::Open)
             } else {
-                let right = Box::new(self.or_expr(expects_stmt)?);
+                let right = Box::new(self.coalesce_expr(pos)?);
                 Ok(RangeExpr::OpenStart(
                     tok,
                     right,
@@ -348,7 +649,7 @@ impl<'i> Parser<'i> {
                 ))
             }
         } else {
-            let left = Box::new(self.or_expr(expects_stmt)?);
+            let left = Box::new(self.coalesce_expr(pos)?);
             if !of_kinds!(self, Dots, DotsInclusive) {
                 Ok(RangeExpr::None(left))
             } else {
@@ -369,7 +670,7 @@ impl<'i> Parser<'i> {
                         Span::new(start, self.prev_tok.span.end),
                     ))
                 } else {
-                    let right = Box::new(self.or_expr(expects_stmt)?);
+                    let right = Box::new(self.coalesce_expr(pos)?);
                     Ok(RangeExpr::Closed(
                         left,
                         tok,
@@ -381,6 +682,7 @@ impl<'i> Parser<'i> {
         }
     }
 
+    simple_expr!(coalesce_expr -> CoalesceExpr: Coalesce => or_expr *);
     simple_expr!(or_expr -> OrExpr: Or => and_expr *);
     simple_expr!(and_expr -> AndExpr: And => bit_or_expr *);
     simple_expr!(bit_or_expr -> BitOrExpr: BitOr => bit_xor_expr *);
@@ -392,30 +694,30 @@ impl<'i> Parser<'i> {
     simple_expr!(add_expr -> AddExpr: Plus, Minus => mul_expr *);
     simple_expr!(mul_expr -> MulExpr: Star, Slash, Rem, Backslash => unary_expr *);
 
-    fn unary_expr(&mut self, expects_stmt: bool) -> Result<UnaryExpr> {
+    fn unary_expr(&mut self, pos: Position) -> Result<UnaryExpr> {
         let start = self.curr_tok.span.start;
 
         if of_kinds!(self, Plus, Minus, Not) {
             let operator = self.curr_tok.kind;
             self.advance();
-            let expr = Box::new(self.unary_expr(false)?);
+            let expr = Box::new(self.unary_expr(pos.operand())?);
             Ok(UnaryExpr::Unary {
                 span: Span::new(start, self.prev_tok.span.end),
                 operator,
                 expr,
             })
         } else {
-            Ok(UnaryExpr::Done(Box::new(self.exp_expr(expects_stmt)?)))
+            Ok(UnaryExpr::Done(Box::new(self.exp_expr(pos)?)))
         }
     }
 
-    fn exp_expr(&mut self, expects_stmt: bool) -> Result<ExpExpr> {
+    fn exp_expr(&mut self, pos: Position) -> Result<ExpExpr> {
         let start = self.curr_tok.span.start;
 
-        let base = self.assign_expr(expects_stmt)?;
+        let base = self.assign_expr(pos)?;
         let exponent = if of_kinds!(self, Pow) {
             self.advance();
-            Some(self.unary_expr(false)?)
+            Some(self.unary_expr(pos.operand())?)
         } else {
             None
         };
@@ -423,10 +725,10 @@ impl<'i> Parser<'i> {
         done!(ExpExpr, start, self; base, exponent)
     }
 
-    fn assign_expr(&mut self, expects_stmt: bool) -> Result<AssignExpr> {
+    fn assign_expr(&mut self, pos: Position) -> Result<AssignExpr> {
         let start = self.curr_tok.span.start;
 
-        let left = self.call_expr(expects_stmt)?;
+        let left = self.call_expr(pos)?;
         let right = if of_kinds!(
             self,
             Assign,
@@ -441,10 +743,13 @@ impl<'i> Parser<'i> {
             BitAndAssign,
             BitXorAssign,
             BitOrAssign,
+            PowAssign,
+            CoalesceAssign,
         ) {
             let tok = self.curr_tok.kind;
+            Self::check_target(&left, tok == TokenKind::Assign)?;
             self.advance();
-            Some((tok, self.expression(false)?))
+            Some((tok, self.expression(pos.operand())?))
         } else {
             None
         };
@@ -452,14 +757,49 @@ impl<'i> Parser<'i> {
         done!(AssignExpr, start, self; left, right)
     }
 
-    fn call_expr(&mut self, expects_stmt: bool) -> Result<CallExpr> {
+    /// Fails if `node` is not a variable, field or index. With `destructure` set, list literals
+    /// whose items are such targets or nested list literals are allowed as well.
+    fn check_target(node: &CallExpr, destructure: bool) -> Result<()> {
+        let assignable = match (node.following.last(), node.base.following.last()) {
+            (Some(CallPart::Member(MemberPart::Field(_) | MemberPart::Index(_))), _)
+            | (None, Some(MemberPart::Field(_) | MemberPart::Index(_))) => true,
+            (None, None) => match &node.base.base {
+                Atom::Identifier { .. } => true,
+                Atom::List(items) if destructure => {
+                    for item in items {
+                        match item.bare_assign() {
+                            Some(AssignExpr {
+                                left, right: None, ..
+                            }) => Self::check_target(left, true)?,
+                            _ => error!(
+                                SyntaxError,
+                                item.span, "Expected variable, field or index, found expression",
+                            ),
+                        }
+                    }
+                    true
+                }
+                _ => false,
+            },
+            _ => false,
+        };
+        if !assignable {
+            error!(
+                SyntaxError,
+                node.span, "Expected variable, field or index, found expression",
+            );
+        }
+        Ok(())
+    }
+
+    fn call_expr(&mut self, pos: Position) -> Result<CallExpr> {
         let start = self.curr_tok.span.start;
 
-        let base = self.member_expr(expects_stmt)?;
+        let base = self.member_expr(pos)?;
         let mut following = vec![];
-        if of_kinds!(self, LParen) {
+        if self.continues_chain(&[TokenKind::LParen]) {
             following.push(CallPart::Args(self.args()?));
-            while of_kinds!(self, LParen, Dot, LBrack) {
+            while self.continues_chain(&[TokenKind::LParen, TokenKind::LBrack]) {
                 following.push(self.call_part()?);
             }
         }
@@ -467,54 +807,52 @@ impl<'i> Parser<'i> {
         done!(CallExpr, start, self; base, following)
     }
 
-    fn member_expr(&mut self, expects_stmt: bool) -> Result<MemberExpr> {
+    /// Skips line breaks and returns whether the current token is a `.`, a `?.` or one of `kinds`.
+    /// Brackets and parentheses at the start of a line begin a new statement instead,
+    /// so that `[a, b] = [b, a]` does not index the previous line.
+    fn continues_chain(&mut self, kinds: &[TokenKind]) -> bool {
+        let line_break = self.curr_tok.kind == TokenKind::Eol;
+        of_kinds!(@skip self);
+        matches!(self.curr_tok.kind, TokenKind::Dot | TokenKind::QuestionDot)
+            || (!line_break && kinds.contains(&self.curr_tok.kind))
+    }
+
+    fn member_expr(&mut self, pos: Position) -> Result<MemberExpr> {
         let start = self.curr_tok.span.start;
 
-        let base = self.atom(expects_stmt)?;
+        let base = self.atom(pos)?;
         let mut following = vec![];
-        while of_kinds!(self, Dot, LBrack) {
+        while self.continues_chain(&[TokenKind::LBrack]) {
             following.push(self.member_part()?);
         }
 
         done!(MemberExpr, start, self; base, following)
     }
 
-    fn atom(&mut self, expects_stmt: bool) -> Result<Atom> {
+    fn atom(&mut self, pos: Position) -> Result<Atom> {
         let start = self.curr_tok.span.start;
 
-        Ok(match self.curr_tok.kind {
-            TokenKind::Number => {
-                let num = self.curr_tok.take_value();
-                self.advance();
-                Atom::Number(match num.parse() {
-                    Ok(num) => num,
-                    Err(rust_decimal::Error::ErrorString(msg)) => {
-                        error!(ValueError, (start, self.prev_tok.span.end), "{}", msg);
-                    }
-                    Err(rust_decimal::Error::ExceedsMaximumPossibleValue) => {
-                        error!(
-                            ValueError,
-                            (start, self.prev_tok.span.end),
-                            "Value too high"
-                        );
-                    }
-                    Err(rust_decimal::Error::LessThanMinimumPossibleValue) => {
-                        error!(ValueError, (start, self.prev_tok.span.end), "Value too low");
-                    }
-                    Err(rust_decimal::Error::ScaleExceedsMaximumPrecision(_)) => {
-                        error!(
-                            ValueError,
-                            (start, self.prev_tok.span.end),
-                            "Value too precise"
-                        );
-                    }
-                    Err(_) => error!(
-                        ValueError,
-                        (start, self.prev_tok.span.end),
-                        "Failed to parse number"
-                    ),
-                })
-            }
+        if pos != Position::Guard
+            && self.curr_tok.kind == TokenKind::Identifier
+            && self.peek_kind() == TokenKind::Arrow
+        {
+            return Ok(Atom::FunExpr(self.arrow_fun()?));
+        }
+        let can_suspend = self.can_suspend;
+        self.can_suspend &= pos == Position::Statement
+            && matches!(
+                self.curr_tok.kind,
+                TokenKind::If
+                    | TokenKind::For
+                    | TokenKind::While
+                    | TokenKind::Loop
+                    | TokenKind::Label
+                    | TokenKind::Try
+                    | TokenKind::Match
+                    | TokenKind::LBrace
+            );
+        let atom = match self.curr_tok.kind {
+            TokenKind::Number => Atom::Number(self.number()?),
             TokenKind::True => {
                 self.advance();
                 Atom::Bool(true)
@@ -528,6 +866,7 @@ impl<'i> Parser<'i> {
                 self.advance();
                 Atom::String(str)
             }
+            TokenKind::StringStart => Atom::InterpolatedString(self.interpolated_string()?),
             TokenKind::Null => {
                 self.advance();
                 Atom::Null
@@ -535,55 +874,185 @@ impl<'i> Parser<'i> {
             TokenKind::Identifier => {
                 let name = self.curr_tok.take_value();
                 self.advance();
-                Atom::Identifier {
-                    span: Span::new(start, self.prev_tok.span.end),
-                    name,
+                let span = Span::new(start, self.prev_tok.span.end);
+                if let (Some(placeholders), "_") = (&mut self.placeholders, name.as_str()) {
+                    placeholders.push(span);
                 }
+                Atom::Identifier { span, name }
             }
-            TokenKind::LParen => {
+            TokenKind::Super => {
                 self.advance();
-                let expr = self.expression(false)?;
-                expect!(self, RParen, "')'");
-                Atom::Expr(expr)
+                expect!(self, Dot, "'.'");
+                let name = expect_ident!(self);
+                Atom::Super {
+                    span: Span::new(start, self.prev_tok.span.end),
+                    name,
+                }
             }
-            TokenKind::LBrack => Atom::List(self.list_literal()?),
+            TokenKind::LParen => self.group(pos)?,
+            TokenKind::LBrack => self.list_literal()?,
             TokenKind::If => Atom::IfExpr(self.if_expr()?),
             TokenKind::For => Atom::ForExpr(self.for_expr()?),
             TokenKind::While => Atom::WhileExpr(self.while_expr()?),
             TokenKind::Loop => Atom::LoopExpr(self.loop_expr()?),
+            TokenKind::Label => match self.labeled_kind() {
+                TokenKind::For => Atom::ForExpr(self.for_expr()?),
+                TokenKind::While => Atom::WhileExpr(self.while_expr()?),
+                _ => Atom::LoopExpr(self.loop_expr()?),
+            },
             TokenKind::Fun => Atom::FunExpr(self.fun_expr()?),
             TokenKind::Class => Atom::ClassExpr(self.class_expr()?),
             TokenKind::Try => Atom::TryExpr(self.try_expr()?),
+            TokenKind::Match => Atom::MatchExpr(self.match_expr()?),
             TokenKind::LBrace => Atom::BlockExpr(self.block_expr()?),
             _ => syntax_err!(
                 self,
                 "Expected {}, found '{}'",
-                if expects_stmt {
+                if pos == Position::Statement {
                     "statement"
                 } else {
                     "expression"
                 },
                 self.curr_tok.value(),
             ),
+        };
+        self.can_suspend = can_suspend;
+
+        Ok(atom)
+    }
+
+    fn number(&mut self) -> Result<Decimal> {
+        let start = self.curr_tok.span.start;
+        let num = self.curr_tok.take_value();
+        self.advance();
+        Ok(match num.parse() {
+            Ok(num) => num,
+            Err(rust_decimal::Error::ErrorString(msg)) => {
+                error!(ValueError, (start, self.prev_tok.span.end), "{}", msg);
+            }
+            Err(rust_decimal::Error::ExceedsMaximumPossibleValue) => {
+                error!(
+                    ValueError,
+                    (start, self.prev_tok.span.end),
+                    "Value too high"
+                );
+            }
+            Err(rust_decimal::Error::LessThanMinimumPossibleValue) => {
+                error!(ValueError, (start, self.prev_tok.span.end), "Value too low");
+            }
+            Err(rust_decimal::Error::ScaleExceedsMaximumPrecision(_)) => {
+                error!(
+                    ValueError,
+                    (start, self.prev_tok.span.end),
+                    "Value too precise"
+                );
+            }
+            Err(_) => error!(
+                ValueError,
+                (start, self.prev_tok.span.end),
+                "Failed to parse number"
+            ),
         })
     }
 
-    fn list_literal(&mut self) -> Result<ListLiteral> {
+    fn interpolated_string(&mut self) -> Result<InterpolatedString> {
+        let start = self.curr_tok.span.start;
+
+        let mut parts = vec![];
+        loop {
+            let kind = self.curr_tok.kind;
+            let literal = self.curr_tok.take_value();
+            self.advance();
+            if !literal.is_empty() {
+                parts.push(StringPart::Literal(literal));
+            }
+            if kind == TokenKind::StringEnd {
+                break;
+            }
+            parts.push(StringPart::Expr(self.expression(Position::Expression)?));
+            if !of_kinds!(self, StringMiddle, StringEnd) {
+                syntax_err!(self, "Expected '}}', found '{}'", self.curr_tok.value());
+            }
+        }
+
+        done!(InterpolatedString, start, self; parts)
+    }
+
+    fn list_literal(&mut self) -> Result<Atom> {
         expect!(self, LBrack, "'['");
+        if of_kinds!(self, Colon) {
+            self.advance();
+            expect!(self, RBrack, "']'");
+            return Ok(Atom::Map(vec![]));
+        }
         let mut exprs = vec![];
         if !of_kinds!(self, RBrack) {
-            exprs.push(self.expression(false)?);
+            let start = self.curr_tok.span.start;
+            let first = self.expression(Position::Expression)?;
+            if of_kinds!(self, Colon) {
+                return Ok(Atom::Map(self.map_literal(start, first)?));
+            }
+            if of_kinds!(self, For) {
+                return Ok(Atom::Comprehension(self.comprehension(start, first)?));
+            }
+            exprs.push(first);
             while of_kinds!(self, Comma) {
                 self.advance();
                 if of_kinds!(self, RBrack) {
                     break;
                 }
-                exprs.push(self.expression(false)?);
+                exprs.push(self.expression(Position::Expression)?);
+            }
+        }
+        expect!(self, RBrack, "']'");
+
+        Ok(Atom::List(exprs))
+    }
+
+    fn comprehension(&mut self, start: Location, expr: Expression) -> Result<Comprehension> {
+        let mut clauses = vec![];
+        // the first clause has to be a `for` clause
+        while of_kinds!(self, For) || (!clauses.is_empty() && of_kinds!(self, If)) {
+            let kind = self.curr_tok.kind;
+            self.advance();
+            expect!(self, LParen, "'('");
+            clauses.push(match kind {
+                TokenKind::For => {
+                    let target = self.target()?;
+                    expect!(self, In, "'in'");
+                    let iter = self.expression(Position::Expression)?;
+                    ComprehensionClause::For { target, iter }
+                }
+                _ => ComprehensionClause::If(self.expression(Position::Expression)?),
+            });
+            expect!(self, RParen, "')'");
+        }
+        expect!(self, RBrack, "']'");
+
+        done!(Comprehension, start, self; expr, clauses)
+    }
+
+    fn map_literal(&mut self, start: Location, first_key: Expression) -> Result<MapLiteral> {
+        let mut entries = vec![self.map_entry(start, first_key)?];
+        while of_kinds!(self, Comma) {
+            self.advance();
+            if of_kinds!(self, RBrack) {
+                break;
             }
+            let start = self.curr_tok.span.start;
+            let key = self.expression(Position::Expression)?;
+            entries.push(self.map_entry(start, key)?);
         }
         expect!(self, RBrack, "']'");
 
-        Ok(exprs)
+        Ok(entries)
+    }
+
+    fn map_entry(&mut self, start: Location, key: Expression) -> Result<MapEntry> {
+        expect!(self, Colon, "':'");
+        let value = self.expression(Position::Expression)?;
+
+        done!(MapEntry, start, self; key, value)
     }
 
     fn if_expr(&mut self) -> Result<IfExpr> {
@@ -591,7 +1060,7 @@ impl<'i> Parser<'i> {
 
         expect!(self, If, "'if'");
         expect!(self, LParen, "'('");
-        let cond = self.expression(false)?;
+        let cond = self.expression(Position::Expression)?;
         expect!(self, RParen, "')'");
         let block = self.block()?;
         let else_block = if of_kinds!(self, Else) {
@@ -607,36 +1076,59 @@ impl<'i> Parser<'i> {
     fn for_expr(&mut self) -> Result<ForExpr> {
         let start = self.curr_tok.span.start;
 
+        let label = self.loop_label();
         expect!(self, For, "'for'");
         expect!(self, LParen, "'('");
-        let ident = expect_ident!(self);
+        let target = self.target()?;
         expect!(self, In, "'in'");
-        let iter = self.expression(false)?;
+        let iter = self.expression(Position::Expression)?;
         expect!(self, RParen, "')'");
-        let block = self.block()?;
+        let block = self.loop_block(&label)?;
 
-        done!(ForExpr, start, self; ident, iter, block)
+        done!(ForExpr, start, self; label, target, iter, block)
     }
 
     fn while_expr(&mut self) -> Result<WhileExpr> {
         let start = self.curr_tok.span.start;
 
+        let label = self.loop_label();
         expect!(self, While, "'while'");
         expect!(self, LParen, "'('");
-        let cond = self.expression(false)?;
+        let cond = self.expression(Position::Expression)?;
         expect!(self, RParen, "')'");
-        let block = self.block()?;
+        let block = self.loop_block(&label)?;
 
-        done!(WhileExpr, start, self; cond, block)
+        done!(WhileExpr, start, self; label, cond, block)
     }
 
     fn loop_expr(&mut self) -> Result<LoopExpr> {
         let start = self.curr_tok.span.start;
 
+        let label = self.loop_label();
         expect!(self, Loop, "'loop'");
-        let block = self.block()?;
+        let block = self.loop_block(&label)?;
 
-        done!(LoopExpr, start, self; block)
+        done!(LoopExpr, start, self; label, block)
+    }
+
+    /// Parses the optional label in front of a loop
+    fn loop_label(&mut self) -> Option<String> {
+        if !of_kinds!(self, Label) {
+            return None;
+        }
+        let label = self.curr_tok.value()[1..].to_string();
+        self.advance();
+        expect!(self, Colon, "':'");
+        Some(label)
+    }
+
+    /// Parses the body of a loop, in which `break` and `continue` can refer to its label
+    fn loop_block(&mut self, label: &Option<String>) -> Result<Block> {
+        let count = self.labels.len();
+        self.labels.extend(label.clone());
+        let block = self.block();
+        self.labels.truncate(count);
+        block
     }
 
     fn fun_expr(&mut self) -> Result<FunExpr> {
@@ -644,18 +1136,172 @@ impl<'i> Parser<'i> {
 
         expect!(self, Fun, "'fun'");
         let args = self.params()?;
-        let block = self.block()?;
+        let return_type = self.annotation()?;
+        let (block, is_generator) = self.fun_body()?;
+
+        done!(FunExpr, start, self; args, return_type, block, is_generator)
+    }
+
+    /// Parses an arrow function with a single parameter without parentheses
+    fn arrow_fun(&mut self) -> Result<FunExpr> {
+        let ident = expect_ident!(self);
+        let param = Param {
+            span: self.prev_tok.span,
+            ident,
+            annotation: None,
+            default: None,
+        };
+        let args = Params {
+            span: self.prev_tok.span,
+            params: vec![param],
+            rest: None,
+        };
+        self.arrow_body(args)
+    }
+
+    /// Parses the `=>` and the body of an arrow function after its parameters
+    fn arrow_body(&mut self, args: Params) -> Result<FunExpr> {
+        let start = args.span.start;
+
+        expect!(self, Arrow, "'=>'");
+        let return_type = None;
+        let (block, is_generator) = self.fun_body()?;
+
+        done!(FunExpr, start, self; args, return_type, block, is_generator)
+    }
+
+    /// Parses a parenthesized expression, or the parameters of an arrow function if `=>` follows.
+    /// The items are parsed as expressions first and only turned into parameters after the `)`.
+    fn group(&mut self, pos: Position) -> Result<Atom> {
+        let start = self.curr_tok.span.start;
+
+        expect!(self, LParen, "'('");
+        // the first token which cannot be part of a parenthesized expression
+        let mut invalid = None;
+        let mut items = vec![];
+        while !of_kinds!(self, RParen) {
+            let item_start = self.curr_tok.span.start;
+            let expr = self.expression(Position::Expression)?;
+            let mut annotation = None;
+            let mut default = None;
+            if of_kinds!(self, Colon) {
+                invalid.get_or_insert_with(|| {
+                    error_val!(
+                        SyntaxError,
+                        self.curr_tok.span,
+                        "Expected ')', found '{}'",
+                        self.curr_tok.value(),
+                    )
+                });
+                annotation = self.annotation()?;
+                if of_kinds!(self, Assign) {
+                    self.advance();
+                    default = Some(self.expression(Position::Expression)?);
+                }
+            }
+            let span = Span::new(item_start, self.prev_tok.span.end);
+            items.push((span, expr, annotation, default));
+            if !of_kinds!(self, Comma) {
+                break;
+            }
+            invalid.get_or_insert_with(|| {
+                error_val!(SyntaxError, self.curr_tok.span, "Expected ')', found ','")
+            });
+            self.advance();
+        }
+        if items.is_empty() && of_kinds!(self, RParen) {
+            invalid = Some(error_val!(
+                SyntaxError,
+                self.curr_tok.span,
+                "Expected expression, found ')'",
+            ));
+        }
+        expect!(self, RParen, "')'");
+
+        if pos == Position::Guard || self.curr_tok.kind != TokenKind::Arrow {
+            if let Some(error) = invalid {
+                return Err(Box::new(error));
+            }
+            let (_, expr, ..) = items.swap_remove(0);
+            return Ok(Atom::Expr(expr));
+        }
+        let args = self.group_params(start, items)?;
+        Ok(Atom::FunExpr(self.arrow_body(args)?))
+    }
+
+    /// Turns the items of a parenthesized group into the parameters of an arrow function
+    fn group_params(&mut self, start: Location, items: Vec<GroupItem>) -> Result<Params> {
+        let mut params = vec![];
+        let mut rest = None;
+        let mut names = vec![];
+        for (span, expr, annotation, default) in items {
+            if rest.is_some() {
+                error!(
+                    SyntaxError,
+                    span, "The rest parameter has to be the last parameter",
+                );
+            }
+            let (node, is_rest) = match &expr.base {
+                _ if !expr.stages.is_empty() => {
+                    error!(SyntaxError, span, "Expected parameter, found expression")
+                }
+                RangeExpr::None(node) => (node, false),
+                RangeExpr::OpenStart(TokenKind::Dots, node, _) => (node, true),
+                _ => error!(SyntaxError, span, "Expected parameter, found expression"),
+            };
+            let assign = node.bare_assign();
+            let (ident_span, name, value) =
+                match assign.map(|assign| (assign.bare_target(), &assign.right)) {
+                    Some((Some(Atom::Identifier { span, name }), value)) => (*span, name, value),
+                    _ => error!(SyntaxError, span, "Expected parameter, found expression"),
+                };
+            // a default value without a type annotation was parsed as an assignment
+            let default = match value {
+                None => default,
+                Some((TokenKind::Assign, value)) if annotation.is_none() && default.is_none() => {
+                    Some(value.clone())
+                }
+                Some(_) => error!(SyntaxError, span, "Expected parameter, found expression"),
+            };
+            names.push(ident_span);
+            if !is_rest {
+                let param = Param {
+                    span,
+                    ident: name.clone(),
+                    annotation,
+                    default,
+                };
+                Self::push_param(&mut params, param)?;
+            } else if annotation.is_none() && default.is_none() {
+                rest = Some(name.clone());
+            } else {
+                error!(SyntaxError, span, "Expected parameter, found expression");
+            }
+        }
+        // the names of the parameters are not placeholders of an enclosing pipeline stage
+        if let Some(placeholders) = &mut self.placeholders {
+            placeholders.retain(|span| !names.contains(span));
+        }
 
-        done!(FunExpr, start, self; args, block)
+        done!(Params, start, self; params, rest)
     }
 
     fn class_expr(&mut self) -> Result<ClassExpr> {
         let start = self.curr_tok.span.start;
 
         expect!(self, Class, "'class'");
+        let parent = self.extends()?;
         let block = self.member_block()?;
 
-        done!(ClassExpr, start, self; block)
+        done!(ClassExpr, start, self; parent, block)
+    }
+
+    fn extends(&mut self) -> Result<Option<Box<MemberExpr>>> {
+        if !of_kinds!(self, Extends) {
+            return Ok(None);
+        }
+        self.advance();
+        Ok(Some(Box::new(self.member_expr(Position::Expression)?)))
     }
 
     fn try_expr(&mut self) -> Result<TryExpr> {
@@ -663,13 +1309,372 @@ impl<'i> Parser<'i> {
 
         expect!(self, Try, "'try'");
         let try_block = self.block()?;
+        let mut catches = vec![];
+        while of_kinds!(self, Catch) {
+            catches.push(self.catch_clause()?);
+        }
+        let finally_block = if of_kinds!(self, Finally) {
+            self.advance();
+            Some(self.block()?)
+        } else {
+            None
+        };
+        if catches.is_empty() && finally_block.is_none() {
+            syntax_err!(
+                self,
+                "Expected 'catch' or 'finally', found '{}'",
+                self.curr_tok.value(),
+            );
+        }
+
+        done!(TryExpr, start, self; try_block, catches, finally_block)
+    }
+
+    fn catch_clause(&mut self) -> Result<CatchClause> {
+        let start = self.curr_tok.span.start;
+
         expect!(self, Catch, "'catch'");
         expect!(self, LParen, "'('");
         let ident = expect_ident!(self);
+        let filter = if of_kinds!(self, Colon) {
+            self.advance();
+            Some(self.catch_filter()?)
+        } else {
+            None
+        };
         expect!(self, RParen, "')'");
-        let catch_block = self.block()?;
+        let block = self.block()?;
+
+        done!(CatchClause, start, self; ident, filter, block)
+    }
+
+    fn catch_filter(&mut self) -> Result<CatchFilter> {
+        if of_kinds!(self, Identifier) && self.peek_kind() != TokenKind::Dot {
+            if let Some(kind) = ErrorKind::from_name(self.curr_tok.value()) {
+                self.advance();
+                return Ok(CatchFilter::Kind(kind));
+            }
+        }
+        Ok(CatchFilter::Class(Box::new(
+            self.member_expr(Position::Expression)?,
+        )))
+    }
+
+    fn match_expr(&mut self) -> Result<MatchExpr> {
+        let start = self.curr_tok.span.start;
+
+        expect!(self, Match, "'match'");
+        expect!(self, LParen, "'('");
+        let expr = self.expression(Position::Expression)?;
+        expect!(self, RParen, "')'");
+        expect!(self, LBrace, "'{'");
+        let mut arms = vec![];
+        while !of_kinds!(self, RBrace) {
+            arms.push(self.match_arm()?);
+            if of_kinds!(self, Comma) {
+                self.advance();
+            } else if !of_kinds!(self, RBrace) && self.prev_tok.kind != TokenKind::Eol {
+                syntax_err!(
+                    self,
+                    "Expected ',', line break or '}}', found '{}'",
+                    self.curr_tok.value(),
+                );
+            }
+        }
+        expect!(self, RBrace, "'}'");
+
+        done!(MatchExpr, start, self; expr, arms)
+    }
+
+    fn match_arm(&mut self) -> Result<MatchArm> {
+        let start = self.curr_tok.span.start;
+
+        let pattern = self.pattern()?;
+        let guard = if of_kinds!(self, If) {
+            self.advance();
+            Some(self.expression(Position::Guard)?)
+        } else {
+            None
+        };
+        expect!(self, Arrow, "'=>'");
+        let block = self.block()?;
+
+        done!(MatchArm, start, self; pattern, guard, block)
+    }
+
+    fn pattern(&mut self) -> Result<Pattern> {
+        let start = self.curr_tok.span.start;
+
+        Ok(match self.curr_tok.kind {
+            TokenKind::Identifier => {
+                let name = self.curr_tok.take_value();
+                self.advance();
+                match name.as_str() {
+                    "_" => Pattern::Wildcard,
+                    _ if self.curr_tok.kind == TokenKind::Dot => self.variant_pattern(name)?,
+                    _ => Pattern::Binding(name),
+                }
+            }
+            TokenKind::True => {
+                self.advance();
+                Pattern::Bool(true)
+            }
+            TokenKind::False => {
+                self.advance();
+                Pattern::Bool(false)
+            }
+            TokenKind::String => {
+                let str = self.curr_tok.take_value();
+                self.advance();
+                Pattern::String(str)
+            }
+            TokenKind::Null => {
+                self.advance();
+                Pattern::Null
+            }
+            TokenKind::Minus | TokenKind::Number => {
+                let num = self.signed_number()?;
+                if matches!(
+                    self.curr_tok.kind,
+                    TokenKind::Dots | TokenKind::DotsInclusive
+                ) {
+                    Self::check_range_bound(num, start, self.prev_tok.span.end)?;
+                    let inclusive = self.curr_tok.kind == TokenKind::DotsInclusive;
+                    self.advance();
+                    self.range_pattern(Some(num), inclusive)?
+                } else {
+                    Pattern::Number(num)
+                }
+            }
+            TokenKind::Dots | TokenKind::DotsInclusive => {
+                let inclusive = self.curr_tok.kind == TokenKind::DotsInclusive;
+                self.advance();
+                self.range_pattern(None, inclusive)?
+            }
+            TokenKind::LBrack => self.list_pattern()?,
+            TokenKind::LBrace => self.object_pattern()?,
+            _ => syntax_err!(self, "Expected pattern, found '{}'", self.curr_tok.value()),
+        })
+    }
+
+    fn signed_number(&mut self) -> Result<Decimal> {
+        let negative = self.curr_tok.kind == TokenKind::Minus;
+        if negative {
+            self.advance();
+        }
+        if self.curr_tok.kind != TokenKind::Number {
+            syntax_err!(self, "Expected number, found '{}'", self.curr_tok.value());
+        }
+        let num = self.number()?;
+        Ok(if negative { -num } else { num })
+    }
+
+    /// Parses the rest of a range pattern after the dots
+    fn range_pattern(&mut self, range_start: Option<Decimal>, inclusive: bool) -> Result<Pattern> {
+        let end = if range_start.is_none()
+            || inclusive
+            || matches!(self.curr_tok.kind, TokenKind::Minus | TokenKind::Number)
+        {
+            let start = self.curr_tok.span.start;
+            let end = self.signed_number()?;
+            Self::check_range_bound(end, start, self.prev_tok.span.end)?;
+            Some(end)
+        } else {
+            None
+        };
+        Ok(Pattern::Range {
+            start: range_start,
+            end,
+            inclusive,
+        })
+    }
+
+    /// Range patterns can match any number, but their bounds have to be integers
+    fn check_range_bound(bound: Decimal, start: Location, end: Location) -> Result<()> {
+        if !bound.fract().is_zero() {
+            error!(
+                SyntaxError,
+                (start, end),
+                "Range bounds have to be integers"
+            );
+        }
+        Ok(())
+    }
+
+    fn variant_pattern(&mut self, enum_name: String) -> Result<Pattern> {
+        expect!(self, Dot, "'.'");
+        let variant = expect_ident!(self);
+        let fields = if self.curr_tok.kind == TokenKind::LParen {
+            self.advance();
+            let mut fields = vec![];
+            while !of_kinds!(self, RParen) {
+                fields.push(self.pattern()?);
+                if !of_kinds!(self, Comma) {
+                    break;
+                }
+                self.advance();
+            }
+            expect!(self, RParen, "')'");
+            Some(fields)
+        } else {
+            None
+        };
+
+        Ok(Pattern::Variant {
+            enum_name,
+            variant,
+            fields,
+        })
+    }
+
+    fn list_pattern(&mut self) -> Result<Pattern> {
+        expect!(self, LBrack, "'['");
+        let mut before = vec![];
+        let mut rest = None;
+        let mut after = vec![];
+        while !of_kinds!(self, RBrack) {
+            if of_kinds!(self, Dots) {
+                let start = self.curr_tok.span.start;
+                self.advance();
+                if matches!(self.curr_tok.kind, TokenKind::Minus | TokenKind::Number) {
+                    let pattern = self.range_pattern(None, false)?;
+                    match rest {
+                        Some(_) => after.push(pattern),
+                        None => before.push(pattern),
+                    }
+                } else if rest.is_some() {
+                    error!(
+                        SyntaxError,
+                        (start, self.prev_tok.span.end),
+                        "List patterns can only contain one rest pattern",
+                    );
+                } else if self.curr_tok.kind == TokenKind::Identifier {
+                    rest = Some(Some(self.curr_tok.take_value()));
+                    self.advance();
+                } else {
+                    rest = Some(None);
+                }
+            } else {
+                let pattern = self.pattern()?;
+                match rest {
+                    Some(_) => after.push(pattern),
+                    None => before.push(pattern),
+                }
+            }
+            if !of_kinds!(self, Comma) {
+                break;
+            }
+            self.advance();
+        }
+        expect!(self, RBrack, "']'");
+
+        Ok(Pattern::List {
+            before,
+            rest,
+            after,
+        })
+    }
+
+    fn object_pattern(&mut self) -> Result<Pattern> {
+        expect!(self, LBrace, "'{'");
+        let mut fields = vec![];
+        while !of_kinds!(self, RBrace) {
+            let name = expect_ident!(self);
+            let pattern = if of_kinds!(self, Colon) {
+                self.advance();
+                Some(self.pattern()?)
+            } else {
+                None
+            };
+            fields.push((name, pattern));
+            if !of_kinds!(self, Comma) {
+                break;
+            }
+            self.advance();
+        }
+        expect!(self, RBrace, "'}'");
+
+        Ok(Pattern::Object(fields))
+    }
+
+    fn target(&mut self) -> Result<Target> {
+        match self.curr_tok.kind {
+            TokenKind::LBrack => self.list_target(),
+            TokenKind::LBrace => self.object_target(),
+            _ => Ok(Target::Ident(expect_ident!(self))),
+        }
+    }
+
+    fn list_target(&mut self) -> Result<Target> {
+        let start = self.curr_tok.span.start;
+
+        expect!(self, LBrack, "'['");
+        let mut before = vec![];
+        let mut rest = None;
+        let mut after = vec![];
+        while !of_kinds!(self, RBrack) {
+            if of_kinds!(self, Dots) {
+                let start = self.curr_tok.span.start;
+                self.advance();
+                if rest.is_some() {
+                    error!(
+                        SyntaxError,
+                        (start, self.prev_tok.span.end),
+                        "List targets can only contain one rest target",
+                    );
+                } else if self.curr_tok.kind == TokenKind::Identifier {
+                    rest = Some(Some(self.curr_tok.take_value()));
+                    self.advance();
+                } else {
+                    rest = Some(None);
+                }
+            } else {
+                let target = self.target()?;
+                match rest {
+                    Some(_) => after.push(target),
+                    None => before.push(target),
+                }
+            }
+            if !of_kinds!(self, Comma) {
+                break;
+            }
+            self.advance();
+        }
+        expect!(self, RBrack, "']'");
+
+        Ok(Target::List {
+            span: Span::new(start, self.prev_tok.span.end),
+            before,
+            rest,
+            after,
+        })
+    }
+
+    fn object_target(&mut self) -> Result<Target> {
+        let start = self.curr_tok.span.start;
+
+        expect!(self, LBrace, "'{'");
+        let mut fields = vec![];
+        while !of_kinds!(self, RBrace) {
+            let name = expect_ident!(self);
+            let target = if of_kinds!(self, Colon) {
+                self.advance();
+                Some(self.target()?)
+            } else {
+                None
+            };
+            fields.push((name, target));
+            if !of_kinds!(self, Comma) {
+                break;
+            }
+            self.advance();
+        }
+        expect!(self, RBrace, "'}'");
 
-        done!(TryExpr, start, self; try_block, ident, catch_block)
+        Ok(Target::Object {
+            span: Span::new(start, self.prev_tok.span.end),
+            fields,
+        })
     }
 
     fn block_expr(&mut self) -> Result<BlockExpr> {
@@ -686,16 +1691,20 @@ impl<'i> Parser<'i> {
                 self.advance();
                 MemberPart::Field(expect_ident!(self))
             }
+            TokenKind::QuestionDot => {
+                self.advance();
+                MemberPart::OptionalField(expect_ident!(self))
+            }
             TokenKind::LBrack => {
                 self.advance();
-                let expr = self.expression(false)?;
+                let expr = self.expression(Position::Expression)?;
                 expect!(self, RBrack, "']'");
                 MemberPart::Index(expr)
             }
             _ => error!(
                 SyntaxError,
                 self.curr_tok.span,
-                "Expected '.' or '[', found '{}'",
+                "Expected '.', '?.' or '[', found '{}'",
                 self.curr_tok.value(),
             ),
         })
@@ -710,36 +1719,121 @@ impl<'i> Parser<'i> {
     }
 
     fn args(&mut self) -> Result<Args> {
-        let mut args = vec![];
+        let mut args: Args = vec![];
         expect!(self, LParen, "'('");
-        if !of_kinds!(self, RParen) {
-            args.push(self.expression(false)?);
-            while of_kinds!(self, Comma) {
-                self.advance();
-                if of_kinds!(self, RParen) {
-                    break;
-                }
-                args.push(self.expression(false)?);
+        while !of_kinds!(self, RParen) {
+            let arg = self.arg()?;
+            if arg.name.is_none() && args.last().is_some_and(|arg| arg.name.is_some()) {
+                error!(
+                    SyntaxError,
+                    arg.span, "Positional arguments cannot follow named arguments",
+                );
             }
+            args.push(arg);
+            if !of_kinds!(self, Comma) {
+                break;
+            }
+            self.advance();
         }
         expect!(self, RParen, "')'");
         Ok(args)
     }
 
+    fn arg(&mut self) -> Result<Arg> {
+        let start = self.curr_tok.span.start;
+
+        let name = if self.curr_tok.kind == TokenKind::Identifier
+            && self.peek_kind() == TokenKind::Colon
+        {
+            let name = self.curr_tok.take_value();
+            self.advance();
+            self.advance();
+            Some(name)
+        } else {
+            None
+        };
+        let expr = self.expression(Position::Expression)?;
+
+        done!(Arg, start, self; name, expr)
+    }
+
     fn params(&mut self) -> Result<Params> {
-        let mut args = vec![];
+        let start = self.curr_tok.span.start;
+
+        let mut params: Vec<Param> = vec![];
+        let mut rest = None;
         expect!(self, LParen, "'('");
-        if !of_kinds!(self, RParen) {
-            args.push(expect_ident!(self));
-            while of_kinds!(self, Comma) {
+        while !of_kinds!(self, RParen) {
+            if rest.is_some() {
+                syntax_err!(self, "The rest parameter has to be the last parameter");
+            }
+            if of_kinds!(self, Dots) {
                 self.advance();
-                if of_kinds!(self, RParen) {
-                    break;
-                }
-                args.push(expect_ident!(self));
+                rest = Some(expect_ident!(self));
+            } else {
+                let param = self.param()?;
+                Self::push_param(&mut params, param)?;
+            }
+            if !of_kinds!(self, Comma) {
+                break;
             }
+            self.advance();
         }
         expect!(self, RParen, "')'");
-        Ok(args)
+
+        done!(Params, start, self; params, rest)
+    }
+
+    /// Adds a parameter to a list, in which parameters with a default value have to come last
+    fn push_param(params: &mut Vec<Param>, param: Param) -> Result<()> {
+        if param.default.is_none() && params.last().is_some_and(|param| param.default.is_some()) {
+            error!(
+                SyntaxError,
+                param.span,
+                "Parameters without a default value cannot follow ones with a default value",
+            );
+        }
+        params.push(param);
+        Ok(())
+    }
+
+    fn param(&mut self) -> Result<Param> {
+        let start = self.curr_tok.span.start;
+
+        let ident = expect_ident!(self);
+        let annotation = self.annotation()?;
+        let default = if of_kinds!(self, Assign) {
+            self.advance();
+            Some(self.expression(Position::Expression)?)
+        } else {
+            None
+        };
+
+        done!(Param, start, self; ident, annotation, default)
+    }
+
+    /// Parses an optional type annotation starting with `:`
+    fn annotation(&mut self) -> Result<Option<TypeAnnotation>> {
+        if !of_kinds!(self, Colon) {
+            return Ok(None);
+        }
+        self.advance();
+        let start = self.curr_tok.span.start;
+
+        let mut types = vec![];
+        loop {
+            // some type names are keywords
+            if !of_kinds!(self, Identifier, Null, Class, Enum) {
+                syntax_err!(self, "Expected type, found '{}'", self.curr_tok.value());
+            }
+            types.push(self.curr_tok.take_value());
+            self.advance();
+            if !of_kinds!(self, BitOr) {
+                break;
+            }
+            self.advance();
+        }
+
+        done!(TypeAnnotation, start, self; types).map(Some)
     }
 }
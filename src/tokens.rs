@@ -4,6 +4,7 @@ use std::fmt::Debug;
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum TokenKind {
     Identifier, // name for variable, function or class
+    Label,      // name of a loop, token value includes the leading '@'
 
     LParen, // '('
     RParen, // ')'
@@ -13,10 +14,15 @@ pub enum TokenKind {
     RBrack, // ']'
 
     String,        // string including quotes, token value does not include quotes
+    StringStart,   // double quoted string up to its first '{', token value does not include them
+    StringMiddle,  // string between the '}' and '{' of two interpolated expressions
+    StringEnd,     // string after the '}' of the last interpolated expression
     Number,        // int or float
     Dots,          // '..'
     DotsInclusive, // '..='
 
+    Pipe,               // '|>'
+    Coalesce,           // '??'
     Or,                 // '||'
     And,                // '&&'
     BitOr,              // '|'
@@ -52,12 +58,17 @@ pub enum TokenKind {
     BitOrAssign,      // '|='
     BitAndAssign,     // '&='
     BitXorAssign,     // '^='
+    CoalesceAssign,   // '??='
 
-    Comma, // ','
-    Dot,   // '.'
+    Comma,       // ','
+    Dot,         // '.'
+    QuestionDot, // '?.'
+    Colon,       // ':'
+    Arrow,       // '=>'
 
     // Keywords
     Var,
+    Const,
     True,
     False,
     Null,
@@ -69,12 +80,20 @@ pub enum TokenKind {
     While,
     For,
     Class,
+    Enum,
+    Extends,
+    Super,
     In,
     Return,
+    Yield,
     Break,
     Continue,
     Try,
     Catch,
+    Finally,
+    Match,
+    Import,
+    As,
 
     Unknown,
     Eol,
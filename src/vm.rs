@@ -0,0 +1,672 @@
+mod bytecode;
+mod compiler;
+
+pub use bytecode::{Bytecode, Function};
+pub use compiler::{compile, Unsupported};
+
+use indexmap::IndexMap;
+use rust_decimal::prelude::ToPrimitive;
+
+#[cfg(feature = "no_std_io")]
+use crate::io::Write;
+use crate::{
+    error::{Result, Span},
+    interpreter::{
+        built_in,
+        locks::Locks,
+        thrown::Thrown,
+        value::{
+            indexing::ToMapKey,
+            members::BuiltInMethods,
+            types::{self, Type},
+            BuiltIn, Value, WrappedValue,
+        },
+    },
+};
+#[cfg(not(feature = "no_std_io"))]
+use std::io::Write;
+use std::{cell::RefCell, mem, rc::Rc, vec};
+
+use self::bytecode::{AssignOp, BinaryOp, Constant, Instruction};
+
+pub(crate) type Frame<'tree> = Rc<RefCell<Vec<Option<WrappedValue<'tree>>>>>;
+
+/// The frames a closure was created in. Two closures only share frames if
+/// they share the exact same frames.
+#[derive(Clone, Default)]
+pub struct Frames<'tree>(pub(crate) Vec<Frame<'tree>>);
+
+impl PartialEq for Frames<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.len() == other.0.len()
+            && self
+                .0
+                .iter()
+                .zip(&other.0)
+                .all(|(left, right)| Rc::ptr_eq(left, right))
+    }
+}
+
+fn new_frame<'tree>(slots: usize) -> Frame<'tree> {
+    Rc::new(RefCell::new(vec![None; slots]))
+}
+
+struct Call<'tree> {
+    function: &'tree Function,
+    /// The instruction to continue with in the caller
+    ip: usize,
+    frames: Vec<Frame<'tree>>,
+    stack: usize,
+    loops: usize,
+    iterators: usize,
+    handlers: usize,
+    name: Option<usize>,
+    span: Span,
+}
+
+struct LoopMarker {
+    stack: usize,
+    frames: usize,
+    iterators: usize,
+    handlers: usize,
+}
+
+struct Handler {
+    target: usize,
+    calls: usize,
+    stack: usize,
+    frames: usize,
+    loops: usize,
+    iterators: usize,
+}
+
+enum Iter<'tree> {
+    Range { next: i128, end: Option<i128> },
+    Values(vec::IntoIter<WrappedValue<'tree>>),
+}
+
+impl<'tree> Iterator for Iter<'tree> {
+    type Item = WrappedValue<'tree>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Iter::Range { next, end } => {
+                if end.is_some_and(|end| *next > end) {
+                    return None;
+                }
+                *next += 1;
+                Some(Value::Number((*next - 1).into()).wrapped())
+            }
+            Iter::Values(values) => values.next(),
+        }
+    }
+}
+
+/// Runs [`Bytecode`] produced by [`compile`], with the same results and errors
+/// as the tree walking [`Interpreter`](crate::interpreter::Interpreter)
+pub struct Vm<'tree, StdOut, StdErr, Exit>
+where
+    StdOut: Write,
+    StdErr: Write,
+    Exit: FnOnce(i32),
+{
+    bytecode: &'tree Bytecode,
+    stdout: StdOut,
+    stderr: StdErr,
+    exit_callback: Option<Exit>,
+    built_in_methods: BuiltInMethods<'tree>,
+    built_ins: Vec<WrappedValue<'tree>>,
+    function: &'tree Function,
+    ip: usize,
+    stack: Vec<WrappedValue<'tree>>,
+    frames: Vec<Frame<'tree>>,
+    calls: Vec<Call<'tree>>,
+    loops: Vec<LoopMarker>,
+    iterators: Vec<Iter<'tree>>,
+    handlers: Vec<Handler>,
+    locks: Locks<'tree>,
+    thrown: Thrown<'tree>,
+    /// The number of function calls which can be nested, if limited
+    max_depth: Option<usize>,
+}
+
+impl<'tree, StdOut, StdErr, Exit> Vm<'tree, StdOut, StdErr, Exit>
+where
+    StdOut: Write,
+    StdErr: Write,
+    Exit: FnOnce(i32),
+{
+    pub fn new(
+        bytecode: &'tree Bytecode,
+        stdout: StdOut,
+        stderr: StdErr,
+        exit_callback: Exit,
+    ) -> Self {
+        Self {
+            bytecode,
+            stdout,
+            stderr,
+            exit_callback: Some(exit_callback),
+            built_in_methods: BuiltInMethods::new(),
+            built_ins: built_in::built_ins()
+                .into_iter()
+                .map(|(_, value)| value)
+                .collect(),
+            function: &bytecode.main,
+            ip: 0,
+            stack: vec![],
+            frames: vec![new_frame(bytecode.main.slots)],
+            calls: vec![],
+            loops: vec![],
+            iterators: vec![],
+            handlers: vec![],
+            locks: Locks::default(),
+            thrown: Thrown::default(),
+            max_depth: None,
+        }
+    }
+
+    /// Limits the number of nested function calls, deeper calls fail with a `RuntimeError`
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    pub fn run(&mut self) -> Result<WrappedValue<'tree>> {
+        loop {
+            let mut error = match self.execute() {
+                Ok(value) => return Ok(value),
+                Err(error) => error,
+            };
+            let handler = self.handlers.pop();
+            let calls = handler.as_ref().map_or(0, |handler| handler.calls);
+            while self.calls.len() > calls {
+                let call = self.calls.pop().unwrap();
+                let name = call.name.map(|name| self.bytecode.names[name].as_str());
+                error = error.with_frame(name, call.span);
+                self.function = call.function;
+                self.frames = call.frames;
+            }
+            let handler = match handler {
+                Some(handler) => handler,
+                None => return Err(error),
+            };
+            self.stack.truncate(handler.stack);
+            self.frames.truncate(handler.frames);
+            self.loops.truncate(handler.loops);
+            self.iterators.truncate(handler.iterators);
+            self.stack.push(self.thrown.value(&error));
+            self.thrown.remove(&error);
+            self.ip = handler.target;
+        }
+    }
+
+    fn pop(&mut self) -> WrappedValue<'tree> {
+        self.stack.pop().unwrap()
+    }
+
+    fn push(&mut self, value: Value<'tree>) {
+        self.stack.push(value.wrapped());
+    }
+
+    /// Runs instructions until the program ends or an error occurs
+    fn execute(&mut self) -> Result<WrappedValue<'tree>> {
+        let mut ip = self.ip;
+        loop {
+            let function = self.function;
+            let instruction = function.code[ip];
+            let span = &function.spans[ip];
+            ip += 1;
+            match instruction {
+                Instruction::Constant(idx) => match &self.bytecode.constants[idx] {
+                    Constant::Number(num) => self.push(Value::Number(*num)),
+                    Constant::String(str) => self.push(Value::String(str.clone())),
+                },
+                Instruction::Null => self.push(Value::Null),
+                Instruction::True => self.push(Value::Bool(true)),
+                Instruction::False => self.push(Value::Bool(false)),
+                Instruction::Pop => {
+                    self.stack.pop();
+                }
+                Instruction::Nip => {
+                    let value = self.pop();
+                    *self.stack.last_mut().unwrap() = value;
+                }
+
+                Instruction::Load { frame, slot, name } => {
+                    let value = self.frames[frame].borrow()[slot].clone();
+                    match value {
+                        Some(value) => self.stack.push(value),
+                        None => error!(
+                            ReferenceError,
+                            *span, "Variable with name '{}' not found", self.bytecode.names[name],
+                        ),
+                    }
+                }
+                Instruction::LoadBuiltIn(idx) => self.stack.push(Rc::clone(&self.built_ins[idx])),
+                Instruction::LoadUndefined(name) => error!(
+                    ReferenceError,
+                    *span, "Variable with name '{}' not found", self.bytecode.names[name],
+                ),
+                Instruction::Define { frame, slot } => {
+                    let value = self.pop();
+                    self.frames[frame].borrow_mut()[slot] = Some(value);
+                }
+                Instruction::AssignVar {
+                    frame,
+                    slot,
+                    name,
+                    op,
+                } => {
+                    let right = self.pop();
+                    let left = self.frames[frame].borrow()[slot].clone();
+                    let Some(left) = left else {
+                        error!(
+                            ReferenceError,
+                            *span, "Variable with name '{}' not found", self.bytecode.names[name],
+                        );
+                    };
+                    let out = assign(&left, &right, op, span)?;
+                    self.frames[frame].borrow_mut()[slot] = Some(Rc::clone(&out));
+                    self.stack.push(out);
+                }
+                Instruction::ReassignConst { name, line, column } => error!(
+                    TypeError,
+                    *span,
+                    "Cannot reassign constant '{}' declared at {}:{}",
+                    self.bytecode.names[name],
+                    line,
+                    column,
+                ),
+                Instruction::PushFrame(slots) => self.frames.push(new_frame(slots)),
+                Instruction::PopFrame => {
+                    self.frames.pop();
+                }
+
+                Instruction::Jump(addr) => ip = addr,
+                Instruction::JumpIfFalse(addr) => {
+                    if self.pop().borrow().is_false() {
+                        ip = addr;
+                    }
+                }
+                Instruction::JumpIfTrue(addr) => {
+                    if self.pop().borrow().is_true() {
+                        ip = addr;
+                    }
+                }
+                Instruction::JumpIfNotNull(addr) => {
+                    if matches!(&*self.stack.last().unwrap().borrow(), Value::Null) {
+                        self.pop();
+                    } else {
+                        ip = addr;
+                    }
+                }
+
+                Instruction::Binary(op) => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    let out = binary(op, &left.borrow(), &right.borrow(), span)?;
+                    self.push(out);
+                }
+                Instruction::Equal | Instruction::NotEqual => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    let equal = left == right;
+                    self.push(Value::Bool(equal == (instruction == Instruction::Equal)));
+                }
+                Instruction::Not => {
+                    let value = self.pop();
+                    let out = value.borrow().is_false();
+                    self.push(Value::Bool(out));
+                }
+                Instruction::RangeClosed(inclusive) => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    let range = match (&*left.borrow(), &*right.borrow()) {
+                        (Value::Number(start), Value::Number(end)) => {
+                            if !start.fract().is_zero() || !end.fract().is_zero() {
+                                error!(ValueError, *span, "Range bounds have to be integers");
+                            }
+                            let start = start.to_i128().unwrap();
+                            let end = end.to_i128().unwrap();
+                            Value::Range {
+                                start: Some(start),
+                                end: Some(end - !inclusive as i128),
+                            }
+                        }
+                        _ => error!(TypeError, *span, "Range bounds have to be of type 'number'"),
+                    };
+                    self.push(range);
+                }
+                Instruction::RangeOpenEnd => {
+                    let start = range_bound(&self.pop().borrow(), span)?;
+                    self.push(Value::Range {
+                        start: Some(start),
+                        end: None,
+                    });
+                }
+                Instruction::RangeOpenStart(inclusive) => {
+                    let end = range_bound(&self.pop().borrow(), span)?;
+                    self.push(Value::Range {
+                        start: None,
+                        end: Some(end - !inclusive as i128),
+                    });
+                }
+                Instruction::RangeOpen => self.push(Value::Range {
+                    start: None,
+                    end: None,
+                }),
+                Instruction::List(len) => {
+                    let values = self.stack.split_off(self.stack.len() - len);
+                    self.push(Value::List(values));
+                }
+                Instruction::Concat(len) => {
+                    let values = self.stack.split_off(self.stack.len() - len);
+                    let string = values
+                        .iter()
+                        .map(|value| value.borrow().to_string())
+                        .collect();
+                    self.push(Value::String(string));
+                }
+                Instruction::Map => self.push(Value::Map(IndexMap::new())),
+                Instruction::MapInsert => {
+                    let value = self.pop();
+                    let key = self.pop().borrow().to_map_key(span)?;
+                    self.stack
+                        .last()
+                        .unwrap()
+                        .borrow_mut()
+                        .unwrap_map_mut()
+                        .insert(key, value);
+                }
+
+                Instruction::Field(name) => {
+                    let base = self.pop();
+                    let out = Value::get_field(
+                        &base,
+                        &self.bytecode.names[name],
+                        &self.built_in_methods,
+                        span,
+                    )?;
+                    *self.stack.last_mut().unwrap() = base;
+                    self.stack.push(out);
+                }
+                Instruction::Index => {
+                    let index = self.pop();
+                    let base = self.pop();
+                    let out = Value::index(&base, &index, span)?;
+                    *self.stack.last_mut().unwrap() = base;
+                    self.stack.push(out);
+                }
+                Instruction::Call { args, callee } => {
+                    let args = self.stack.split_off(self.stack.len() - args);
+                    let value = self.pop();
+                    let parent = mem::replace(self.stack.last_mut().unwrap(), Rc::clone(&value));
+                    let closure = match &*value.borrow() {
+                        Value::Closure { function, frames } => Some((*function, frames.clone())),
+                        _ => None,
+                    };
+                    match closure {
+                        Some((function, frames)) => {
+                            if self.max_depth == Some(self.calls.len()) {
+                                error!(
+                                    RuntimeError,
+                                    *span,
+                                    "Maximum call depth of {} exceeded",
+                                    self.calls.len(),
+                                );
+                            }
+                            if function.params != args.len() {
+                                error!(
+                                    TypeError,
+                                    *span,
+                                    "Function takes {} arguments {}, however {} were supplied",
+                                    function.params,
+                                    function.signature,
+                                    args.len(),
+                                );
+                            }
+                            let mut frames = frames.0;
+                            let frame = new_frame(function.slots);
+                            for (slot, arg) in args.into_iter().enumerate() {
+                                frame.borrow_mut()[slot] = Some(arg);
+                            }
+                            frames.push(frame);
+                            self.calls.push(Call {
+                                function: self.function,
+                                ip,
+                                frames: mem::replace(&mut self.frames, frames),
+                                stack: self.stack.len(),
+                                loops: self.loops.len(),
+                                iterators: self.iterators.len(),
+                                handlers: self.handlers.len(),
+                                name: callee,
+                                span: *span,
+                            });
+                            self.function = function;
+                            ip = 0;
+                        }
+                        None => {
+                            let out = self.call_built_in(&value, args, &parent, span)?;
+                            self.stack.push(out);
+                        }
+                    }
+                }
+                Instruction::AssignIndex(op) => {
+                    let right = self.pop();
+                    let index = self.pop();
+                    let base = self.pop();
+                    // assigning to a key which is not yet present in a map inserts it
+                    let new_key = match &*base.borrow() {
+                        Value::Map(map) if op == AssignOp::Assign => {
+                            !map.contains_key(&index.borrow().to_map_key(span)?)
+                        }
+                        _ => false,
+                    };
+                    let left = if new_key {
+                        Value::Null.wrapped()
+                    } else {
+                        Value::index(&base, &index, span)?
+                    };
+                    let out = assign(&left, &right, op, span)?;
+                    self.locks.check_mutate(&base, span)?;
+                    Value::set_index(&base, &index, Rc::clone(&out), span)?;
+                    self.stack.push(out);
+                }
+                Instruction::AssignField { name, op } => {
+                    let right = self.pop();
+                    let base = self.pop();
+                    let name = &self.bytecode.names[name];
+                    let left = Value::get_field(&base, name, &self.built_in_methods, span)?;
+                    let out = assign(&left, &right, op, span)?;
+                    self.locks.check_mutate(&base, span)?;
+                    Value::set_field(&base, name, Rc::clone(&out), span)?;
+                    self.stack.push(out);
+                }
+                Instruction::Closure(idx) => self.push(Value::Closure {
+                    function: &self.bytecode.functions[idx],
+                    frames: Frames(self.frames.clone()),
+                }),
+                Instruction::Return => {
+                    let value = self.pop();
+                    let call = match self.calls.pop() {
+                        Some(call) => call,
+                        None => return Ok(value),
+                    };
+                    self.stack.truncate(call.stack);
+                    self.stack.push(value);
+                    self.loops.truncate(call.loops);
+                    self.iterators.truncate(call.iterators);
+                    self.handlers.truncate(call.handlers);
+                    self.frames = call.frames;
+                    self.function = call.function;
+                    ip = call.ip;
+                }
+
+                Instruction::LoopStart => self.loops.push(LoopMarker {
+                    stack: self.stack.len(),
+                    frames: self.frames.len(),
+                    iterators: self.iterators.len(),
+                    handlers: self.handlers.len(),
+                }),
+                Instruction::LoopEnd => {
+                    self.loops.pop();
+                }
+                Instruction::Break(addr) => {
+                    let value = self.pop();
+                    self.restore_loop();
+                    self.stack.push(value);
+                    ip = addr;
+                }
+                Instruction::Continue(addr) => {
+                    self.restore_loop();
+                    ip = addr;
+                }
+                Instruction::IterStart => {
+                    let value = self.pop();
+                    let iter = match &*value.borrow() {
+                        Value::Range {
+                            start: Some(start),
+                            end,
+                        } => Iter::Range {
+                            next: *start,
+                            end: *end,
+                        },
+                        value => Iter::Values(value.to_iter(span)?.collect::<Vec<_>>().into_iter()),
+                    };
+                    self.iterators.push(iter);
+                }
+                Instruction::IterNext(addr) => match self.iterators.last_mut().unwrap().next() {
+                    Some(item) => self.stack.push(item),
+                    None => ip = addr,
+                },
+                Instruction::IterEnd => {
+                    self.iterators.pop();
+                }
+                Instruction::TryStart(target) => self.handlers.push(Handler {
+                    target,
+                    calls: self.calls.len(),
+                    stack: self.stack.len(),
+                    frames: self.frames.len(),
+                    loops: self.loops.len(),
+                    iterators: self.iterators.len(),
+                }),
+                Instruction::TryEnd => {
+                    self.handlers.pop();
+                }
+            }
+        }
+    }
+
+    /// Restores the state at the start of the current loop for `break` and `continue`
+    fn restore_loop(&mut self) {
+        let marker = self.loops.last().unwrap();
+        self.stack.truncate(marker.stack);
+        self.frames.truncate(marker.frames);
+        self.iterators.truncate(marker.iterators);
+        self.handlers.truncate(marker.handlers);
+    }
+
+    fn call_built_in(
+        &mut self,
+        value: &WrappedValue<'tree>,
+        args: Vec<WrappedValue<'tree>>,
+        parent: &WrappedValue<'tree>,
+        span: &Span,
+    ) -> Result<WrappedValue<'tree>> {
+        if self.built_in_methods.mutates(value) {
+            self.locks.check_mutate(parent, span)?;
+        }
+        match &*value.borrow() {
+            Value::BuiltIn(BuiltIn::Function(func)) => func(args, span),
+            Value::BuiltIn(BuiltIn::Method(func)) => func(parent, args, span),
+            Value::BuiltIn(BuiltIn::Print {
+                newline,
+                stderr: false,
+            }) => built_in::print(args, &mut self.stdout, span, *newline),
+            Value::BuiltIn(BuiltIn::Print {
+                newline,
+                stderr: true,
+            }) => built_in::print(args, &mut self.stderr, span, *newline),
+            Value::BuiltIn(BuiltIn::Exit) => {
+                built_in::exit(args, self.exit_callback.take().unwrap(), span)
+            }
+            Value::BuiltIn(BuiltIn::Debug) => built_in::debug(args, &mut self.stderr, span),
+            Value::BuiltIn(BuiltIn::Freeze) => built_in::freeze(args, &mut self.locks, span),
+            Value::BuiltIn(BuiltIn::Throw) => {
+                built_in::throw(args, &mut self.thrown, &self.locks, span)
+            }
+            value => error!(
+                TypeError,
+                *span,
+                "Type '{}' is not callable",
+                types::type_of(value),
+            ),
+        }
+    }
+}
+
+fn binary<'tree>(
+    op: BinaryOp,
+    left: &Value<'tree>,
+    right: &Value<'tree>,
+    span: &Span,
+) -> Result<Value<'tree>> {
+    match op {
+        BinaryOp::Or => left.or(right, span),
+        BinaryOp::Xor => left.xor(right, span),
+        BinaryOp::And => left.and(right, span),
+        BinaryOp::LessThan => left.lt(right, span),
+        BinaryOp::LessThanOrEqual => left.le(right, span),
+        BinaryOp::GreaterThan => left.gt(right, span),
+        BinaryOp::GreaterThanOrEqual => left.ge(right, span),
+        BinaryOp::ShiftLeft => left.shl(right, span),
+        BinaryOp::ShiftRight => left.shr(right, span),
+        BinaryOp::Add => left.add(right, span),
+        BinaryOp::Sub => left.sub(right, span),
+        BinaryOp::Mul => left.mul(right, span),
+        BinaryOp::Div => left.div(right, span),
+        BinaryOp::Rem => left.rem(right, span),
+        BinaryOp::DivFloor => left.div_floor(right, span),
+        BinaryOp::Pow => left.pow(right, span),
+    }
+}
+
+/// Returns the value which replaces `left` when it is assigned to
+fn assign<'tree>(
+    left: &WrappedValue<'tree>,
+    right: &WrappedValue<'tree>,
+    op: AssignOp,
+    span: &Span,
+) -> Result<WrappedValue<'tree>> {
+    let left_type = types::type_of(&left.borrow());
+    if matches!(left_type, Type::Class | Type::Range)
+        || (left_type == Type::Object && op == AssignOp::Assign)
+    {
+        error!(TypeError, *span, "Cannot reassign type '{}'", left_type,);
+    }
+    let new_value = match op {
+        AssignOp::Assign => right.borrow().clone(),
+        AssignOp::Binary(op) => binary(op, &left.borrow(), &right.borrow(), span)?,
+    };
+    let new_type = types::type_of(&new_value);
+    if left_type != new_type && left_type != Type::Null && new_type != Type::Null {
+        error!(
+            TypeError,
+            *span, "Cannot change type by reassigning, create a new variable instead",
+        );
+    }
+    Ok(new_value.wrapped())
+}
+
+fn range_bound(value: &Value, span: &Span) -> Result<i128> {
+    match value {
+        Value::Number(num) => {
+            if !num.fract().is_zero() {
+                error!(ValueError, *span, "Range bounds have to be integers");
+            }
+            Ok(num.to_i128().unwrap())
+        }
+        _ => error!(TypeError, *span, "Range bounds have to be of type 'number'"),
+    }
+}
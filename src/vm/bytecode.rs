@@ -0,0 +1,180 @@
+use std::{fmt::Debug, ptr};
+
+use rust_decimal::Decimal;
+
+use crate::error::Span;
+
+/// A compiled program which can be run by the [`Vm`](super::Vm)
+#[derive(Debug)]
+pub struct Bytecode {
+    pub(super) main: Function,
+    pub(super) functions: Vec<Function>,
+    pub(super) constants: Vec<Constant>,
+    pub(super) names: Vec<String>,
+}
+
+pub struct Function {
+    pub(super) params: usize,
+    /// The parameters formatted for arity errors
+    pub(super) signature: String,
+    /// Number of slots in the frame created for each call, including the parameters
+    pub(super) slots: usize,
+    pub(super) code: Vec<Instruction>,
+    /// The span of the node each instruction was compiled from
+    pub(super) spans: Vec<Span>,
+}
+
+impl Debug for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "<function> params: {}, slots: {}",
+            self.params, self.slots
+        )?;
+        for (idx, instruction) in self.code.iter().enumerate() {
+            writeln!(f, "{idx: >5}  {instruction:?}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Functions are only equal to themselves
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        ptr::eq(self, other)
+    }
+}
+
+#[derive(Debug)]
+pub enum Constant {
+    Number(Decimal),
+    String(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// Pushes a copy of a constant
+    Constant(usize),
+    Null,
+    True,
+    False,
+    Pop,
+    /// Removes the value below the top of the stack
+    Nip,
+
+    /// Pushes the variable at `slot` of the frame at index `frame`
+    Load {
+        frame: usize,
+        slot: usize,
+        name: usize,
+    },
+    LoadBuiltIn(usize),
+    /// Fails with a reference error, the variable is not declared anywhere
+    LoadUndefined(usize),
+    /// Pops a value and binds it to `slot` of the frame at index `frame`
+    Define {
+        frame: usize,
+        slot: usize,
+    },
+    /// Pops the new value and assigns it to `slot` of the frame at index `frame`,
+    /// pushes the value stored in the slot
+    AssignVar {
+        frame: usize,
+        slot: usize,
+        name: usize,
+        op: AssignOp,
+    },
+    /// Fails with a type error, the named constant declared at `line` and `column` is reassigned
+    ReassignConst {
+        name: usize,
+        line: usize,
+        column: usize,
+    },
+    PushFrame(usize),
+    PopFrame,
+
+    Jump(usize),
+    /// Pops a value and jumps if it is false
+    JumpIfFalse(usize),
+    /// Pops a value and jumps if it is true
+    JumpIfTrue(usize),
+    /// Jumps if the value on top of the stack is not null and pops it otherwise
+    JumpIfNotNull(usize),
+
+    Binary(BinaryOp),
+    Equal,
+    NotEqual,
+    Not,
+    /// Builds a range, the bool marks whether the end is inclusive
+    RangeClosed(bool),
+    RangeOpenEnd,
+    RangeOpenStart(bool),
+    RangeOpen,
+    List(usize),
+    /// Pops the given number of values and pushes their displayed forms joined into a string
+    Concat(usize),
+    Map,
+    /// Pops a key and a value and inserts them into the map below
+    MapInsert,
+
+    /// Replaces the parent and the base on the stack with the base and its field
+    Field(usize),
+    /// Replaces the parent, the base and the index on the stack with the base and its item
+    Index,
+    /// Replaces the parent, the base and the arguments on the stack with the base and the
+    /// result of calling it, `callee` is the name used for stack traces
+    Call {
+        args: usize,
+        callee: Option<usize>,
+    },
+    /// Pops the new value, the index and the base, replaces the indexed item and pushes it
+    AssignIndex(AssignOp),
+    /// Pops the new value and the base, replaces the field of the base and pushes it
+    AssignField {
+        name: usize,
+        op: AssignOp,
+    },
+    Closure(usize),
+    Return,
+
+    /// Marks the start of a loop, `break` and `continue` restore the state at this point
+    LoopStart,
+    LoopEnd,
+    /// Pops a break value and jumps to the end of the current loop
+    Break(usize),
+    Continue(usize),
+    /// Pops a value and starts iterating over it
+    IterStart,
+    /// Pushes the next item of the current iterator or jumps if it is exhausted
+    IterNext(usize),
+    IterEnd,
+    /// Registers an error handler starting at the given instruction
+    TryStart(usize),
+    TryEnd,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Or,
+    Xor,
+    And,
+    LessThan,
+    LessThanOrEqual,
+    GreaterThan,
+    GreaterThanOrEqual,
+    ShiftLeft,
+    ShiftRight,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    DivFloor,
+    Pow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssignOp {
+    Assign,
+    Binary(BinaryOp),
+}
@@ -0,0 +1,993 @@
+use std::{
+    collections::{HashMap, HashSet},
+    slice,
+};
+
+use crate::{error::Span, interpreter::built_in, nodes::*, tokens::TokenKind};
+
+use super::bytecode::{AssignOp, BinaryOp, Bytecode, Constant, Function, Instruction};
+
+/// A construct the compiler cannot translate to bytecode, programs using it
+/// have to be run by the tree walking [`Interpreter`](crate::interpreter::Interpreter)
+#[derive(Debug, Clone)]
+pub struct Unsupported {
+    pub feature: &'static str,
+    pub span: Span,
+}
+
+type Result<T> = std::result::Result<T, Unsupported>;
+
+/// Compiles a program for the [`Vm`](super::Vm).
+///
+/// Supported are variables and constants, functions and closures, arrow functions,
+/// all operators including `??`, ranges, lists, maps, string interpolation, `if`,
+/// labeled and unlabeled loops with `break` and `continue`, `try` with a single untyped
+/// `catch` clause, and the built-in functions and methods. Classes, enums, generators,
+/// imports, `match`, destructuring, default, rest and named parameters, optional chaining,
+/// `??=`, typed or multiple `catch` clauses, `finally`, list comprehensions and pipelines
+/// are reported as [`Unsupported`].
+pub fn compile(program: &Program) -> Result<Bytecode> {
+    let mut compiler = Compiler {
+        functions: vec![],
+        constants: vec![],
+        names: vec![],
+        name_indices: HashMap::new(),
+        built_ins: built_in::built_ins().map(|(name, _)| name).to_vec(),
+        contexts: vec![],
+        depth: 0,
+    };
+    let main = compiler.function(&[], String::new(), program)?;
+    Ok(Bytecode {
+        main,
+        functions: compiler.functions,
+        constants: compiler.constants,
+        names: compiler.names,
+    })
+}
+
+struct Compiler {
+    functions: Vec<Function>,
+    constants: Vec<Constant>,
+    names: Vec<String>,
+    name_indices: HashMap<String, usize>,
+    built_ins: Vec<&'static str>,
+    /// The functions currently being compiled, innermost last
+    contexts: Vec<Context>,
+    /// The number of frames which exist at runtime at the current point
+    depth: usize,
+}
+
+#[derive(Default)]
+struct Context {
+    code: Vec<Instruction>,
+    spans: Vec<Span>,
+    scopes: Vec<BlockScope>,
+    loops: Vec<Loop>,
+}
+
+/// A block which declares variables and therefore gets its own frame
+struct BlockScope {
+    frame: usize,
+    slots: HashMap<String, usize>,
+    /// The spans of the declarations of the constants
+    constants: HashMap<String, Span>,
+    /// The variables declared so far, later ones are not yet visible in the same function
+    declared: HashSet<String>,
+}
+
+struct Loop {
+    label: Option<String>,
+    continue_target: usize,
+    breaks: Vec<usize>,
+}
+
+macro_rules! unsupported {
+    ($feature:literal, $span:expr) => {
+        return Err(Unsupported {
+            feature: $feature,
+            span: $span,
+        })
+    };
+}
+
+impl Compiler {
+    fn context(&mut self) -> &mut Context {
+        self.contexts.last_mut().unwrap()
+    }
+
+    fn emit(&mut self, instruction: Instruction, span: Span) -> usize {
+        let context = self.context();
+        context.code.push(instruction);
+        context.spans.push(span);
+        context.code.len() - 1
+    }
+
+    fn here(&mut self) -> usize {
+        self.context().code.len()
+    }
+
+    /// Points the jump at `idx` to the next instruction
+    fn patch(&mut self, idx: usize) {
+        let target = self.here();
+        match &mut self.context().code[idx] {
+            Instruction::Jump(addr)
+            | Instruction::JumpIfFalse(addr)
+            | Instruction::JumpIfTrue(addr)
+            | Instruction::JumpIfNotNull(addr)
+            | Instruction::Break(addr)
+            | Instruction::IterNext(addr)
+            | Instruction::TryStart(addr) => *addr = target,
+            instruction => unreachable!("cannot patch {instruction:?}"),
+        }
+    }
+
+    fn constant(&mut self, constant: Constant) -> usize {
+        self.constants.push(constant);
+        self.constants.len() - 1
+    }
+
+    fn name(&mut self, name: &str) -> usize {
+        if let Some(idx) = self.name_indices.get(name) {
+            return *idx;
+        }
+        self.names.push(name.to_string());
+        self.name_indices
+            .insert(name.to_string(), self.names.len() - 1);
+        self.names.len() - 1
+    }
+
+    /// Assigns a slot to every name in `bound` and every variable declared
+    /// directly inside of `block`
+    fn slots(bound: &[String], block: &Block) -> HashMap<String, usize> {
+        let mut slots = HashMap::new();
+        for (slot, name) in bound.iter().enumerate() {
+            slots.insert(name.clone(), slot);
+        }
+        let mut count = bound.len();
+        for stmt in block {
+            let name = match stmt {
+                Statement::Var(VarStmt {
+                    target: Target::Ident(name),
+                    ..
+                }) => name,
+                Statement::Function(node) => &node.ident,
+                _ => continue,
+            };
+            slots.entry(name.clone()).or_insert_with(|| {
+                count += 1;
+                count - 1
+            });
+        }
+        slots
+    }
+
+    /// Finds the constants declared directly inside of `block`, a later declaration
+    /// of the same name which is not constant makes it a variable again
+    fn constants(block: &Block) -> HashMap<String, Span> {
+        let mut constants = HashMap::new();
+        for stmt in block {
+            match stmt {
+                Statement::Var(VarStmt {
+                    target: Target::Ident(name),
+                    is_const: true,
+                    span,
+                    ..
+                }) => {
+                    constants.insert(name.clone(), *span);
+                }
+                Statement::Var(VarStmt {
+                    target: Target::Ident(name),
+                    ..
+                })
+                | Statement::Function(FunctionDecl { ident: name, .. }) => {
+                    constants.remove(name);
+                }
+                _ => {}
+            }
+        }
+        constants
+    }
+
+    fn enter(
+        &mut self,
+        slots: HashMap<String, usize>,
+        block: &Block,
+        declared: &[String],
+    ) -> usize {
+        let frame = self.depth;
+        self.depth += 1;
+        self.context().scopes.push(BlockScope {
+            frame,
+            slots,
+            constants: Self::constants(block),
+            declared: declared.iter().cloned().collect(),
+        });
+        frame
+    }
+
+    fn leave(&mut self) {
+        self.depth -= 1;
+        self.context().scopes.pop();
+    }
+
+    /// Binds the value on top of the stack to `name` in the innermost block
+    fn define(&mut self, name: &str) {
+        let scope = self.context().scopes.last_mut().unwrap();
+        let (frame, slot) = (scope.frame, scope.slots[name]);
+        scope.declared.insert(name.to_string());
+        self.emit(Instruction::Define { frame, slot }, Span::default());
+    }
+
+    /// Finds the frame and slot of a variable and the span of its declaration if it is constant
+    fn lookup(&self, name: &str) -> Option<(usize, usize, Option<Span>)> {
+        let current = self.contexts.len() - 1;
+        self.contexts
+            .iter()
+            .enumerate()
+            .rev()
+            .flat_map(|(idx, context)| context.scopes.iter().rev().map(move |s| (idx, s)))
+            .find_map(|(idx, scope)| match scope.slots.get(name) {
+                // functions are called after the enclosing blocks declared their variables
+                Some(slot) if idx != current || scope.declared.contains(name) => {
+                    Some((scope.frame, *slot, scope.constants.get(name).copied()))
+                }
+                _ => None,
+            })
+    }
+
+    fn resolve(&mut self, name: &str) -> Instruction {
+        match self.lookup(name) {
+            Some((frame, slot, _)) => Instruction::Load {
+                frame,
+                slot,
+                name: self.name(name),
+            },
+            None => match self.built_ins.iter().position(|built_in| *built_in == name) {
+                Some(idx) => Instruction::LoadBuiltIn(idx),
+                None => Instruction::LoadUndefined(self.name(name)),
+            },
+        }
+    }
+
+    fn function(
+        &mut self,
+        params: &[String],
+        signature: String,
+        block: &Block,
+    ) -> Result<Function> {
+        let slots = Self::slots(params, block);
+        let slot_count = slots.values().max().map_or(0, |max| max + 1);
+        self.contexts.push(Context::default());
+        self.enter(slots, block, params);
+        let res = self.statements(block);
+        self.emit(Instruction::Return, Span::default());
+        self.leave();
+        let context = self.contexts.pop().unwrap();
+        res?;
+        Ok(Function {
+            params: params.len(),
+            signature,
+            slots: slot_count.max(params.len()),
+            code: context.code,
+            spans: context.spans,
+        })
+    }
+
+    fn closure(&mut self, params: &Params, block: &Block, span: Span) -> Result<()> {
+        if params.rest.is_some() {
+            unsupported!("rest parameters", params.span);
+        }
+        if let Some(param) = params.params.iter().find(|param| param.default.is_some()) {
+            unsupported!("default parameters", param.span);
+        }
+        let names: Vec<_> = params
+            .params
+            .iter()
+            .map(|param| param.ident.clone())
+            .collect();
+        let function = self.function(&names, params.to_string(), block)?;
+        self.functions.push(function);
+        let idx = self.functions.len() - 1;
+        self.emit(Instruction::Closure(idx), span);
+        Ok(())
+    }
+
+    /// Compiles a block which leaves its value on the stack, `bound` names
+    /// the values on the stack which are bound to variables of the block
+    fn block(&mut self, block: &Block, bound: &[String]) -> Result<()> {
+        let slots = Self::slots(bound, block);
+        if slots.is_empty() {
+            return self.statements(block);
+        }
+        let slot_count = slots.values().max().unwrap() + 1;
+        self.emit(Instruction::PushFrame(slot_count), Span::default());
+        self.enter(slots, block, &[]);
+        for name in bound.iter().rev() {
+            self.define(name);
+        }
+        let res = self.statements(block);
+        self.leave();
+        res?;
+        self.emit(Instruction::PopFrame, Span::default());
+        Ok(())
+    }
+
+    ////////////////////////////////////////
+
+    fn statements(&mut self, node: &Statements) -> Result<()> {
+        if node.is_empty() {
+            self.emit(Instruction::Null, Span::default());
+        }
+        for (idx, stmt) in node.iter().enumerate() {
+            let pushed = self.statement(stmt)?;
+            match (pushed, idx == node.len() - 1) {
+                (true, false) => {
+                    self.emit(Instruction::Pop, Span::default());
+                }
+                (false, true) => {
+                    self.emit(Instruction::Null, Span::default());
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Compiles a statement and returns whether it left a value on the stack
+    fn statement(&mut self, node: &Statement) -> Result<bool> {
+        match node {
+            Statement::Var(node) => {
+                let Target::Ident(ident) = &node.target else {
+                    unsupported!("destructuring", node.span)
+                };
+                self.optional_expression(&node.expr)?;
+                self.define(ident);
+            }
+            Statement::Function(node) => {
+                if node.is_generator {
+                    unsupported!("generators", node.span);
+                }
+                self.closure(&node.args, &node.block, node.span)?;
+                self.define(&node.ident);
+            }
+            Statement::Class(node) => unsupported!("classes", node.span),
+            Statement::Enum(node) => unsupported!("enums", node.span),
+            Statement::Break(node) => {
+                self.optional_expression(&node.expr)?;
+                match self.leave_loops(&node.label, node.span) {
+                    Some(target) => {
+                        let idx = self.emit(Instruction::Break(0), node.span);
+                        self.context().loops[target].breaks.push(idx);
+                    }
+                    // outside of loops `break` leaves the function with null
+                    None => {
+                        self.emit(Instruction::Pop, node.span);
+                        self.emit(Instruction::Null, node.span);
+                        self.emit(Instruction::Return, node.span);
+                    }
+                }
+            }
+            Statement::Continue(node) => match self.leave_loops(&node.label, node.span) {
+                Some(target) => {
+                    let target = self.context().loops[target].continue_target;
+                    self.emit(Instruction::Continue(target), node.span);
+                }
+                None => {
+                    self.emit(Instruction::Null, node.span);
+                    self.emit(Instruction::Return, node.span);
+                }
+            },
+            Statement::Return(node) => {
+                self.optional_expression(&node.expr)?;
+                self.emit(Instruction::Return, node.span);
+            }
+            Statement::Yield(node) => unsupported!("generators", node.span),
+            Statement::Import(node) => unsupported!("imports", node.span),
+            Statement::Expr(node) => {
+                self.expression(node)?;
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn optional_expression(&mut self, node: &Option<Expression>) -> Result<()> {
+        match node {
+            Some(node) => self.expression(node),
+            None => {
+                self.emit(Instruction::Null, Span::default());
+                Ok(())
+            }
+        }
+    }
+
+    fn expression(&mut self, node: &Expression) -> Result<()> {
+        if !node.stages.is_empty() {
+            unsupported!("pipelines", node.span);
+        }
+        self.range_expr(&node.base)
+    }
+
+    fn range_expr(&mut self, node: &RangeExpr) -> Result<()> {
+        match node {
+            RangeExpr::None(node) => self.coalesce_expr(node)?,
+            RangeExpr::Closed(left, tok, right, span) => {
+                self.coalesce_expr(left)?;
+                self.coalesce_expr(right)?;
+                let inclusive = tok == &TokenKind::DotsInclusive;
+                self.emit(Instruction::RangeClosed(inclusive), *span);
+            }
+            RangeExpr::OpenEnd(left, span) => {
+                self.coalesce_expr(left)?;
+                self.emit(Instruction::RangeOpenEnd, *span);
+            }
+            RangeExpr::OpenStart(tok, right, span) => {
+                self.coalesce_expr(right)?;
+                let inclusive = tok == &TokenKind::DotsInclusive;
+                self.emit(Instruction::RangeOpenStart(inclusive), *span);
+            }
+            RangeExpr::Open => {
+                self.emit(Instruction::RangeOpen, Span::default());
+            }
+        }
+        Ok(())
+    }
+
+    fn coalesce_expr(&mut self, node: &CoalesceExpr) -> Result<()> {
+        self.or_expr(&node.base)?;
+        let mut jumps = vec![];
+        for other in &node.following {
+            jumps.push(self.emit(Instruction::JumpIfNotNull(0), node.span));
+            self.or_expr(other)?;
+        }
+        for jump in jumps {
+            self.patch(jump);
+        }
+        Ok(())
+    }
+
+    fn or_expr(&mut self, node: &OrExpr) -> Result<()> {
+        self.and_expr(&node.base)?;
+        if node.following.is_empty() {
+            return Ok(());
+        }
+        let mut jumps = vec![self.emit(Instruction::JumpIfFalse(0), node.span)];
+        for other in &node.following {
+            self.and_expr(other)?;
+            jumps.push(self.emit(Instruction::JumpIfFalse(0), node.span));
+        }
+        self.short_circuit(jumps, Instruction::True, Instruction::False, node.span);
+        Ok(())
+    }
+
+    fn and_expr(&mut self, node: &AndExpr) -> Result<()> {
+        self.bit_or_expr(&node.base)?;
+        if node.following.is_empty() {
+            return Ok(());
+        }
+        let mut jumps = vec![self.emit(Instruction::JumpIfTrue(0), node.span)];
+        for other in &node.following {
+            self.bit_or_expr(other)?;
+            jumps.push(self.emit(Instruction::JumpIfTrue(0), node.span));
+        }
+        self.short_circuit(jumps, Instruction::False, Instruction::True, node.span);
+        Ok(())
+    }
+
+    /// Pushes `done` if none of the `jumps` was taken and `jumped` otherwise
+    fn short_circuit(
+        &mut self,
+        jumps: Vec<usize>,
+        done: Instruction,
+        jumped: Instruction,
+        span: Span,
+    ) {
+        self.emit(done, span);
+        let end = self.emit(Instruction::Jump(0), span);
+        for jump in jumps {
+            self.patch(jump);
+        }
+        self.emit(jumped, span);
+        self.patch(end);
+    }
+
+    fn bit_or_expr(&mut self, node: &BitOrExpr) -> Result<()> {
+        self.bit_xor_expr(&node.base)?;
+        for other in &node.following {
+            self.bit_xor_expr(other)?;
+            self.emit(Instruction::Binary(BinaryOp::Or), node.span);
+        }
+        Ok(())
+    }
+
+    fn bit_xor_expr(&mut self, node: &BitXorExpr) -> Result<()> {
+        self.bit_and_expr(&node.base)?;
+        for other in &node.following {
+            self.bit_and_expr(other)?;
+            self.emit(Instruction::Binary(BinaryOp::Xor), node.span);
+        }
+        Ok(())
+    }
+
+    fn bit_and_expr(&mut self, node: &BitAndExpr) -> Result<()> {
+        self.eq_expr(&node.base)?;
+        for other in &node.following {
+            self.eq_expr(other)?;
+            self.emit(Instruction::Binary(BinaryOp::And), node.span);
+        }
+        Ok(())
+    }
+
+    fn eq_expr(&mut self, node: &EqExpr) -> Result<()> {
+        self.rel_expr(&node.left)?;
+        if let Some((tok, right)) = &node.right {
+            self.rel_expr(right)?;
+            let instruction = match tok {
+                TokenKind::Equal => Instruction::Equal,
+                TokenKind::NotEqual => Instruction::NotEqual,
+                _ => unreachable!(),
+            };
+            self.emit(instruction, node.span);
+        }
+        Ok(())
+    }
+
+    fn rel_expr(&mut self, node: &RelExpr) -> Result<()> {
+        self.shift_expr(&node.left)?;
+        if let Some((tok, right)) = &node.right {
+            self.shift_expr(right)?;
+            let op = match tok {
+                TokenKind::LessThan => BinaryOp::LessThan,
+                TokenKind::LessThanOrEqual => BinaryOp::LessThanOrEqual,
+                TokenKind::GreaterThan => BinaryOp::GreaterThan,
+                TokenKind::GreaterThanOrEqual => BinaryOp::GreaterThanOrEqual,
+                _ => unreachable!(),
+            };
+            self.emit(Instruction::Binary(op), node.span);
+        }
+        Ok(())
+    }
+
+    fn shift_expr(&mut self, node: &ShiftExpr) -> Result<()> {
+        self.add_expr(&node.base)?;
+        for (tok, other) in &node.following {
+            self.add_expr(other)?;
+            self.emit(Instruction::Binary(binary_op(tok)), node.span);
+        }
+        Ok(())
+    }
+
+    fn add_expr(&mut self, node: &AddExpr) -> Result<()> {
+        self.mul_expr(&node.base)?;
+        for (tok, other) in &node.following {
+            self.mul_expr(other)?;
+            self.emit(Instruction::Binary(binary_op(tok)), node.span);
+        }
+        Ok(())
+    }
+
+    fn mul_expr(&mut self, node: &MulExpr) -> Result<()> {
+        self.unary_expr(&node.base)?;
+        for (tok, other) in &node.following {
+            self.unary_expr(other)?;
+            self.emit(Instruction::Binary(binary_op(tok)), node.span);
+        }
+        Ok(())
+    }
+
+    fn unary_expr(&mut self, node: &UnaryExpr) -> Result<()> {
+        match node {
+            UnaryExpr::Unary {
+                span,
+                operator: TokenKind::Not,
+                expr,
+            } => {
+                self.unary_expr(expr)?;
+                self.emit(Instruction::Not, *span);
+            }
+            UnaryExpr::Unary {
+                span,
+                operator,
+                expr,
+            } => {
+                // `-x` is computed as `0 - x`
+                let zero = self.constant(Constant::Number(0.into()));
+                self.emit(Instruction::Constant(zero), *span);
+                self.unary_expr(expr)?;
+                self.emit(Instruction::Binary(binary_op(operator)), *span);
+            }
+            UnaryExpr::Done(node) => self.exp_expr(node)?,
+        }
+        Ok(())
+    }
+
+    fn exp_expr(&mut self, node: &ExpExpr) -> Result<()> {
+        self.assign_expr(&node.base)?;
+        if let Some(exponent) = &node.exponent {
+            self.unary_expr(exponent)?;
+            self.emit(Instruction::Binary(BinaryOp::Pow), node.span);
+        }
+        Ok(())
+    }
+
+    fn assign_expr(&mut self, node: &AssignExpr) -> Result<()> {
+        let (tok, right) = match &node.right {
+            Some((tok, right)) => (tok, right),
+            None => return self.call_expr(&node.left),
+        };
+        let op = match tok {
+            TokenKind::Assign => AssignOp::Assign,
+            TokenKind::StarAssign => AssignOp::Binary(BinaryOp::Mul),
+            TokenKind::SlashAssign => AssignOp::Binary(BinaryOp::Div),
+            TokenKind::BackslashAssign => AssignOp::Binary(BinaryOp::DivFloor),
+            TokenKind::RemAssign => AssignOp::Binary(BinaryOp::Rem),
+            TokenKind::PlusAssign => AssignOp::Binary(BinaryOp::Add),
+            TokenKind::MinusAssign => AssignOp::Binary(BinaryOp::Sub),
+            TokenKind::ShiftLeftAssign => AssignOp::Binary(BinaryOp::ShiftLeft),
+            TokenKind::ShiftRightAssign => AssignOp::Binary(BinaryOp::ShiftRight),
+            TokenKind::BitAndAssign => AssignOp::Binary(BinaryOp::And),
+            TokenKind::BitXorAssign => AssignOp::Binary(BinaryOp::Xor),
+            TokenKind::BitOrAssign => AssignOp::Binary(BinaryOp::Or),
+            TokenKind::PowAssign => AssignOp::Binary(BinaryOp::Pow),
+            TokenKind::CoalesceAssign => unsupported!("'??='", node.span),
+            _ => unreachable!(),
+        };
+        if let (AssignOp::Assign, Atom::List(_), [], []) = (
+            &op,
+            &node.left.base.base,
+            node.left.base.following.as_slice(),
+            node.left.following.as_slice(),
+        ) {
+            unsupported!("destructuring", node.span);
+        }
+
+        // the last member access or index is assigned separately, so that the item or field
+        // can be replaced
+        let part = match (
+            node.left.following.split_last(),
+            node.left.base.following.split_last(),
+        ) {
+            (Some((CallPart::Member(part), parts)), _) => {
+                self.call_parts(&node.left, parts)?;
+                part
+            }
+            (None, Some((part, parts))) => {
+                self.member_parts(&node.left.base, parts)?;
+                part
+            }
+            (None, None) => {
+                let Atom::Identifier { name, .. } = &node.left.base.base else {
+                    unreachable!("the parser only allows assignable targets")
+                };
+                return self.assign_var(name, right, op, node.span);
+            }
+            (Some((CallPart::Args(_), _)), _) => {
+                unreachable!("the parser only allows assignable targets")
+            }
+        };
+        self.emit(Instruction::Nip, node.span);
+        match part {
+            MemberPart::Field(ident) => {
+                self.expression(right)?;
+                let name = self.name(ident);
+                self.emit(Instruction::AssignField { name, op }, node.span);
+            }
+            MemberPart::OptionalField(_) => {
+                unreachable!("the parser only allows assignable targets")
+            }
+            MemberPart::Index(index) => {
+                self.expression(index)?;
+                self.expression(right)?;
+                self.emit(Instruction::AssignIndex(op), node.span);
+            }
+        }
+        Ok(())
+    }
+
+    fn assign_var(
+        &mut self,
+        name: &str,
+        right: &Expression,
+        op: AssignOp,
+        span: Span,
+    ) -> Result<()> {
+        match self.lookup(name) {
+            Some((_, _, Some(declared))) => {
+                let name = self.name(name);
+                self.emit(
+                    Instruction::ReassignConst {
+                        name,
+                        line: declared.start.line,
+                        column: declared.start.column,
+                    },
+                    span,
+                );
+            }
+            Some((frame, slot, None)) => {
+                self.expression(right)?;
+                let name = self.name(name);
+                self.emit(
+                    Instruction::AssignVar {
+                        frame,
+                        slot,
+                        name,
+                        op,
+                    },
+                    span,
+                );
+            }
+            None if self.built_ins.contains(&name) => {
+                unsupported!("reassigning built-in functions", span)
+            }
+            None => {
+                let name = self.name(name);
+                self.emit(Instruction::LoadUndefined(name), span);
+            }
+        }
+        Ok(())
+    }
+
+    fn call_expr(&mut self, node: &CallExpr) -> Result<()> {
+        self.call_parts(node, &node.following)?;
+        self.emit(Instruction::Nip, node.span);
+        Ok(())
+    }
+
+    /// Leaves the parent and the base of the call chain on the stack
+    fn call_parts(&mut self, node: &CallExpr, parts: &[CallPart]) -> Result<()> {
+        self.member_parts(&node.base, &node.base.following)?;
+        let mut callee = match (node.base.following.last(), &node.base.base) {
+            (Some(MemberPart::Field(ident) | MemberPart::OptionalField(ident)), _) => {
+                Some(ident.as_str())
+            }
+            (None, Atom::Identifier { name, .. }) => Some(name.as_str()),
+            _ => None,
+        };
+        for part in parts {
+            match part {
+                CallPart::Args(args) => {
+                    for arg in args {
+                        if arg.name.is_some() {
+                            unsupported!("named arguments", arg.span);
+                        }
+                        self.expression(&arg.expr)?;
+                    }
+                    let callee = callee.take().map(|name| self.name(name));
+                    self.emit(
+                        Instruction::Call {
+                            args: args.len(),
+                            callee,
+                        },
+                        node.span,
+                    );
+                }
+                CallPart::Member(MemberPart::Field(ident)) => {
+                    callee = Some(ident);
+                    let name = self.name(ident);
+                    self.emit(Instruction::Field(name), node.span);
+                }
+                CallPart::Member(MemberPart::OptionalField(_)) => {
+                    unsupported!("optional chaining", node.span)
+                }
+                CallPart::Member(MemberPart::Index(expr)) => {
+                    callee = None;
+                    self.expression(expr)?;
+                    self.emit(Instruction::Index, node.span);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Leaves the parent and the base of the member chain on the stack
+    fn member_parts(&mut self, node: &MemberExpr, parts: &[MemberPart]) -> Result<()> {
+        self.emit(Instruction::Null, node.span);
+        self.atom(&node.base)?;
+        for part in parts {
+            match part {
+                MemberPart::Field(ident) => {
+                    let name = self.name(ident);
+                    self.emit(Instruction::Field(name), node.span);
+                }
+                MemberPart::OptionalField(_) => unsupported!("optional chaining", node.span),
+                MemberPart::Index(expr) => {
+                    self.expression(expr)?;
+                    self.emit(Instruction::Index, node.span);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn atom(&mut self, node: &Atom) -> Result<()> {
+        match node {
+            Atom::Number(val) => {
+                let idx = self.constant(Constant::Number(*val));
+                self.emit(Instruction::Constant(idx), Span::default());
+            }
+            Atom::Bool(true) => {
+                self.emit(Instruction::True, Span::default());
+            }
+            Atom::Bool(false) => {
+                self.emit(Instruction::False, Span::default());
+            }
+            Atom::String(val) => {
+                let idx = self.constant(Constant::String(val.clone()));
+                self.emit(Instruction::Constant(idx), Span::default());
+            }
+            Atom::InterpolatedString(node) => {
+                for part in &node.parts {
+                    match part {
+                        StringPart::Literal(literal) => {
+                            let idx = self.constant(Constant::String(literal.clone()));
+                            self.emit(Instruction::Constant(idx), Span::default());
+                        }
+                        StringPart::Expr(expr) => self.expression(expr)?,
+                    }
+                }
+                self.emit(Instruction::Concat(node.parts.len()), node.span);
+            }
+            Atom::Null => {
+                self.emit(Instruction::Null, Span::default());
+            }
+            Atom::Identifier { span, name } if name == "this" => unsupported!("'this'", *span),
+            Atom::Identifier { span, name } => {
+                let instruction = self.resolve(name);
+                self.emit(instruction, *span);
+            }
+            Atom::Super { span, .. } => unsupported!("'super'", *span),
+            Atom::Expr(node) => self.expression(node)?,
+            Atom::List(nodes) => {
+                for node in nodes {
+                    self.expression(node)?;
+                }
+                self.emit(Instruction::List(nodes.len()), Span::default());
+            }
+            Atom::Comprehension(node) => unsupported!("list comprehensions", node.span),
+            Atom::Map(nodes) => {
+                self.emit(Instruction::Map, Span::default());
+                for node in nodes {
+                    self.expression(&node.key)?;
+                    self.expression(&node.value)?;
+                    self.emit(Instruction::MapInsert, node.span);
+                }
+            }
+            Atom::IfExpr(node) => self.if_expr(node)?,
+            Atom::ForExpr(node) => self.for_expr(node)?,
+            Atom::WhileExpr(node) => self.while_expr(node)?,
+            Atom::LoopExpr(node) => self.loop_expr(node)?,
+            Atom::FunExpr(node) if node.is_generator => unsupported!("generators", node.span),
+            Atom::FunExpr(node) => self.closure(&node.args, &node.block, node.span)?,
+            Atom::ClassExpr(node) => unsupported!("classes", node.span),
+            Atom::TryExpr(node) => self.try_expr(node)?,
+            Atom::MatchExpr(node) => unsupported!("match expressions", node.span),
+            Atom::BlockExpr(node) => self.block(node, &[])?,
+        }
+        Ok(())
+    }
+
+    fn if_expr(&mut self, node: &IfExpr) -> Result<()> {
+        self.expression(&node.cond)?;
+        let else_jump = self.emit(Instruction::JumpIfFalse(0), node.span);
+        self.block(&node.block, &[])?;
+        let end_jump = self.emit(Instruction::Jump(0), node.span);
+        self.patch(else_jump);
+        match &node.else_block {
+            Some(block) => self.block(block, &[])?,
+            None => {
+                self.emit(Instruction::Null, node.span);
+            }
+        }
+        self.patch(end_jump);
+        Ok(())
+    }
+
+    /// Returns the index of the loop a `break` or `continue` refers to, and ends the loops
+    /// nested inside of it so that their state is discarded
+    fn leave_loops(&mut self, label: &Option<String>, span: Span) -> Option<usize> {
+        let loops = &self.context().loops;
+        let target = loops
+            .iter()
+            .rposition(|current| label.is_none() || current.label == *label)?;
+        for _ in target + 1..loops.len() {
+            self.emit(Instruction::LoopEnd, span);
+        }
+        Some(target)
+    }
+
+    /// Makes `break` and `continue` refer to a new loop, `continue` jumps to `continue_target`
+    fn begin_loop(&mut self, label: &Option<String>, continue_target: usize) {
+        self.context().loops.push(Loop {
+            label: label.clone(),
+            continue_target,
+            breaks: vec![],
+        });
+    }
+
+    /// Pushes the null a loop results in when it ends normally and ends the loop
+    fn end_loop(&mut self, span: Span) {
+        let current = self.context().loops.pop().unwrap();
+        self.emit(Instruction::Null, span);
+        for idx in current.breaks {
+            self.patch(idx);
+        }
+        self.emit(Instruction::LoopEnd, span);
+    }
+
+    fn for_expr(&mut self, node: &ForExpr) -> Result<()> {
+        let Target::Ident(ident) = &node.target else {
+            unsupported!("destructuring", node.span)
+        };
+        self.expression(&node.iter)?;
+        self.emit(Instruction::IterStart, node.span);
+        self.emit(Instruction::LoopStart, node.span);
+        let next = self.emit(Instruction::IterNext(0), node.span);
+        self.begin_loop(&node.label, next);
+        self.block(&node.block, slice::from_ref(ident))?;
+        self.emit(Instruction::Pop, node.span);
+        self.emit(Instruction::Jump(next), node.span);
+        self.patch(next);
+        self.end_loop(node.span);
+        self.emit(Instruction::IterEnd, node.span);
+        Ok(())
+    }
+
+    fn while_expr(&mut self, node: &WhileExpr) -> Result<()> {
+        self.emit(Instruction::LoopStart, node.span);
+        let cond = self.here();
+        self.begin_loop(&node.label, cond);
+        self.expression(&node.cond)?;
+        let exit = self.emit(Instruction::JumpIfFalse(0), node.span);
+        self.block(&node.block, &[])?;
+        self.emit(Instruction::Pop, node.span);
+        self.emit(Instruction::Jump(cond), node.span);
+        self.patch(exit);
+        self.end_loop(node.span);
+        Ok(())
+    }
+
+    fn loop_expr(&mut self, node: &LoopExpr) -> Result<()> {
+        self.emit(Instruction::LoopStart, node.span);
+        let start = self.here();
+        self.begin_loop(&node.label, start);
+        self.block(&node.block, &[])?;
+        self.emit(Instruction::Pop, node.span);
+        self.emit(Instruction::Jump(start), node.span);
+        self.end_loop(node.span);
+        Ok(())
+    }
+
+    fn try_expr(&mut self, node: &TryExpr) -> Result<()> {
+        if node.finally_block.is_some() {
+            unsupported!("'finally'", node.span);
+        }
+        let [clause] = node.catches.as_slice() else {
+            unsupported!("multiple catch clauses", node.span);
+        };
+        if clause.filter.is_some() {
+            unsupported!("typed catch clauses", clause.span);
+        }
+        let handler = self.emit(Instruction::TryStart(0), node.span);
+        self.block(&node.try_block, &[])?;
+        self.emit(Instruction::TryEnd, node.span);
+        let end = self.emit(Instruction::Jump(0), node.span);
+        self.patch(handler);
+        self.block(&clause.block, slice::from_ref(&clause.ident))?;
+        self.patch(end);
+        Ok(())
+    }
+}
+
+fn binary_op(tok: &TokenKind) -> BinaryOp {
+    match tok {
+        TokenKind::ShiftLeft => BinaryOp::ShiftLeft,
+        TokenKind::ShiftRight => BinaryOp::ShiftRight,
+        TokenKind::Plus => BinaryOp::Add,
+        TokenKind::Minus => BinaryOp::Sub,
+        TokenKind::Star => BinaryOp::Mul,
+        TokenKind::Slash => BinaryOp::Div,
+        TokenKind::Rem => BinaryOp::Rem,
+        TokenKind::Backslash => BinaryOp::DivFloor,
+        _ => unreachable!(),
+    }
+}